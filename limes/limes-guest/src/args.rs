@@ -0,0 +1,74 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+
+/// Builds the argument of a function from the arguments of the call
+pub trait FromArgs: Sized {
+    fn from_args(args: String) -> Result<Self, Error>;
+}
+
+impl FromArgs for String {
+    fn from_args(args: String) -> Result<Self, Error> {
+        Ok(args)
+    }
+}
+
+/// The UTF-8 bytes of the arguments
+impl FromArgs for Vec<u8> {
+    fn from_args(args: String) -> Result<Self, Error> {
+        Ok(args.into_bytes())
+    }
+}
+
+/// The raw arguments of the call, with helpers for the usual formats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Args(String);
+
+impl Args {
+    pub fn new(args: impl Into<String>) -> Self {
+        Args(args.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// The arguments split on `separator`, trimmed, e.g. `TCP, 127.0.0.1:80` split on `,`
+    pub fn fields(&self, separator: char) -> Vec<&str> {
+        self.0.split(separator).map(str::trim).collect()
+    }
+
+    /// The `index` field, an [`Error::invalid_args`] when it is missing
+    pub fn field(&self, separator: char, index: usize) -> Result<&str, Error> {
+        self.fields(separator)
+            .get(index)
+            .copied()
+            .ok_or_else(|| Error::invalid_args(format!("missing field {} of `{}`", index, self.0)))
+    }
+
+    /// The arguments deserialized from JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_str(&self.0).map_err(Error::invalid_args)
+    }
+}
+
+impl FromArgs for Args {
+    fn from_args(args: String) -> Result<Self, Error> {
+        Ok(Args(args))
+    }
+}
+
+/// A JSON value: as argument it is deserialized from the call arguments, as result serialized
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromArgs for Json<T> {
+    fn from_args(args: String) -> Result<Self, Error> {
+        serde_json::from_str(&args)
+            .map(Json)
+            .map_err(Error::invalid_args)
+    }
+}
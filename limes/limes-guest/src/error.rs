@@ -0,0 +1,65 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Error returned by a function, a machine readable `code` and a message for humans
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Error {
+    pub code: String,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(code: impl Into<String>, message: impl fmt::Display) -> Self {
+        Error {
+            code: code.into(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The call arguments could not be read as the argument of the function
+    pub fn invalid_args(message: impl fmt::Display) -> Self {
+        Error::new("invalid_args", message)
+    }
+
+    /// Any other failure of the function
+    pub fn internal(message: impl fmt::Display) -> Self {
+        Error::new("internal", message)
+    }
+
+    /// `{"error":{"code":..,"message":..}}`, the line written on stderr when the function fails
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "error": self }).to_string()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::internal(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::internal(message)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::new("io", error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::new("json", error)
+    }
+}
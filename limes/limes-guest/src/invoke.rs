@@ -0,0 +1,13 @@
+// Calls to other functions of the runtime through `limes:invoke/call`, the callees are those the
+// `[invoke]` section of the manifest allows
+use crate::bindings::limes::invoke::call as host;
+use crate::Error;
+
+/// Runs `function_id` with `args` and returns its response. The callee has its own limits and
+/// runs within the deadline of the caller, a refused or failed call is an `invoke` error
+pub fn call(function_id: &str, args: &str) -> Result<String, Error> {
+    if !cfg!(target_arch = "wasm32") {
+        return Err(Error::new("invoke", "no runtime to call outside wasm"));
+    }
+    host::call(function_id, args).map_err(|message| Error::new("invoke", message))
+}
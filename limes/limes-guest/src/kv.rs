@@ -0,0 +1,63 @@
+// The key-value namespace the runtime keeps for the function through `limes:keyvalue/store`,
+// which one and its quota are set by the `[kv]` section of the manifest
+use crate::bindings::limes::keyvalue::store;
+use crate::Error;
+
+/// Value stored under `key`
+pub fn get(key: &str) -> Result<Option<Vec<u8>>, Error> {
+    host()?;
+    Ok(store::get(key)?)
+}
+
+/// Stores `value` under `key`, refused with `quota_exceeded` past the quota of the namespace
+pub fn set(key: &str, value: impl AsRef<[u8]>) -> Result<(), Error> {
+    host()?;
+    Ok(store::set(key, value.as_ref())?)
+}
+
+pub fn delete(key: &str) -> Result<(), Error> {
+    host()?;
+    Ok(store::delete(key)?)
+}
+
+pub fn exists(key: &str) -> Result<bool, Error> {
+    host()?;
+    Ok(store::exists(key)?)
+}
+
+/// A page of keys in order, after `cursor` when given, with the cursor of the next page
+pub fn list_keys(cursor: Option<&str>) -> Result<(Vec<String>, Option<String>), Error> {
+    host()?;
+    let page = store::list_keys(cursor)?;
+    Ok((page.keys, page.cursor))
+}
+
+// Outside wasm, e.g. in the native tests of a guest, there is no store to reach
+fn host() -> Result<(), Error> {
+    match cfg!(target_arch = "wasm32") {
+        true => Ok(()),
+        false => Err(Error::new(
+            "no_such_store",
+            "no key-value store outside wasm",
+        )),
+    }
+}
+
+impl From<store::Error> for Error {
+    fn from(error: store::Error) -> Self {
+        match error {
+            store::Error::NoSuchStore => {
+                Error::new("no_such_store", "the function has no key-value namespace")
+            }
+            store::Error::AccessDenied => Error::new(
+                "access_denied",
+                "the key-value namespace refused the access",
+            ),
+            store::Error::QuotaExceeded => Error::new(
+                "quota_exceeded",
+                "the key-value namespace is over its quota",
+            ),
+            store::Error::Other(message) => Error::new("kv", message),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! SDK to write Limes functions.
+//!
+//! `#[limes_guest::function]` exports a plain Rust function as the `component:run/run` the runtime
+//! calls. Its argument is built from the call arguments through [`FromArgs`] and its returned value
+//! sent back through [`IntoResponse`], so a function can take and return typed values:
+//!
+//! ```ignore
+//! use limes_guest::{Error, Json};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Request {
+//!     n: u64,
+//! }
+//!
+//! #[limes_guest::function]
+//! fn square(Json(request): Json<Request>) -> Result<Json<u64>, Error> {
+//!     limes_guest::log::info(format!("squaring {}", request.n));
+//!     match request.n.checked_mul(request.n) {
+//!         Some(square) => Ok(Json(square)),
+//!         None => Err(Error::new("overflow", "the square does not fit in a u64")),
+//!     }
+//! }
+//! ```
+//!
+//! The guest crate is a `cdylib` depending on `limes-guest` and `wit-bindgen`, built for
+//! `wasm32-wasip2`. The attribute is used once per crate.
+pub mod args;
+pub mod error;
+pub mod invoke;
+pub mod kv;
+pub mod log;
+pub mod response;
+
+// Imports of the host interfaces, generated once here for every guest using the SDK
+mod bindings {
+    wit_bindgen::generate!({
+        inline: "
+            package limes:guest;
+
+            world imports {
+                import limes:log/logging;
+                import limes:keyvalue/store;
+                import limes:invoke/call;
+            }
+        ",
+        path: ["../wit/log.wit", "../wit/keyvalue.wit", "../wit/invoke.wit"],
+        world: "limes:guest/imports",
+        generate_all,
+    });
+}
+
+pub use args::{Args, FromArgs, Json};
+pub use error::Error;
+pub use limes_macros::guest_function as function;
+pub use response::IntoResponse;
+
+/// Value of the `key` environment variable, set by the `env` of the function manifest
+pub fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+// Used by the code generated by `#[limes_guest::function]`
+#[doc(hidden)]
+pub mod __private {
+    use crate::Error;
+
+    // Runs the function, an error is written on stderr as one JSON line and fails the call
+    pub fn run(args: String, function: impl FnOnce(String) -> Result<String, Error>) -> String {
+        match function(args) {
+            Ok(response) => response,
+            Err(error) => {
+                eprintln!("{}", error.to_json());
+                panic!("{}", error)
+            }
+        }
+    }
+}
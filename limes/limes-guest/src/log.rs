@@ -0,0 +1,54 @@
+// Leveled lines written to the host log through `limes:log/logging`, tagged by the runtime with
+// the function and invocation ids. The `[log]` section of the manifest sets the lowest level
+// kept and the lines allowed per invocation
+use crate::bindings::limes::log::logging;
+use std::fmt::Display;
+
+pub use crate::bindings::limes::log::logging::Level;
+
+/// Writes `message` at `level` along with `key=value` fields
+pub fn log(level: Level, message: impl Display, fields: &[(&str, &dyn Display)]) {
+    let fields: Vec<logging::Field> = fields
+        .iter()
+        .map(|(key, value)| logging::Field {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .collect();
+    write(level, &message.to_string(), &fields);
+}
+
+pub fn trace(message: impl Display) {
+    log(Level::Trace, message, &[]);
+}
+
+pub fn debug(message: impl Display) {
+    log(Level::Debug, message, &[]);
+}
+
+pub fn info(message: impl Display) {
+    log(Level::Info, message, &[]);
+}
+
+pub fn warn(message: impl Display) {
+    log(Level::Warn, message, &[]);
+}
+
+pub fn error(message: impl Display) {
+    log(Level::Error, message, &[]);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(level: Level, message: &str, fields: &[logging::Field]) {
+    logging::log(level, message, fields);
+}
+
+// Without a host, e.g. in the native tests of a guest, the lines go to stderr
+#[cfg(not(target_arch = "wasm32"))]
+fn write(level: Level, message: &str, fields: &[logging::Field]) {
+    let fields: String = fields
+        .iter()
+        .map(|field| format!(" {}={}", field.key, field.value))
+        .collect();
+    eprintln!("{:?} {}{}", level, message, fields);
+}
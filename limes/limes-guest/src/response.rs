@@ -0,0 +1,47 @@
+use crate::{Error, Json};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde::Serialize;
+
+/// Turns the value returned by a function into the result of the call
+pub trait IntoResponse {
+    fn into_response(self) -> Result<String, Error>;
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Result<String, Error> {
+        Ok(self)
+    }
+}
+
+impl IntoResponse for &str {
+    fn into_response(self) -> Result<String, Error> {
+        Ok(self.to_string())
+    }
+}
+
+/// Sent back base64 encoded, `limes fn exec --base64` decodes it
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Result<String, Error> {
+        Ok(BASE64_STANDARD.encode(self))
+    }
+}
+
+/// An empty result
+impl IntoResponse for () {
+    fn into_response(self) -> Result<String, Error> {
+        Ok(String::new())
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Result<String, Error> {
+        serde_json::to_string(&self.0).map_err(Error::internal)
+    }
+}
+
+/// An `Err` fails the call, see [`Error`]
+impl<T: IntoResponse, E: Into<Error>> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Result<String, Error> {
+        self.map_err(Into::into)?.into_response()
+    }
+}
@@ -0,0 +1,151 @@
+// Same stand-in of `wit_bindgen` as the limes-macros tests, `call_run` calls the exported run
+use limes_guest::{Args, Error, FromArgs, IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+mod wit_bindgen {
+    macro_rules! generate {
+        ({ inline: $wit:literal }) => {
+            pub mod exports {
+                pub mod component {
+                    pub mod run {
+                        pub mod run {
+                            pub trait Guest {
+                                fn run(args: String) -> String;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+    }
+    pub(crate) use generate;
+}
+
+macro_rules! export {
+    ($component:ident) => {
+        pub fn call_run(args: &str) -> String {
+            <$component as self::exports::component::run::run::Guest>::run(args.to_string())
+        }
+    };
+}
+
+#[derive(Deserialize)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+#[derive(Serialize)]
+struct Sum {
+    sum: i64,
+}
+
+mod json_function {
+    use crate::wit_bindgen;
+    use crate::{Point, Sum};
+    use limes_guest::Json;
+
+    #[limes_guest::function]
+    fn add(Json(point): Json<Point>) -> Json<Sum> {
+        Json(Sum {
+            sum: point.x + point.y,
+        })
+    }
+}
+
+mod args_function {
+    use crate::wit_bindgen;
+    use limes_guest::{Args, Error};
+
+    #[limes_guest::function]
+    fn second(args: Args) -> Result<String, Error> {
+        Ok(args.field(',', 1)?.to_uppercase())
+    }
+}
+
+mod unit_function {
+    use crate::wit_bindgen;
+
+    #[limes_guest::function]
+    fn nothing() {
+        limes_guest::log::info("nothing to do");
+    }
+}
+
+mod bytes_function {
+    use crate::wit_bindgen;
+
+    #[limes_guest::function]
+    fn reverse(bytes: Vec<u8>) -> Vec<u8> {
+        bytes.into_iter().rev().collect()
+    }
+}
+
+mod error_function {
+    use crate::wit_bindgen;
+    use limes_guest::Error;
+
+    #[limes_guest::function]
+    fn parse(args: String) -> Result<String, Error> {
+        let n = args.parse::<i64>().map_err(Error::invalid_args)?;
+        Ok((n * 2).to_string())
+    }
+}
+
+fn panic_message(call: impl FnOnce() -> String + std::panic::UnwindSafe) -> String {
+    let error = std::panic::catch_unwind(call).unwrap_err();
+    error.downcast_ref::<String>().unwrap().clone()
+}
+
+#[test]
+fn guest_json_arguments_and_result() {
+    assert_eq!(
+        json_function::call_run(r#"{"x": 1, "y": 2}"#),
+        r#"{"sum":3}"#
+    );
+    let message = panic_message(|| json_function::call_run("not json"));
+    assert!(message.starts_with("invalid_args: "));
+}
+
+#[test]
+fn guest_raw_arguments() {
+    assert_eq!(args_function::call_run("TCP, udp"), "UDP");
+    assert_eq!(
+        panic_message(|| args_function::call_run("TCP")),
+        "invalid_args: missing field 1 of `TCP`"
+    );
+    assert_eq!(unit_function::call_run("ignored"), "");
+    assert_eq!(bytes_function::call_run("olleh"), "aGVsbG8=");
+}
+
+#[test]
+fn guest_errors() {
+    assert_eq!(error_function::call_run("21"), "42");
+    assert_eq!(
+        panic_message(|| error_function::call_run("x")),
+        "invalid_args: invalid digit found in string"
+    );
+
+    let error = Error::new("overflow", "too big");
+    assert_eq!(error.to_string(), "overflow: too big");
+    assert_eq!(
+        error.to_json(),
+        r#"{"error":{"code":"overflow","message":"too big"}}"#
+    );
+    let result: Result<String, &str> = Err("failed");
+    assert_eq!(
+        result.into_response().unwrap_err(),
+        Error::internal("failed")
+    );
+}
+
+#[test]
+fn guest_args_helpers() {
+    let args = Args::from_args(" a , b ,c".to_string()).unwrap();
+    assert_eq!(args.fields(','), vec!["a", "b", "c"]);
+    assert_eq!(args.as_str(), " a , b ,c");
+    let Json(numbers) = Json::<Vec<u8>>::from_args("[1, 2]".to_string()).unwrap();
+    assert_eq!(numbers, vec![1, 2]);
+    assert_eq!(Args::new("[3]").json::<Vec<u8>>().unwrap(), vec![3]);
+    assert_eq!(().into_response().unwrap(), "");
+}
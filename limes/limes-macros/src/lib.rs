@@ -0,0 +1,330 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, FnArg, GenericArgument, ItemFn, PathArguments, ReturnType, Type};
+
+/// WIT of every Limes function, the runtime calls `component:run/run`
+const RUN_WIT: &str = r"
+    package component:run;
+
+    interface run {
+        run: func(args: string) -> string;
+    }
+
+    world runnable {
+        export run;
+    }
+";
+
+/// Exports the function as the `component:run/run` of the component, generating the
+/// `wit_bindgen` boilerplate. Use it once per crate, in `lib.rs`, and add `wit-bindgen` to the
+/// dependencies of the guest.
+///
+/// The function takes no argument or one of `String`, `&str`, `Vec<u8>` and `&[u8]`, the bytes
+/// being the UTF-8 bytes of the call arguments. It returns `String`, or `Vec<u8>` which is sent
+/// back base64 encoded, optionally wrapped in a `Result` whose error is `Display`: an `Err`
+/// makes the call fail with the error written on stderr.
+///
+/// ```ignore
+/// #[limes::function]
+/// fn greet(name: &str) -> Result<String, String> {
+///     match name.is_empty() {
+///         true => Err("missing name".to_string()),
+///         false => Ok(format!("Hello, {}", name)),
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = TokenStream2::from(attr);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(attr, function)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// The `#[limes_guest::function]` of the `limes-guest` SDK: the argument is any
+/// `limes_guest::FromArgs` and the returned value any `limes_guest::IntoResponse`. Use it through
+/// `limes-guest`, the generated code refers to that crate.
+#[doc(hidden)]
+#[proc_macro_attribute]
+pub fn guest_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = TokenStream2::from(attr);
+    let function = parse_macro_input!(item as ItemFn);
+    expand_guest(attr, function)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+enum Input {
+    None,
+    String,
+    Str,
+    Bytes,
+    ByteSlice,
+}
+
+enum Output {
+    String,
+    Bytes,
+}
+
+fn expand(attr: TokenStream2, function: ItemFn) -> syn::Result<TokenStream2> {
+    check_signature(attr, &function)?;
+    let signature = &function.sig;
+    let input = parse_input(&function)?;
+    let (output, fallible) = parse_output(&signature.output)?;
+
+    let name = &signature.ident;
+    let argument = match input {
+        Input::None => quote! {},
+        Input::String => quote! { args },
+        Input::Str => quote! { &args },
+        Input::Bytes => quote! { args.into_bytes() },
+        Input::ByteSlice => quote! { args.as_bytes() },
+    };
+    let call = quote! { self::#name(#argument) };
+    let value = match fallible {
+        true => quote! {
+            match #call {
+                ::core::result::Result::Ok(value) => value,
+                ::core::result::Result::Err(e) => ::core::panic!("{}", e),
+            }
+        },
+        false => call,
+    };
+    let body = match output {
+        Output::String => quote! { #value },
+        Output::Bytes => {
+            let base64 = base64_encoder();
+            quote! {
+                #base64
+                __limes_base64(&#value)
+            }
+        }
+    };
+    let unused_args = match input {
+        Input::None => quote! { let _ = args; },
+        _ => quote! {},
+    };
+
+    Ok(export_run(
+        &function,
+        quote! {
+            #unused_args
+            #body
+        },
+    ))
+}
+
+fn expand_guest(attr: TokenStream2, function: ItemFn) -> syn::Result<TokenStream2> {
+    check_signature(attr, &function)?;
+    let signature = &function.sig;
+    let name = &signature.ident;
+    let (args, call) = match signature.inputs.first() {
+        None => (quote! { _ }, quote! { self::#name() }),
+        Some(FnArg::Typed(argument)) => {
+            if let Type::Reference(reference) = &*argument.ty {
+                return Err(syn::Error::new(
+                    reference.span(),
+                    "take the arguments by value, e.g. String, Vec<u8>, Args or Json<T>",
+                ));
+            }
+            let ty = &argument.ty;
+            let call = quote! { self::#name(<#ty as ::limes_guest::FromArgs>::from_args(args)?) };
+            (quote! { args }, call)
+        }
+        Some(FnArg::Receiver(_)) => unreachable!("rejected by check_signature"),
+    };
+    Ok(export_run(
+        &function,
+        quote! {
+            ::limes_guest::__private::run(args, |#args| {
+                ::limes_guest::IntoResponse::into_response(#call)
+            })
+        },
+    ))
+}
+
+// Rejections shared by every attribute: arguments, async, generics, more than one argument, self
+fn check_signature(attr: TokenStream2, function: &ItemFn) -> syn::Result<()> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new(
+            attr.span(),
+            "limes::function takes no arguments",
+        ));
+    }
+    let signature = &function.sig;
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "a limes function can not be async",
+        ));
+    }
+    if !signature.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            signature.generics.span(),
+            "a limes function can not be generic",
+        ));
+    }
+    if signature.inputs.len() > 1 {
+        return Err(syn::Error::new(
+            signature.inputs.span(),
+            "a limes function takes at most one argument",
+        ));
+    }
+    if let Some(FnArg::Receiver(receiver)) = signature.inputs.first() {
+        return Err(syn::Error::new(
+            receiver.span(),
+            "a limes function can not take self",
+        ));
+    }
+    Ok(())
+}
+
+// The function followed by the component exporting `run`, whose body sees `args: String`
+fn export_run(function: &ItemFn, body: TokenStream2) -> TokenStream2 {
+    quote! {
+        #function
+
+        wit_bindgen::generate!({
+            inline: #RUN_WIT
+        });
+
+        #[doc(hidden)]
+        struct __LimesFunction;
+
+        impl self::exports::component::run::run::Guest for __LimesFunction {
+            fn run(args: ::std::string::String) -> ::std::string::String {
+                #body
+            }
+        }
+
+        export!(__LimesFunction);
+    }
+}
+
+fn parse_input(function: &ItemFn) -> syn::Result<Input> {
+    let ty = match function.sig.inputs.first() {
+        Some(FnArg::Typed(argument)) => &*argument.ty,
+        _ => return Ok(Input::None),
+    };
+    if is_path(ty, "String") {
+        return Ok(Input::String);
+    }
+    if is_vec_u8(ty) {
+        return Ok(Input::Bytes);
+    }
+    if let Type::Reference(reference) = ty {
+        if is_path(&reference.elem, "str") {
+            return Ok(Input::Str);
+        }
+        if let Type::Slice(slice) = &*reference.elem {
+            if is_path(&slice.elem, "u8") {
+                return Ok(Input::ByteSlice);
+            }
+        }
+    }
+    Err(syn::Error::new(
+        ty.span(),
+        "the argument must be String, &str, Vec<u8> or &[u8]",
+    ))
+}
+
+// The returned value and whether it is wrapped in a Result
+fn parse_output(output: &ReturnType) -> syn::Result<(Output, bool)> {
+    let ty = match output {
+        ReturnType::Type(_, ty) => &**ty,
+        ReturnType::Default => {
+            return Err(syn::Error::new(
+                output.span(),
+                "a limes function must return String or Vec<u8>, optionally in a Result",
+            ))
+        }
+    };
+    if let Some(ok) = result_ok_type(ty) {
+        return plain_output(ok).map(|output| (output, true));
+    }
+    plain_output(ty).map(|output| (output, false))
+}
+
+fn plain_output(ty: &Type) -> syn::Result<Output> {
+    if is_path(ty, "String") {
+        return Ok(Output::String);
+    }
+    if is_vec_u8(ty) {
+        return Ok(Output::Bytes);
+    }
+    Err(syn::Error::new(
+        ty.span(),
+        "a limes function must return String or Vec<u8>, optionally in a Result",
+    ))
+}
+
+// `T` of `Result<T, E>`
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let arguments = last_segment_arguments(ty, "Result")?;
+    match arguments.first()? {
+        GenericArgument::Type(ok) if arguments.len() == 2 => Some(ok),
+        _ => None,
+    }
+}
+
+fn is_vec_u8(ty: &Type) -> bool {
+    last_segment_arguments(ty, "Vec").is_some_and(|arguments| {
+        matches!(arguments.first(), Some(GenericArgument::Type(item)) if is_path(item, "u8"))
+    })
+}
+
+// A path ending in `name` without generic arguments, e.g. `String` or `std::string::String`
+fn is_path(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name && segment.arguments.is_none()),
+        _ => false,
+    }
+}
+
+fn last_segment_arguments<'a>(
+    ty: &'a Type,
+    name: &str,
+) -> Option<&'a syn::punctuated::Punctuated<GenericArgument, syn::Token![,]>> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => Some(&arguments.args),
+        _ => None,
+    }
+}
+
+// Standard base64 with padding, so the guest needs no other dependency
+fn base64_encoder() -> TokenStream2 {
+    quote! {
+        fn __limes_base64(bytes: &[u8]) -> ::std::string::String {
+            const TABLE: &[u8; 64] =
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+            let mut encoded = ::std::string::String::with_capacity(bytes.len().div_ceil(3) * 4);
+            for chunk in bytes.chunks(3) {
+                let triple = (chunk[0] as u32) << 16
+                    | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+                    | *chunk.get(2).unwrap_or(&0) as u32;
+                for index in 0..4 {
+                    match index <= chunk.len() {
+                        true => encoded.push(TABLE[(triple >> (18 - 6 * index)) as usize & 63] as char),
+                        false => encoded.push('='),
+                    }
+                }
+            }
+            encoded
+        }
+    }
+}
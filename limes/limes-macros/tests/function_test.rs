@@ -0,0 +1,136 @@
+// The expansion is checked on the host against a stand-in of `wit_bindgen`, which declares the
+// Guest trait and an `export!` exposing the exported run as `call_run`. The real components are
+// built by `wasm_build_test.rs` of the limes crate
+extern crate limes_macros as limes;
+
+mod wit_bindgen {
+    macro_rules! generate {
+        ({ inline: $wit:literal }) => {
+            pub mod exports {
+                pub mod component {
+                    pub mod run {
+                        pub mod run {
+                            pub trait Guest {
+                                fn run(args: String) -> String;
+                            }
+                        }
+                    }
+                }
+            }
+
+            #[allow(dead_code)]
+            pub const WIT: &str = $wit;
+        };
+    }
+    pub(crate) use generate;
+}
+
+macro_rules! export {
+    ($component:ident) => {
+        pub fn call_run(args: &str) -> String {
+            <$component as self::exports::component::run::run::Guest>::run(args.to_string())
+        }
+    };
+}
+
+mod string_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn greet(name: String) -> String {
+        format!("Hello, {}", name)
+    }
+}
+
+mod str_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn run(args: &str) -> String {
+        args.to_uppercase()
+    }
+}
+
+mod no_argument_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn version() -> std::string::String {
+        "1.0".to_string()
+    }
+}
+
+mod bytes_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn reverse(bytes: Vec<u8>) -> Vec<u8> {
+        bytes.into_iter().rev().collect()
+    }
+}
+
+mod byte_slice_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn length(bytes: &[u8]) -> String {
+        bytes.len().to_string()
+    }
+}
+
+mod result_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn parse(args: &str) -> Result<String, std::num::ParseIntError> {
+        Ok((args.trim().parse::<i64>()? * 2).to_string())
+    }
+}
+
+mod result_bytes_function {
+    use crate::wit_bindgen;
+
+    #[limes::function]
+    fn checked(args: String) -> Result<Vec<u8>, String> {
+        match args.is_empty() {
+            true => Err("empty arguments".to_string()),
+            false => Ok(args.into_bytes()),
+        }
+    }
+}
+
+#[test]
+fn function_exports_the_run_world() {
+    assert!(string_function::WIT.contains("run: func(args: string) -> string;"));
+    assert!(string_function::WIT.contains("world runnable"));
+}
+
+#[test]
+fn function_with_text() {
+    assert_eq!(string_function::call_run("limes"), "Hello, limes");
+    assert_eq!(str_function::call_run("limes"), "LIMES");
+    assert_eq!(no_argument_function::call_run("ignored"), "1.0");
+    assert_eq!(byte_slice_function::call_run("four"), "4");
+}
+
+#[test]
+fn function_with_bytes_returns_base64() {
+    assert_eq!(bytes_function::call_run(""), "");
+    assert_eq!(bytes_function::call_run("a"), "YQ==");
+    assert_eq!(bytes_function::call_run("ba"), "YWI=");
+    assert_eq!(bytes_function::call_run("cba"), "YWJj");
+    assert_eq!(bytes_function::call_run("olleh"), "aGVsbG8=");
+}
+
+#[test]
+fn function_with_result() {
+    assert_eq!(result_function::call_run("21"), "42");
+    assert_eq!(result_bytes_function::call_run("ok"), "b2s=");
+
+    let error = std::panic::catch_unwind(|| result_function::call_run("not a number")).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<String>().unwrap(),
+        "invalid digit found in string"
+    );
+    assert!(std::panic::catch_unwind(|| result_bytes_function::call_run("")).is_err());
+}
@@ -0,0 +1,4 @@
+#[limes::function]
+fn reverse(bytes: Vec<u8>) -> Vec<u8> {
+    bytes.into_iter().rev().collect()
+}
@@ -0,0 +1,6 @@
+use std::num::ParseIntError;
+
+#[limes::function]
+fn double(args: String) -> Result<String, ParseIntError> {
+    Ok((args.trim().parse::<i64>()? * 2).to_string())
+}
@@ -0,0 +1,5 @@
+#[limes::function]
+fn greet(name: &str) -> String {
+    println!("greeting {}", name);
+    format!("Hello, {}", name)
+}
@@ -0,0 +1,49 @@
+;; wasip1 command writing `hello ` and what it read on stdin on stdout. `exit` exits with code 3,
+;; `spin` never returns, `grow` exits with code 4 when it can not grow its memory by 64MiB and
+;; `argc` writes the number of its arguments.
+(module
+  (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  ;; iovecs at 16 and 40, counters at 32 and 36, `hello ` at 64 followed by the input
+  (data (i32.const 64) "hello ")
+
+  (func $is (param $len i32) (param $word i32) (result i32)
+    (i32.and
+      (i32.eq (local.get $len) (i32.const 4))
+      (i32.eq (i32.load (i32.const 70)) (local.get $word))))
+
+  (func (export "_start")
+    (local $len i32)
+    (i32.store (i32.const 16) (i32.const 70))
+    (i32.store (i32.const 20) (i32.const 1024))
+    (drop (call $fd_read (i32.const 0) (i32.const 16) (i32.const 1) (i32.const 32)))
+    (local.set $len (i32.load (i32.const 32)))
+
+    ;; "exit"
+    (if (call $is (local.get $len) (i32.const 0x74697865))
+      (then (call $proc_exit (i32.const 3))))
+    ;; "spin"
+    (if (call $is (local.get $len) (i32.const 0x6e697073))
+      (then (loop $spin (br $spin))))
+    ;; "grow"
+    (if (call $is (local.get $len) (i32.const 0x776f7267))
+      (then
+        (if (i32.eq (memory.grow (i32.const 1024)) (i32.const -1))
+          (then (call $proc_exit (i32.const 4))))))
+    ;; "argc", the count as a single digit
+    (if (call $is (local.get $len) (i32.const 0x63677261))
+      (then
+        (drop (call $args_sizes_get (i32.const 32) (i32.const 36)))
+        (i32.store8 (i32.const 70) (i32.add (i32.const 48) (i32.load (i32.const 32))))
+        (i32.store (i32.const 40) (i32.const 70))
+        (i32.store (i32.const 44) (i32.const 1))
+        (drop (call $fd_write (i32.const 1) (i32.const 40) (i32.const 1) (i32.const 32)))
+        (return)))
+
+    (i32.store (i32.const 40) (i32.const 64))
+    (i32.store (i32.const 44) (i32.add (i32.const 6) (local.get $len)))
+    (drop (call $fd_write (i32.const 1) (i32.const 40) (i32.const 1) (i32.const 32))))
+)
@@ -0,0 +1,39 @@
+;; Function component whose `run` returns its args passed through test:codec/shout.
+(component
+  (import "test:codec/shout" (instance $shout
+    (export "shout" (func (param "s" string) (result string)))))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core func $shout (canon lower (func $shout "shout")
+    (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (core module $m
+    (import "shout" "shout" (func $shout (param i32 i32 i32)))
+    (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+      local.get $ptr
+      local.get $len
+      i32.const 16
+      call $shout
+      i32.const 16))
+  (core instance $i (instantiate $m
+    (with "shout" (instance (export "shout" (func $shout))))))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+)
@@ -0,0 +1,70 @@
+;; Library component exporting test:codec/codec, `upper` turns the ascii letters uppercase.
+(component
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (func (export "upper") (param $ptr i32) (param $len i32) (result i32)
+      (local $i i32)
+      (local $c i32)
+      block $done
+        loop $next
+          local.get $i
+          local.get $len
+          i32.ge_u
+          br_if $done
+          local.get $ptr
+          local.get $i
+          i32.add
+          i32.load8_u
+          local.tee $c
+          i32.const 97
+          i32.ge_u
+          local.get $c
+          i32.const 122
+          i32.le_u
+          i32.and
+          if
+            local.get $ptr
+            local.get $i
+            i32.add
+            local.get $c
+            i32.const 32
+            i32.sub
+            i32.store8
+          end
+          local.get $i
+          i32.const 1
+          i32.add
+          local.set $i
+          br $next
+        end
+      end
+      ;; The string is upper cased in place
+      i32.const 16
+      local.get $ptr
+      i32.store
+      i32.const 20
+      local.get $len
+      i32.store
+      i32.const 16))
+  (core instance $i (instantiate $m (with "libc" (instance $libc))))
+
+  (func $upper (param "s" string) (result string)
+    (canon lift (core func $i "upper") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $codec (export "upper" (func $upper)))
+  (export "test:codec/codec" (instance $codec))
+)
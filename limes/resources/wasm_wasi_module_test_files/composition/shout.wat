@@ -0,0 +1,57 @@
+;; Library component exporting test:codec/shout, `shout` upper cases the string through
+;; test:codec/codec and appends `!`.
+(component
+  (import "test:codec/codec" (instance $codec
+    (export "upper" (func (param "s" string) (result string)))))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core func $upper (canon lower (func $codec "upper")
+    (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "codec" "upper" (func $upper (param i32 i32 i32)))
+    (func (export "shout") (param $ptr i32) (param $len i32) (result i32)
+      (local $end i32)
+      local.get $ptr
+      local.get $len
+      i32.const 16
+      call $upper
+      ;; The result is the last allocation, `!` is written right after it
+      i32.const 16
+      i32.load
+      i32.const 20
+      i32.load
+      i32.add
+      i32.const 33
+      i32.store8
+      i32.const 20
+      i32.const 20
+      i32.load
+      i32.const 1
+      i32.add
+      i32.store
+      i32.const 16))
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "codec" (instance (export "upper" (func $upper))))))
+
+  (func $shout (param "s" string) (result string)
+    (canon lift (core func $i "shout") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $shout-instance (export "shout" (func $shout)))
+  (export "test:codec/shout" (instance $shout-instance))
+)
@@ -0,0 +1,99 @@
+;; Component writing through limes:log/logging.
+;; `run` logs `call` three times at info with the field `args`, then `detail` at debug and
+;; `done` at warn, and returns `ok`.
+(component
+  (import "limes:log/logging" (instance $logging
+    (type $level' (enum "trace" "debug" "info" "warn" "error"))
+    (export "level" (type $level (eq $level')))
+    (type $field' (record (field "key" string) (field "value" string)))
+    (export "field" (type $field (eq $field')))
+    (export "log" (func (param "level" $level) (param "message" string) (param "fields" (list $field))))))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (data (i32.const 32) "ok")
+    (data (i32.const 300) "call")
+    (data (i32.const 310) "args")
+    (data (i32.const 320) "detail")
+    (data (i32.const 330) "done")
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core func $log (canon lower (func $logging "log") (memory (core memory $libc "memory"))))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "logging" "log" (func $log (param i32 i32 i32 i32 i32)))
+
+    (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+      (local $n i32)
+      ;; The field record at 400: key `args`, value the args
+      i32.const 400
+      i32.const 310
+      i32.store
+      i32.const 404
+      i32.const 4
+      i32.store
+      i32.const 408
+      local.get $ptr
+      i32.store
+      i32.const 412
+      local.get $len
+      i32.store
+      loop $calls
+        i32.const 2
+        i32.const 300
+        i32.const 4
+        i32.const 400
+        i32.const 1
+        call $log
+        local.get $n
+        i32.const 1
+        i32.add
+        local.tee $n
+        i32.const 3
+        i32.lt_u
+        br_if $calls
+      end
+      i32.const 1
+      i32.const 320
+      i32.const 6
+      i32.const 0
+      i32.const 0
+      call $log
+      i32.const 3
+      i32.const 330
+      i32.const 4
+      i32.const 0
+      i32.const 0
+      call $log
+
+      ;; Return area at 16 pointing to `ok` at 32
+      i32.const 16
+      i32.const 32
+      i32.store
+      i32.const 20
+      i32.const 2
+      i32.store
+      i32.const 16))
+
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "logging" (instance
+      (export "log" (func $log))))))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+)
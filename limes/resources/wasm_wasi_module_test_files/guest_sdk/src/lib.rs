@@ -0,0 +1,36 @@
+use limes_guest::{invoke, kv};
+use limes_guest::log::{self, Level};
+use limes_guest::{Args, Error};
+
+// Reaches the host interfaces through the SDK, the first comma separated field picks how:
+// `log,<text>` writes a line with fields then a debug line, `count,<key>` increments a counter
+// stored in the key-value namespace and returns it, `call,<function id>,<args>` returns the
+// response of another function
+#[limes_guest::function]
+fn run(args: Args) -> Result<String, Error> {
+    match args.field(',', 0)? {
+        "log" => {
+            let text = args.field(',', 1)?;
+            log::log(Level::Info, "sdk", &[("text", &text), ("len", &text.len())]);
+            log::debug("sdk detail");
+            Ok("logged".to_string())
+        }
+        "count" => {
+            let key = args.field(',', 1)?;
+            let count = match kv::get(key)? {
+                Some(value) => String::from_utf8_lossy(&value)
+                    .parse::<u64>()
+                    .map_err(Error::internal)?,
+                None => 0,
+            } + 1;
+            kv::set(key, count.to_string())?;
+            Ok(count.to_string())
+        }
+        "call" => {
+            let mut fields = args.as_str().splitn(3, ',').skip(1);
+            let function_id = fields.next().unwrap_or_default();
+            invoke::call(function_id, fields.next().unwrap_or_default())
+        }
+        command => Err(Error::invalid_args(format!("unknown command `{}`", command))),
+    }
+}
@@ -0,0 +1,74 @@
+;; Component calling another function through limes:invoke/call.
+;; `run` calls the function whose id is given as args, passing it the same args, and returns
+;; its result, or `E:` followed by the error of the call.
+(component
+  (import "limes:invoke/call" (instance $invoke
+    (export "call" (func (param "function-id" string) (param "args" string) (result (result string (error string)))))))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 4096))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core func $call (canon lower (func $invoke "call")
+    (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "invoke" "call" (func $call (param i32 i32 i32 i32 i32)))
+
+    (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+      ;; result at 100, string pointer at 104 and length at 108
+      local.get $ptr
+      local.get $len
+      local.get $ptr
+      local.get $len
+      i32.const 100
+      call $call
+      i32.const 100
+      i32.load8_u
+      if
+        ;; `E:` at 2048 followed by the error
+        i32.const 2048
+        i32.const 0x3a45
+        i32.store16
+        i32.const 2050
+        i32.const 104
+        i32.load
+        i32.const 108
+        i32.load
+        memory.copy
+        i32.const 16
+        i32.const 2048
+        i32.store
+        i32.const 20
+        i32.const 108
+        i32.load
+        i32.const 2
+        i32.add
+        i32.store
+        i32.const 16
+        return
+      end
+      i32.const 104))
+
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "invoke" (instance
+      (export "call" (func $call))))))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+)
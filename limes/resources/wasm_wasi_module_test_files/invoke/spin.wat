@@ -0,0 +1,22 @@
+;; Component whose `run` never returns.
+(component
+  (core module $libc
+    (memory (export "memory") 1)
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      i32.const 1024))
+  (core instance $libc (instantiate $libc))
+
+  (core module $m
+    (func (export "run") (param i32 i32) (result i32)
+      loop $spin
+        br $spin
+      end
+      unreachable))
+  (core instance $i (instantiate $m))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+)
@@ -0,0 +1,127 @@
+;; Component counting its calls per key in limes:keyvalue/store.
+;; `run` reads the byte stored under the args, stores it incremented and returns it as an ascii
+;; digit. A failing store call returns `E` followed by the case of the error.
+(component
+  (import "limes:keyvalue/store" (instance $store
+    (type $error' (variant
+      (case "no-such-store")
+      (case "access-denied")
+      (case "quota-exceeded")
+      (case "other" string)))
+    (export "error" (type $error (eq $error')))
+    (export "get" (func (param "key" string) (result (result (option (list u8)) (error $error)))))
+    (export "set" (func (param "key" string) (param "value" (list u8)) (result (result (error $error)))))))
+
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret))
+  (core instance $libc (instantiate $libc))
+
+  (core func $get (canon lower (func $store "get")
+    (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+  (core func $set (canon lower (func $store "set")
+    (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (core module $m
+    (import "libc" "memory" (memory 1))
+    (import "kv" "get" (func $get (param i32 i32 i32)))
+    (import "kv" "set" (func $set (param i32 i32 i32 i32 i32)))
+
+    ;; Return area at 16 pointing to the `len` bytes of the result at 32
+    (func $reply (param $len i32) (result i32)
+      i32.const 16
+      i32.const 32
+      i32.store
+      i32.const 20
+      local.get $len
+      i32.store
+      i32.const 16)
+
+    (func $error (param $case i32) (result i32)
+      i32.const 32
+      i32.const 69
+      i32.store8
+      i32.const 33
+      local.get $case
+      i32.const 48
+      i32.add
+      i32.store8
+      i32.const 2
+      call $reply)
+
+    (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+      (local $count i32)
+      ;; get: result at 100, option at 104, list pointer at 108
+      local.get $ptr
+      local.get $len
+      i32.const 100
+      call $get
+      i32.const 100
+      i32.load8_u
+      if
+        i32.const 104
+        i32.load8_u
+        call $error
+        return
+      end
+      i32.const 104
+      i32.load8_u
+      if
+        i32.const 108
+        i32.load
+        i32.load8_u
+        local.set $count
+      end
+      local.get $count
+      i32.const 1
+      i32.add
+      local.set $count
+
+      ;; set the byte at 200, result at 120
+      i32.const 200
+      local.get $count
+      i32.store8
+      local.get $ptr
+      local.get $len
+      i32.const 200
+      i32.const 1
+      i32.const 120
+      call $set
+      i32.const 120
+      i32.load8_u
+      if
+        i32.const 124
+        i32.load8_u
+        call $error
+        return
+      end
+
+      i32.const 32
+      local.get $count
+      i32.const 48
+      i32.add
+      i32.store8
+      i32.const 1
+      call $reply))
+
+  (core instance $i (instantiate $m
+    (with "libc" (instance $libc))
+    (with "kv" (instance
+      (export "get" (func $get))
+      (export "set" (func $set))))))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $libc "memory")) (realloc (core func $libc "realloc"))))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+)
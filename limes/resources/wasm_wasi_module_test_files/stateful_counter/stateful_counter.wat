@@ -0,0 +1,71 @@
+;; Component keeping a counter in linear memory and one in a global.
+;; `run` increments both and returns them as two ascii digits, `init` presets them to 5 and 2.
+(component
+  (core module $m
+    (memory (export "memory") 1)
+    (global $calls (mut i32) (i32.const 0))
+    (global $heap (mut i32) (i32.const 1024))
+    (data (i32.const 64) "limes")
+
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      global.get $heap
+      local.set $ret
+      global.get $heap
+      local.get 3
+      i32.add
+      global.set $heap
+      local.get $ret)
+
+    (func (export "init")
+      i32.const 0
+      i32.const 5
+      i32.store
+      i32.const 2
+      global.set $calls)
+
+    (func (export "run") (param i32 i32) (result i32)
+      ;; Bump the counters
+      i32.const 0
+      i32.const 0
+      i32.load
+      i32.const 1
+      i32.add
+      i32.store
+      global.get $calls
+      i32.const 1
+      i32.add
+      global.set $calls
+
+      ;; Result string at 32
+      i32.const 32
+      i32.const 0
+      i32.load
+      i32.const 48
+      i32.add
+      i32.store8
+      i32.const 33
+      global.get $calls
+      i32.const 48
+      i32.add
+      i32.store8
+
+      ;; Return area at 16 holding pointer and length
+      i32.const 16
+      i32.const 32
+      i32.store
+      i32.const 20
+      i32.const 2
+      i32.store
+      i32.const 16))
+
+  (core instance $i (instantiate $m))
+
+  (func $run (param "args" string) (result string)
+    (canon lift (core func $i "run") (memory (core memory $i "memory")) (realloc (core func $i "realloc"))))
+  (func $init (canon lift (core func $i "init")))
+
+  (instance $run-instance (export "run" (func $run)))
+  (export "component:run/run" (instance $run-instance))
+  (export "init" (func $init))
+)
@@ -1,43 +1,20 @@
-wit_bindgen::generate!({
-    inline: r"
-        package component:run;
-
-        interface run {
-            run: func(args: string) -> string;
-        }
-
-        world runnable {
-            export run;
-        }
-    "
-});
-
+use limes_guest::{Args, Error};
 use std::net::{TcpListener, UdpSocket};
 
-//  crate exported  component:run -> run interface -> Guest
-use crate::exports::component::run::run::Guest;
-
-struct Component;
-
-impl Guest for Component {
-    fn run(args: String) -> String {
-        let split: Vec<&str> = args.split(",").collect();
-        let protocol = split[0];
-        let ip_str = split[1];
-
-        if protocol == "TCP" {
-            #[allow(unused)]
-            let tcp_listener = TcpListener::bind(ip_str).unwrap();
-            drop(tcp_listener);
-            return "### TCP ###".to_string();
-        } else if protocol == "UDP" {
-            #[allow(unused)]
-            let udp_listener = UdpSocket::bind(ip_str).unwrap();
-            drop(udp_listener);
-            return "### UDP ###".to_string();
+// `TCP,<ip:port>` or `UDP,<ip:port>`, a bind refused by the runtime fails the call
+#[limes_guest::function]
+fn run(args: Args) -> Result<String, Error> {
+    let protocol = args.field(',', 0)?;
+    let address = args.field(',', 1)?;
+    match protocol {
+        "TCP" => {
+            drop(TcpListener::bind(address)?);
+            Ok("### TCP ###".to_string())
         }
-        return "### ERROR ###".to_string();
+        "UDP" => {
+            drop(UdpSocket::bind(address)?);
+            Ok("### UDP ###".to_string())
+        }
+        _ => Err(Error::invalid_args(format!("unknown protocol `{}`", protocol))),
     }
 }
-
-export!(Component);
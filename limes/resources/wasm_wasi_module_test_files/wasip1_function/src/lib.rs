@@ -0,0 +1,7 @@
+// Built for `wasm32-wasip1`: a core module importing `wasi_snapshot_preview1`, registered with
+// the adaptation into a component
+#[limes_guest::function]
+fn run(args: String) -> String {
+    eprintln!("adapted");
+    format!("wasip1 {}", args)
+}
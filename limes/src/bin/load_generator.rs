@@ -0,0 +1,175 @@
+use clap::{Parser, ValueEnum};
+use limes::runtime::runtime::Runtime;
+use limes::tools::load::{self, HttpTarget, LoadConfig, LoadModel, LoadTarget, RuntimeTarget};
+use log::info;
+use std::env;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Model {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Parser)]
+pub struct ArgsParser {
+    /// Component deployed before the run, in process or on the server given by --url
+    #[clap(short, long)]
+    module: Option<PathBuf>,
+    /// Address of a running Limes server, e.g. http://127.0.0.1:8080
+    #[clap(short, long)]
+    url: Option<String>,
+    /// Already deployed function to call on the server, instead of deploying --module
+    #[clap(long)]
+    function_id: Option<String>,
+    /// Arguments passed to every call
+    #[clap(long, default_value = "")]
+    args: String,
+    #[clap(long, value_enum, default_value = "closed")]
+    model: Model,
+    /// Requests in flight at most, the number of workers for the closed model
+    #[clap(short, long, default_value_t = 1)]
+    concurrency: usize,
+    /// Target requests per second, required by the open model
+    #[clap(short, long)]
+    rate: Option<f64>,
+    /// Length of the run in seconds
+    #[clap(short, long, default_value_t = 10.0)]
+    duration: f64,
+    /// Width in seconds of the windows reported over time
+    #[clap(short, long, default_value_t = 1.0)]
+    interval: f64,
+    /// Writes the whole report as JSON
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", "info");
+    }
+    env_logger::init();
+    let args = ArgsParser::parse();
+
+    let target = build_target(&args).await?;
+    let config = LoadConfig {
+        model: match args.model {
+            Model::Open => LoadModel::Open,
+            Model::Closed => LoadModel::Closed,
+        },
+        concurrency: args.concurrency,
+        rate: args.rate,
+        duration: Duration::from_secs_f64(args.duration),
+        interval: Duration::from_secs_f64(args.interval),
+    };
+    info!(
+        "Running a {:?} loop load for {}s, concurrency {}",
+        config.model, args.duration, config.concurrency
+    );
+    let report = load::run_load(target, &config).await?;
+
+    println!("window_s  completed  errors  req/s  p50_ms  p99_ms  queued_ms");
+    for window in report.windows.iter() {
+        println!(
+            "{:>8.1}  {:>9}  {:>6}  {:>5.1}  {:>6.2}  {:>6.2}  {:>9.2}",
+            window.start_secs,
+            window.completed,
+            window.errors,
+            window.throughput,
+            window.latency_p50_ms,
+            window.latency_p99_ms,
+            window.queued_mean_ms
+        );
+    }
+    println!(
+        "\n{} requests in {:.2}s: {:.1} req/s, {:.2}% errors",
+        report.completed,
+        report.elapsed_secs,
+        report.throughput,
+        report.error_rate * 100.0
+    );
+    println!(
+        "latency ms: p50 {:.2} p90 {:.2} p99 {:.2} max {:.2}, queued ms: mean {:.2} p99 {:.2}",
+        report.latency.p50,
+        report.latency.p90,
+        report.latency.p99,
+        report.latency.max,
+        report.queued.mean,
+        report.queued.p99
+    );
+
+    if let Some(output) = &args.output {
+        std::fs::write(output, serde_json::to_string_pretty(&report)?)?;
+        info!("Report written to {}", output.display());
+    }
+    Ok(())
+}
+
+async fn build_target(args: &ArgsParser) -> anyhow::Result<Arc<dyn LoadTarget>> {
+    match (&args.url, &args.function_id, &args.module) {
+        (Some(url), Some(func_id), _) => {
+            Ok(Arc::new(HttpTarget::new(url, func_id, args.args.clone())))
+        }
+        (Some(url), None, Some(module)) => {
+            let func_id = deploy(url, module).await?;
+            info!("Deployed {} as function {}", module.display(), func_id);
+            Ok(Arc::new(HttpTarget::new(url, &func_id, args.args.clone())))
+        }
+        (None, _, Some(module)) => {
+            let runtime = Runtime::new()
+                .set_cpus(num_cpus())
+                .set_max_functions_number(1)
+                .build()?;
+            let module_id = runtime.register_module(std::fs::read(module)?).await?;
+            let func_id = runtime
+                .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+                .await?;
+            Ok(Arc::new(RuntimeTarget::new(
+                Arc::new(runtime),
+                func_id,
+                args.args.clone(),
+            )))
+        }
+        _ => anyhow::bail!(
+            "Give a --module to run in process, or a --url with --module or --function-id"
+        ),
+    }
+}
+
+// Registers the module on the server and initializes a function from it
+async fn deploy(url: &str, module: &PathBuf) -> anyhow::Result<String> {
+    let client = reqwest::Client::new();
+    let url = url.trim_end_matches('/');
+    let response: serde_json::Value = client
+        .post(format!("{}/modules", url))
+        .body(std::fs::read(module)?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let module_id = response["module_id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("The server did not return a module id"))?;
+    let response: serde_json::Value = client
+        .post(format!("{}/modules/{}/functions", url, module_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    response["function_id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("The server did not return a function id"))
+}
+
+fn num_cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
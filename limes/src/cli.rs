@@ -0,0 +1,720 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine as _;
+use clap::{Args, Subcommand, ValueEnum};
+use limes::db::kv::{KvQuota, KvStore};
+use limes::runtime::lambda::WasiFlags;
+use limes::runtime::lambda_error::LambdaError;
+use limes::runtime::logs::{LogLine, LogStream};
+use limes::runtime::runtime::{FunctionHandlerStatus, FunctionInfo};
+use limes::runtime::usage::Usage;
+use limes::runtime::versions::ModuleName;
+use limes::server::client::LimesClient;
+use limes::tools::inspect::ModuleReport;
+use limes::tools::{loader, units};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime_wasi::{DirPerms, FilePerms};
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub struct ClientArgs {
+    /// Address of the Limes server
+    #[clap(
+        long,
+        env = "LIMES_SERVER",
+        default_value = "http://127.0.0.1:8080",
+        global = true
+    )]
+    server: String,
+    /// Bearer token expected by the server
+    #[clap(long, env = "LIMES_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+    /// Print tables and plain results, or JSON
+    #[clap(long, value_enum, default_value_t = Format::Human, global = true)]
+    format: Format,
+}
+
+impl ClientArgs {
+    fn client(&self) -> LimesClient {
+        let mut client = LimesClient::new(&self.server);
+        client.set_token(self.token.clone());
+        client
+    }
+}
+
+/// Commands talking to a running server, and `run` executing a component locally
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a component once without a server, then print its result, output and usage
+    #[clap(after_help = RUN_EXIT_CODES)]
+    Run(RunArgs),
+    /// Register, list and remove the modules of the server
+    Module {
+        #[clap(flatten)]
+        client: ClientArgs,
+        #[clap(subcommand)]
+        command: ModuleCommand,
+    },
+    /// Initialize, call and inspect the functions of the server
+    #[clap(name = "fn")]
+    Function {
+        #[clap(flatten)]
+        client: ClientArgs,
+        #[clap(subcommand)]
+        command: FunctionCommand,
+    },
+    /// Print what the instances of a function wrote on stdout and stderr
+    Logs {
+        #[clap(flatten)]
+        client: ClientArgs,
+        function_id: String,
+        /// Only the last lines
+        #[clap(long)]
+        tail: Option<usize>,
+        /// Keep printing the new lines
+        #[clap(short, long)]
+        follow: bool,
+    },
+}
+
+const RUN_EXIT_CODES: &str = "Exit codes:
+  0  the call returned
+  1  unreadable files
+  2  invalid arguments
+  3  the component could not be compiled or instantiated
+  4  the component does not export component:run/run, or the core module _start
+  5  the guest trapped, or the command exited with another code than 0
+  6  the call exceeded --timeout
+  7  not enough memory
+  8  the call was stopped";
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Component exporting `component:run/run`, or wasip1 core module exporting `_start`
+    module: PathBuf,
+    /// Arguments of the call, `-` reads them from stdin
+    #[clap(long, default_value = "")]
+    arg: String,
+    /// Host directory mapped into the guest, `host:guest` or `host:guest:ro`, can be repeated
+    #[clap(long = "dir", value_parser = parse_dir)]
+    dirs: Vec<DirMapping>,
+    /// Environment variable of the guest, `KEY=VALUE`, can be repeated
+    #[clap(long = "env", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+    /// Linear memory of the function, e.g. 64M or 1GiB
+    #[clap(long, default_value = "64MiB", value_parser = parse_memory)]
+    mem: usize,
+    /// Longest the call may run, e.g. 5s or 500ms
+    #[clap(long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// The only address the guest may bind, every socket is refused without it
+    #[clap(long)]
+    allow_bind: Option<Ipv4Addr>,
+    /// Directory keeping the key-value namespace of the component between runs
+    #[clap(long)]
+    kv_dir: Option<PathBuf>,
+    /// Print the report for humans, or as JSON
+    #[clap(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+#[derive(Clone, Debug)]
+pub struct DirMapping {
+    host: PathBuf,
+    guest: String,
+    read_only: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ModuleCommand {
+    /// Register a component, `-` reads it from stdin
+    Push {
+        path: PathBuf,
+        /// Registered module exporting interfaces the component imports, repeatable
+        #[clap(long = "dependency")]
+        dependencies: Vec<String>,
+        /// Name to publish the module under, along with `--version`
+        #[clap(long, requires = "version")]
+        name: Option<String>,
+        /// Version to publish the module as, `name@latest` moves to it
+        #[clap(long, requires = "name")]
+        version: Option<String>,
+    },
+    /// Publish a registered module as `name@version`, `name@latest` moves to it
+    Publish {
+        module_id: String,
+        name: String,
+        version: String,
+    },
+    /// List the versions and aliases of a module name
+    Versions { name: String },
+    /// Point `name@alias` to a published version
+    Alias {
+        name: String,
+        alias: String,
+        version: String,
+    },
+    /// Remove an alias, the functions initialized from it keep their module
+    Unalias { name: String, alias: String },
+    /// Check a component would register, and list its imports and exports
+    Validate {
+        path: PathBuf,
+        /// Registered module exporting interfaces the component imports, repeatable
+        #[clap(long = "dependency")]
+        dependencies: Vec<String>,
+    },
+    /// List the imports and exports of a registered module
+    Inspect { module_id: String },
+    /// List the registered modules
+    #[clap(alias = "ls")]
+    List,
+    /// Remove a module, its functions keep running
+    Rm { module_id: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum FunctionCommand {
+    /// Initialize a function from a registered module, an id or `name@version` and `name@alias`
+    Init {
+        module_id: String,
+        /// The only address the function may bind, the server default when missing
+        #[clap(long)]
+        tap_ip: Option<Ipv4Addr>,
+    },
+    /// List the functions
+    #[clap(alias = "list")]
+    Ls,
+    /// Remove a function
+    Rm { function_id: String },
+    /// Call a function and print its result
+    Exec {
+        function_id: String,
+        /// Arguments of the call, read from stdin when missing or `-` and stdin is not a terminal
+        args: Option<String>,
+        /// Write the result to a file instead of stdout
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+        /// Decode the result from base64 first, for functions returning binary data
+        #[clap(long)]
+        base64: bool,
+    },
+    /// Move a function to another module, its running calls finish on the previous one
+    Update {
+        function_id: String,
+        /// Module id, or `name@version` and `name@alias`
+        module: String,
+    },
+    /// Stop a function, its running and later calls fail
+    Stop { function_id: String },
+    /// Print the state of a function
+    Status { function_id: String },
+}
+
+pub async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Run(args) => {
+            let code = run_local(args).await?;
+            std::io::stdout().flush()?;
+            std::process::exit(code)
+        }
+        Command::Module { client, command } => module(&client, command).await,
+        Command::Function { client, command } => function(&client, command).await,
+        Command::Logs {
+            client,
+            function_id,
+            tail,
+            follow,
+        } => logs(&client, &function_id, tail, follow).await,
+    }
+}
+
+async fn module(args: &ClientArgs, command: ModuleCommand) -> Result<()> {
+    let client = args.client();
+    match command {
+        ModuleCommand::Push {
+            path,
+            dependencies,
+            name,
+            version,
+        } => {
+            let bytes = read_input(&path)?;
+            let module_id = client
+                .push_module_with_dependencies(bytes, &dependencies)
+                .await?;
+            if let (Some(name), Some(version)) = (&name, &version) {
+                client.publish_module(&module_id, name, version).await?;
+            }
+            match args.format {
+                Format::Human => println!("{}", module_id),
+                Format::Json => print_json(&json!({ "module_id": module_id }))?,
+            }
+        }
+        ModuleCommand::Publish {
+            module_id,
+            name,
+            version,
+        } => {
+            let module_name = client.publish_module(&module_id, &name, &version).await?;
+            print_module_name(args.format, &module_name)?;
+        }
+        ModuleCommand::Versions { name } => {
+            let module_name = client.module_name(&name).await?;
+            print_module_name(args.format, &module_name)?;
+        }
+        ModuleCommand::Alias {
+            name,
+            alias,
+            version,
+        } => {
+            let module_name = client.set_alias(&name, &alias, &version).await?;
+            print_module_name(args.format, &module_name)?;
+        }
+        ModuleCommand::Unalias { name, alias } => {
+            client.remove_alias(&name, &alias).await?;
+            print_done(args.format, "removed", &format!("{}@{}", name, alias))?;
+        }
+        ModuleCommand::Validate { path, dependencies } => {
+            let bytes = read_input(&path)?;
+            let report = client.validate_module(bytes, &dependencies).await?;
+            print_report(args.format, &report)?;
+            if !report.is_valid() {
+                bail!("{} can not be registered", path.display());
+            }
+        }
+        ModuleCommand::Inspect { module_id } => {
+            let report = client.inspect_module(&module_id).await?;
+            print_report(args.format, &report)?;
+        }
+        ModuleCommand::List => {
+            let modules = client.list_modules().await?;
+            if args.format == Format::Json {
+                return print_json(&modules);
+            }
+            println!(
+                "{:<22} {:>10} {:>10}  VERSIONS",
+                "MODULE ID", "SIZE", "FUNCTIONS"
+            );
+            for module in modules {
+                println!(
+                    "{:<22} {:>10} {:>10}  {}",
+                    module.module_id,
+                    units::format_bytes(module.size_bytes),
+                    module.functions,
+                    module.versions.join(",")
+                );
+            }
+        }
+        ModuleCommand::Rm { module_id } => {
+            client.remove_module(&module_id).await?;
+            print_done(args.format, "removed", &module_id)?;
+        }
+    }
+    Ok(())
+}
+
+async fn function(args: &ClientArgs, command: FunctionCommand) -> Result<()> {
+    let client = args.client();
+    match command {
+        FunctionCommand::Init { module_id, tap_ip } => {
+            let function_id = client.init_function(&module_id, tap_ip).await?;
+            match args.format {
+                Format::Human => println!("{}", function_id),
+                Format::Json => print_json(&json!({ "function_id": function_id }))?,
+            }
+        }
+        FunctionCommand::Ls => {
+            let functions = client.list_functions().await?;
+            if args.format == Format::Json {
+                return print_json(&functions);
+            }
+            println!(
+                "{:<12} {:<22} {:<8} {:>10} {:>5}",
+                "FUNCTION ID", "MODULE ID", "STATUS", "MEMORY", "WARM"
+            );
+            for function in functions {
+                println!(
+                    "{:<12} {:<22} {:<8} {:>10} {:>5}",
+                    function.function_id,
+                    function.module_id,
+                    status_name(function.status),
+                    units::format_bytes(function.memory_bytes),
+                    function.warm_instances
+                );
+            }
+        }
+        FunctionCommand::Rm { function_id } => {
+            client.remove_function(&function_id).await?;
+            print_done(args.format, "removed", &function_id)?;
+        }
+        FunctionCommand::Exec {
+            function_id,
+            args: call_args,
+            out,
+            base64,
+        } => {
+            let call_args = read_args(call_args)?;
+            let output = client.exec(&function_id, &call_args).await?;
+            let result = match base64 {
+                true => base64::engine::general_purpose::STANDARD
+                    .decode(output.result.trim())
+                    .context("the result is not valid base64")?,
+                false => output.result.clone().into_bytes(),
+            };
+            match (out, args.format) {
+                (Some(path), format) => {
+                    std::fs::write(&path, &result)
+                        .with_context(|| format!("could not write {}", path.display()))?;
+                    match format {
+                        Format::Human => {
+                            eprintln!("Wrote {} bytes to {}", result.len(), path.display())
+                        }
+                        Format::Json => print_json(&json!({
+                            "out": path,
+                            "bytes": result.len(),
+                            "usage": output.usage,
+                        }))?,
+                    }
+                }
+                (None, Format::Human) => print_result(&result, !base64)?,
+                (None, Format::Json) => print_json(&output)?,
+            }
+        }
+        FunctionCommand::Update {
+            function_id,
+            module,
+        } => {
+            let info = client.update_function(&function_id, &module).await?;
+            match args.format {
+                Format::Human => println!("{} now runs {}", function_id, info.module_id),
+                Format::Json => print_json(&info)?,
+            }
+        }
+        FunctionCommand::Stop { function_id } => {
+            client.stop_function(&function_id).await?;
+            print_done(args.format, "stopped", &function_id)?;
+        }
+        FunctionCommand::Status { function_id } => {
+            let info = client.function_info(&function_id).await?;
+            match args.format {
+                Format::Human => print_status(&info),
+                Format::Json => print_json(&info)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+// Builds the lambda, calls it once and reports, returns the exit code of the process
+async fn run_local(args: RunArgs) -> Result<i32> {
+    if !args.module.is_file() {
+        bail!("{} is not a file", args.module.display());
+    }
+    let call_args = match args.arg.as_str() {
+        "-" => read_args(Some(args.arg.clone()))?,
+        _ => args.arg.clone(),
+    };
+    let mut file_mapper = HashMap::new();
+    for dir in args.dirs.iter() {
+        if !dir.host.is_dir() {
+            bail!("{} is not a directory", dir.host.display());
+        }
+        let perms = match dir.read_only {
+            true => (DirPerms::READ, FilePerms::READ),
+            false => (DirPerms::all(), FilePerms::all()),
+        };
+        file_mapper.insert(
+            dir.host.to_string_lossy().to_string(),
+            (dir.guest.clone(), perms.0, perms.1),
+        );
+    }
+    let mut wasi_flags = WasiFlags::new(
+        args.allow_bind.map(|_| ()),
+        (!file_mapper.is_empty()).then_some(file_mapper),
+    );
+    let kv_store = match &args.kv_dir {
+        Some(dir) => KvStore::open(dir)?,
+        None => KvStore::in_memory(),
+    };
+    let namespace = Arc::new(kv_store).namespace("local", KvQuota::default());
+    wasi_flags
+        .set_env(args.env.clone())
+        .set_keyvalue(Some(namespace));
+
+    let tap_ip = args.allow_bind.unwrap_or(Ipv4Addr::LOCALHOST);
+    let mut lambda =
+        match loader::build_lambda_from_file(&args.module, args.mem, tap_ip, wasi_flags).await {
+            Ok(lambda) => lambda,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                return Ok(e.downcast_ref::<LambdaError>().map_or(3, exit_code));
+            }
+        };
+    lambda.set_timeout(args.timeout);
+    let (result, usage) = lambda.run_with_usage(&call_args).await;
+    let logs = lambda.logs().lines(None, None);
+    let code = result.as_ref().err().map_or(0, exit_code);
+
+    match args.format {
+        Format::Json => {
+            let stream = |stream: LogStream| {
+                logs.iter()
+                    .filter(|line| line.stream == stream)
+                    .map(|line| line.line.as_str())
+                    .collect::<Vec<&str>>()
+            };
+            print_json(&json!({
+                "result": result.as_ref().ok(),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+                "exit_code": code,
+                "stdout": stream(LogStream::Stdout),
+                "stderr": stream(LogStream::Stderr),
+                "usage": usage,
+            }))?;
+        }
+        // Only the result goes to stdout, so it can be piped
+        Format::Human => {
+            for line in logs.iter() {
+                match line.stream {
+                    LogStream::Stdout => eprintln!("[stdout] {}", line.line),
+                    LogStream::Stderr => eprintln!("[stderr] {}", line.line),
+                }
+            }
+            print_usage(&usage);
+            match &result {
+                Ok(result) => print_result(result.as_bytes(), true)?,
+                Err(e) => eprintln!("Error: {} (exit code {})", e, code),
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Exit status of `limes run` for a failed lambda, see RUN_EXIT_CODES
+fn exit_code(error: &LambdaError) -> i32 {
+    match error {
+        LambdaError::FunctionInterfaceError
+        | LambdaError::FunctionInterfaceRetrievError
+        | LambdaError::FunctionRetrievError(_) => 4,
+        LambdaError::FunctionExecError
+        | LambdaError::GuestError(_)
+        | LambdaError::CommandExit(_) => 5,
+        LambdaError::Timeout => 6,
+        LambdaError::NotEnoughtMemory
+        | LambdaError::MemoryFunctionError
+        | LambdaError::ArgsOutOfMemory => 7,
+        LambdaError::ForceStop | LambdaError::FunctionNotRunning => 8,
+        _ => 3,
+    }
+}
+
+fn print_usage(usage: &Usage) {
+    eprintln!("instantiate   {:.2?}", usage.instantiate);
+    eprintln!("execute       {:.2?}", usage.execute);
+    eprintln!(
+        "peak memory   {}",
+        units::format_bytes(usage.peak_memory_bytes as usize)
+    );
+    eprintln!("table growth  {}", usage.table_growth);
+    if let Some(fuel_used) = usage.fuel_used {
+        eprintln!("fuel used     {}", fuel_used);
+    }
+    eprintln!(
+        "preopen io    {} read, {} written",
+        units::format_bytes(usage.preopen_bytes_read as usize),
+        units::format_bytes(usage.preopen_bytes_written as usize)
+    );
+    eprintln!("sockets       {}", usage.sockets_opened);
+    if let Some(trap) = &usage.trap {
+        eprintln!("trap          {}", trap);
+    }
+}
+
+// A newline is added on a terminal, text results only
+fn print_result(result: &[u8], text: bool) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(result)?;
+    if text && stdout.is_terminal() {
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+async fn logs(
+    args: &ClientArgs,
+    function_id: &str,
+    tail: Option<usize>,
+    follow: bool,
+) -> Result<()> {
+    let client = args.client();
+    let mut lines = client.logs(function_id, None, tail).await?;
+    let mut after = None;
+    loop {
+        for line in lines.iter() {
+            print_log_line(args.format, line)?;
+        }
+        if !follow {
+            return Ok(());
+        }
+        after = lines.last().map(|line| line.seq).or(after);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        lines = client.logs(function_id, after, None).await?;
+    }
+}
+
+fn print_log_line(format: Format, line: &LogLine) -> Result<()> {
+    match format {
+        // One object per line, so a followed stream can be read line by line
+        Format::Json => println!("{}", serde_json::to_string(line)?),
+        Format::Human => match line.stream {
+            LogStream::Stdout => println!("{}", line.line),
+            LogStream::Stderr => println!("[stderr] {}", line.line),
+        },
+    }
+    Ok(())
+}
+
+fn print_status(info: &FunctionInfo) {
+    let timeout = info
+        .timeout_ms
+        .map(|timeout| format!("{}ms", timeout))
+        .unwrap_or_else(|| "none".to_string());
+    println!("function id     {}", info.function_id);
+    println!("module id       {}", info.module_id);
+    println!("status          {}", status_name(info.status));
+    println!("running calls   {}", info.running);
+    println!("memory          {}", units::format_bytes(info.memory_bytes));
+    println!("timeout         {}", timeout);
+    println!("warm instances  {}", info.warm_instances);
+}
+
+fn print_report(format: Format, report: &ModuleReport) -> Result<()> {
+    if format == Format::Json {
+        return print_json(report);
+    }
+    println!("runnable  {}", if report.runnable { "yes" } else { "no" });
+    for (title, entries) in [("imports", &report.imports), ("exports", &report.exports)] {
+        println!("{}:", title);
+        for entry in entries {
+            let kind = format!("{:?}", entry.kind).to_lowercase();
+            println!("  {} ({})", entry.name, kind);
+            for (name, signature) in entry.functions.iter() {
+                println!("    {}: {}", name, signature);
+            }
+        }
+    }
+    if !report.errors.is_empty() {
+        println!("errors:");
+        for error in report.errors.iter() {
+            println!("  {}", error);
+        }
+    }
+    Ok(())
+}
+
+fn print_module_name(format: Format, module_name: &ModuleName) -> Result<()> {
+    if format == Format::Json {
+        return print_json(module_name);
+    }
+    println!("{:<16} MODULE ID", "VERSION");
+    for (version, module_id) in module_name.versions.iter() {
+        println!("{:<16} {}", version, module_id);
+    }
+    if !module_name.aliases.is_empty() {
+        println!("aliases:");
+        for (alias, version) in module_name.aliases.iter() {
+            println!("  {} -> {}", alias, version);
+        }
+    }
+    Ok(())
+}
+
+fn status_name(status: FunctionHandlerStatus) -> &'static str {
+    match status {
+        FunctionHandlerStatus::Ready => "ready",
+        FunctionHandlerStatus::Running => "running",
+        FunctionHandlerStatus::Stopped => "stopped",
+    }
+}
+
+fn print_done(format: Format, action: &str, id: &str) -> Result<()> {
+    match format {
+        Format::Human => println!("{} {}", id, action),
+        Format::Json => print_json(&json!({ "id": id, "status": action }))?,
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+// The file, or stdin when the path is `-`
+fn read_input(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+    std::fs::read(path).with_context(|| format!("could not read {}", path.display()))
+}
+
+pub fn parse_memory(value: &str) -> Result<usize, String> {
+    units::parse_bytes(value).map_err(|e| e.to_string())
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    units::parse_duration(value).map_err(|e| e.to_string())
+}
+
+// `host:guest`, `host:guest:ro` or `host:guest:rw`
+fn parse_dir(value: &str) -> Result<DirMapping, String> {
+    let (mapping, read_only) = match value.rsplit_once(':') {
+        Some((mapping, "ro")) => (mapping, true),
+        Some((mapping, "rw")) => (mapping, false),
+        _ => (value, false),
+    };
+    let (host, guest) = mapping
+        .split_once(':')
+        .ok_or_else(|| format!("`{}` is not host:guest[:ro]", value))?;
+    if !guest.starts_with('/') {
+        return Err(format!("the guest path of `{}` must be absolute", value));
+    }
+    Ok(DirMapping {
+        host: PathBuf::from(host),
+        guest: guest.to_string(),
+        read_only,
+    })
+}
+
+fn parse_env(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("`{}` is not KEY=VALUE", value)),
+    }
+}
+
+fn read_args(args: Option<String>) -> Result<String> {
+    match args.as_deref() {
+        Some("-") => {}
+        Some(_) => return Ok(args.unwrap_or_default()),
+        None if std::io::stdin().is_terminal() => return Ok(String::new()),
+        None => {}
+    }
+    let mut args = String::new();
+    std::io::stdin()
+        .read_to_string(&mut args)
+        .context("could not read the arguments from stdin")?;
+    Ok(args)
+}
@@ -0,0 +1,312 @@
+use super::kv_error::KvError;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Longest key accepted by a namespace, in bytes
+pub const MAX_KEY_BYTES: usize = 256;
+/// Keys returned by one `list-keys` call
+pub const LIST_PAGE_SIZE: usize = 100;
+
+/// Limits of a namespace, the bytes count keys and values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KvQuota {
+    pub max_bytes: usize,
+    pub max_keys: usize,
+}
+
+impl Default for KvQuota {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            max_keys: 1000,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Namespace {
+    entries: BTreeMap<String, Vec<u8>>,
+    bytes: usize,
+}
+
+// The entries are only locked for memory operations, the writes of a namespace wait for each
+// other on `writing` while its file is rewritten on a blocking thread
+#[derive(Default)]
+struct NamespaceHandle {
+    data: Mutex<Namespace>,
+    writing: tokio::sync::Mutex<()>,
+}
+
+/// Key-value namespaces of the functions. In memory, or also written to a directory, one file
+/// per namespace rewritten on every change, when opened on one
+pub struct KvStore {
+    namespaces: DashMap<String, Arc<NamespaceHandle>>,
+    dir: Option<PathBuf>,
+}
+
+impl KvStore {
+    pub fn in_memory() -> Self {
+        Self {
+            namespaces: DashMap::new(),
+            dir: None,
+        }
+    }
+
+    /// Loads the namespaces written in `dir`, creating it when missing
+    pub fn open(dir: &Path) -> Result<Self, KvError> {
+        std::fs::create_dir_all(dir).map_err(|e| KvError::Io(e.to_string()))?;
+        let entries = std::fs::read_dir(dir).map_err(|e| KvError::Io(e.to_string()))?;
+        let namespaces = DashMap::new();
+        for entry in entries {
+            let path = entry.map_err(|e| KvError::Io(e.to_string()))?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let load_error = |e: String| KvError::Load(path.display().to_string(), e);
+            let name = path
+                .file_stem()
+                .and_then(|stem| hex::decode(stem.to_string_lossy().as_bytes()).ok())
+                .and_then(|name| String::from_utf8(name).ok())
+                .ok_or_else(|| load_error("not a namespace file".to_string()))?;
+            let text = std::fs::read_to_string(&path).map_err(|e| load_error(e.to_string()))?;
+            let file: NamespaceFile =
+                serde_json::from_str(&text).map_err(|e| load_error(e.to_string()))?;
+            let mut namespace = Namespace::default();
+            for (key, value) in file.entries {
+                let value = BASE64_STANDARD
+                    .decode(value)
+                    .map_err(|e| load_error(e.to_string()))?;
+                namespace.bytes += key.len() + value.len();
+                namespace.entries.insert(key, value);
+            }
+            let handle = NamespaceHandle {
+                data: Mutex::new(namespace),
+                ..NamespaceHandle::default()
+            };
+            namespaces.insert(name, Arc::new(handle));
+        }
+        Ok(Self {
+            namespaces,
+            dir: Some(dir.to_path_buf()),
+        })
+    }
+
+    /// Handle on the `name` namespace, created on first write, enforcing `quota`
+    pub fn namespace(self: &Arc<Self>, name: &str, quota: KvQuota) -> KvNamespace {
+        KvNamespace {
+            store: self.clone(),
+            name: name.to_string(),
+            quota,
+        }
+    }
+
+    /// Names of the namespaces holding at least one key
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .namespaces
+            .iter()
+            .filter(|namespace| !lock(&namespace.value().data).entries.is_empty())
+            .map(|namespace| namespace.key().clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Drops every key of the namespace, once its pending write finished
+    pub async fn remove_namespace(&self, name: &str) -> Result<(), KvError> {
+        let removed = self.namespaces.remove(name).map(|(_, namespace)| namespace);
+        let _writing = match &removed {
+            Some(namespace) => Some(namespace.writing.lock().await),
+            None => None,
+        };
+        let Some(path) = self.file_path(name) else {
+            return Ok(());
+        };
+        let removed = tokio::task::spawn_blocking(move || match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        });
+        match removed.await {
+            Ok(result) => result.map_err(|e| KvError::Io(e.to_string())),
+            Err(e) => Err(KvError::Io(e.to_string())),
+        }
+    }
+
+    fn find_namespace(&self, name: &str) -> Option<Arc<NamespaceHandle>> {
+        self.namespaces
+            .get(name)
+            .map(|namespace| namespace.value().clone())
+    }
+
+    fn get_namespace(&self, name: &str) -> Arc<NamespaceHandle> {
+        self.namespaces.entry(name.to_string()).or_default().clone()
+    }
+
+    // Namespace names are hex encoded, any name makes a valid file name
+    fn file_path(&self, name: &str) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{}.json", hex::encode(name))))
+    }
+
+    // Content of the namespace file, none when the store is in memory
+    fn snapshot(&self, namespace: &Namespace) -> Option<NamespaceFile> {
+        self.dir.as_ref()?;
+        Some(NamespaceFile {
+            entries: namespace
+                .entries
+                .iter()
+                .map(|(key, value)| (key.clone(), BASE64_STANDARD.encode(value)))
+                .collect(),
+        })
+    }
+
+    // Writes the namespace next to its file and renames it over on a blocking thread, a crash
+    // keeps the old file
+    async fn persist(&self, name: &str, file: Option<NamespaceFile>) -> Result<(), KvError> {
+        let (Some(path), Some(file)) = (self.file_path(name), file) else {
+            return Ok(());
+        };
+        let written = tokio::task::spawn_blocking(move || {
+            let text = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+            let temp = path.with_extension("json.tmp");
+            std::fs::write(&temp, text)
+                .and_then(|_| std::fs::rename(&temp, &path))
+                .map_err(|e| e.to_string())
+        });
+        match written.await {
+            Ok(result) => result.map_err(KvError::Io),
+            Err(e) => Err(KvError::Io(e.to_string())),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct NamespaceFile {
+    // Values are base64 encoded
+    entries: BTreeMap<String, String>,
+}
+
+fn lock(namespace: &Mutex<Namespace>) -> std::sync::MutexGuard<'_, Namespace> {
+    namespace.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A namespace of the store as a function sees it
+#[derive(Clone)]
+pub struct KvNamespace {
+    store: Arc<KvStore>,
+    name: String,
+    quota: KvQuota,
+}
+
+impl KvNamespace {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn quota(&self) -> KvQuota {
+        self.quota
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let namespace = self.store.find_namespace(&self.name)?;
+        let value = lock(&namespace.data).entries.get(key).cloned();
+        value
+    }
+
+    pub fn exists(&self, key: &str) -> bool {
+        self.store
+            .find_namespace(&self.name)
+            .is_some_and(|namespace| lock(&namespace.data).entries.contains_key(key))
+    }
+
+    /// Stores `value`, refused when the namespace would go over its quota. When the store is
+    /// written to a directory the call returns once the file is, a failed write undoes the set
+    pub async fn set(&self, key: &str, value: Vec<u8>) -> Result<(), KvError> {
+        if key.is_empty() || key.len() > MAX_KEY_BYTES {
+            return Err(KvError::InvalidKey(MAX_KEY_BYTES));
+        }
+        let namespace = self.store.get_namespace(&self.name);
+        let _writing = namespace.writing.lock().await;
+        let (previous, file) = {
+            let mut data = lock(&namespace.data);
+            let (keys, bytes) = match data.entries.get(key) {
+                Some(old) => (data.entries.len(), data.bytes - old.len() + value.len()),
+                None => (data.entries.len() + 1, data.bytes + key.len() + value.len()),
+            };
+            if keys > self.quota.max_keys || bytes > self.quota.max_bytes {
+                return Err(KvError::QuotaExceeded(self.name.clone()));
+            }
+            let previous = data.entries.insert(key.to_string(), value);
+            let previous_bytes = std::mem::replace(&mut data.bytes, bytes);
+            ((previous, previous_bytes), self.store.snapshot(&data))
+        };
+        let persisted = self.store.persist(&self.name, file).await;
+        if persisted.is_err() {
+            // The other writes of the namespace wait on `writing`, nothing changed since
+            let mut data = lock(&namespace.data);
+            let (previous, previous_bytes) = previous;
+            match previous {
+                Some(previous) => data.entries.insert(key.to_string(), previous),
+                None => data.entries.remove(key),
+            };
+            data.bytes = previous_bytes;
+        }
+        persisted
+    }
+
+    /// Drops the key, a failed write of the file puts it back
+    pub async fn delete(&self, key: &str) -> Result<(), KvError> {
+        let Some(namespace) = self.store.find_namespace(&self.name) else {
+            return Ok(());
+        };
+        let _writing = namespace.writing.lock().await;
+        let (value, file) = {
+            let mut data = lock(&namespace.data);
+            let Some(value) = data.entries.remove(key) else {
+                return Ok(());
+            };
+            data.bytes -= key.len() + value.len();
+            (value, self.store.snapshot(&data))
+        };
+        let persisted = self.store.persist(&self.name, file).await;
+        if persisted.is_err() {
+            let mut data = lock(&namespace.data);
+            data.bytes += key.len() + value.len();
+            data.entries.insert(key.to_string(), value);
+        }
+        persisted
+    }
+
+    /// Up to `LIST_PAGE_SIZE` keys after `cursor`, with the cursor of the next page
+    pub fn list_keys(&self, cursor: Option<&str>) -> (Vec<String>, Option<String>) {
+        let Some(namespace) = self.store.find_namespace(&self.name) else {
+            return (Vec::new(), None);
+        };
+        let namespace = lock(&namespace.data);
+        let mut keys = namespace.entries.keys().filter(|key| match cursor {
+            Some(cursor) => key.as_str() > cursor,
+            None => true,
+        });
+        let page: Vec<String> = keys.by_ref().take(LIST_PAGE_SIZE).cloned().collect();
+        let cursor = match keys.next() {
+            Some(_) => page.last().cloned(),
+            None => None,
+        };
+        (page, cursor)
+    }
+
+    /// Number of keys and bytes used
+    pub fn usage(&self) -> (usize, usize) {
+        self.store
+            .find_namespace(&self.name)
+            .map(|namespace| {
+                let namespace = lock(&namespace.data);
+                (namespace.entries.len(), namespace.bytes)
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum KvError {
+    #[error("KvError: The namespace `{0}` is over its quota")]
+    QuotaExceeded(String),
+    #[error("KvError: Keys must be 1 to {0} bytes long")]
+    InvalidKey(usize),
+    #[error("KvError: Could not persist the store due to `{0}`")]
+    Io(String),
+    #[error("KvError: Could not load `{0}` due to `{1}`")]
+    Load(String, String),
+}
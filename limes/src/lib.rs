@@ -1,2 +1,4 @@
+pub mod db;
 pub mod runtime;
+pub mod server;
 pub mod tools;
@@ -1,82 +1,181 @@
-use axum::{routing::post, Json, Router};
-use clap::Parser;
-use limes::runtime::runtime::{Runtime, RuntimeBuilder};
-use limes::runtime::runtime_error::RuntimeError;
-use tokio;
-use tokio::net::TcpListener;
+mod cli;
 
+use clap::{Args, Parser};
+use limes::server::api::{self, ApiOptions};
+use limes::server::config::ServerConfig;
+use limes::server::telemetry;
+use limes::tools::reconcile::Reconciler;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Runs the Limes server, or talks to a running one through the subcommands
 #[derive(Debug, Parser)]
+#[clap(author, version, about, args_conflicts_with_subcommands = true)]
 pub struct ArgsParser {
+    #[clap(subcommand)]
+    command: Option<cli::Command>,
+    #[clap(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// TOML or YAML configuration file, the flags below override its values
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Validate the configuration, compile the preloaded modules and exit
+    #[clap(long)]
+    check_config: bool,
     /// The ip address on which the server will listen, the default is localhost
     #[clap(short, long)]
-    ip_address: Option<String>,
+    ip_address: Option<IpAddr>,
     /// The port to server to listen on
     #[clap(short, long)]
-    port: Option<usize>,
+    port: Option<u16>,
     /// Number of cpu to use
     #[clap(short, long)]
     cpus: Option<usize>,
-    /// The size of max memory limes will take
-    #[clap(short, long)]
+    /// The size of max memory limes will take, e.g. 512MiB, 2G or a number of bytes
+    #[clap(short, long, value_parser = cli::parse_memory)]
     memory: Option<usize>,
     /// Number of max function limes can deploy
     #[clap(short, long)]
     func_cap: Option<usize>,
+    /// OTLP/HTTP collector receiving the traces, e.g. http://localhost:4318/v1/traces
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+    /// Directory of function manifests the runtime is reconciled with at startup
+    #[clap(long)]
+    apply: Option<PathBuf>,
+    /// Print the changes --apply makes at startup and exit. With --watch, apply at startup
+    /// and only log the changes made to the directory afterwards
+    #[clap(long, requires = "apply")]
+    dry_run: bool,
+    /// Reconcile again every time the --apply directory changes
+    #[clap(long, requires = "apply")]
+    watch: bool,
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
     let args = ArgsParser::parse();
-    let _runtime = match build_runtime(&args) {
-        Ok(runtime) => runtime,
-        Err(e) => panic!("{}", e.to_string()),
-    };
-
-    // Setup server
-    // let router = Router::new().route(, );
+    match args.command {
+        Some(command) => cli::run(command).await,
+        None => serve(args.serve).await,
+    }
 }
 
-#[allow(dead_code)]
-async fn register_module() {
-    todo!();
-}
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    let config = load_config(&args)?;
+    config.validate()?;
 
-#[allow(dead_code)]
-async fn remove_module() {
-    todo!();
-}
+    if args.check_config {
+        let runtime = config.runtime_builder().build()?;
+        let preloaded = config.preload(&runtime).await?;
+        println!(
+            "Configuration is valid: {} cpus, {} bytes of memory, {} functions at most, {} modules preloaded",
+            config.runtime.cpus,
+            config.runtime.memory,
+            config.runtime.max_functions,
+            preloaded.len()
+        );
+        return Ok(());
+    }
 
-#[allow(dead_code)]
-async fn init_function() {
-    todo!();
-}
+    let tracer_provider = match &config.server.otlp_endpoint {
+        Some(endpoint) => Some(telemetry::init_tracing(endpoint)?),
+        None => None,
+    };
+    let runtime = Arc::new(config.runtime_builder().build()?);
+    if let (Some(dir), true, false) = (&args.apply, args.dry_run, args.watch) {
+        let reconciler = Reconciler::new(runtime, config.network.default_tap_ip);
+        println!("{}", reconciler.plan(dir)?);
+        return Ok(());
+    }
 
-#[allow(dead_code)]
-async fn remove_function() {
-    todo!();
-}
+    for module in config.preload(&runtime).await? {
+        log::info!(
+            "Preloaded {} as module {} with functions {:?}",
+            module.path.display(),
+            module.module_id,
+            module.function_ids
+        );
+    }
 
-#[allow(dead_code)]
-async fn exec_function() {
-    todo!();
-}
-
-#[allow(dead_code)]
-async fn stop_function() {
-    todo!();
-}
+    if let Some(dir) = args.apply.clone() {
+        let mut reconciler = Reconciler::new(runtime.clone(), config.network.default_tap_ip);
+        let plan = reconciler.plan(&dir)?;
+        log::info!("Applying {}:\n{}", dir.display(), plan);
+        let report = reconciler.apply(plan).await;
+        for (change, error) in report.failed.iter() {
+            log::warn!("Could not apply `{}`: {}", change, error);
+        }
+        for (name, deployment) in reconciler.deployments() {
+            log::info!("{} is function {}", name, deployment.function_id);
+        }
+        if args.watch {
+            let dry_run = args.dry_run;
+            tokio::spawn(async move {
+                if let Err(e) = reconciler
+                    .watch(&dir, Duration::from_millis(500), dry_run)
+                    .await
+                {
+                    log::error!("Stopped watching {}: {:#}", dir.display(), e);
+                }
+            });
+        }
+    }
 
-fn build_runtime(args: &ArgsParser) -> anyhow::Result<Runtime> {
-    let cpus = args.cpus.map_or(1, |cpus| cpus);
-    let memory = args.memory.map_or(1024 * 1024 * 100, |memory| memory);
-    let func_cap = args.func_cap.map_or(25, |func_cap| func_cap);
+    // Setup server
+    let options = ApiOptions {
+        default_tap_ip: config.network.default_tap_ip,
+        tokens: config.auth.tokens.clone(),
+        allow_mounts: config.server.allow_manifest_mounts,
+        max_upload_size: config.server.max_upload_size,
+    };
+    let router = api::router_with_options(runtime, options);
+    let listener =
+        tokio::net::TcpListener::bind((config.server.address, config.server.port)).await?;
+    log::info!("Limes listening on {}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
 
-    // Create runtime
-    let runtime = Runtime::new()
-        .set_cpus(cpus)
-        .set_total_memory_size(memory)
-        .set_max_functions_number(func_cap)
-        .build()?;
+    // Flush the spans still in the batch
+    if let Some(provider) = tracer_provider {
+        provider.shutdown()?;
+    }
+    Ok(())
+}
 
-    Ok(runtime)
+// The file, when given, then the flags on top of it
+fn load_config(args: &ServeArgs) -> anyhow::Result<ServerConfig> {
+    let mut config = match &args.config {
+        Some(path) => ServerConfig::from_file(path)?,
+        None => ServerConfig::default(),
+    };
+    if let Some(ip_address) = args.ip_address {
+        config.server.address = ip_address;
+    }
+    if let Some(port) = args.port {
+        config.server.port = port;
+    }
+    if let Some(endpoint) = &args.otlp_endpoint {
+        config.server.otlp_endpoint = Some(endpoint.clone());
+    }
+    if let Some(cpus) = args.cpus {
+        config.runtime.cpus = cpus;
+    }
+    if let Some(memory) = args.memory {
+        config.runtime.memory = memory;
+    }
+    if let Some(func_cap) = args.func_cap {
+        config.runtime.max_functions = func_cap;
+    }
+    Ok(config)
 }
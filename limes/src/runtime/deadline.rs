@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::Instant;
+use wasmtime::Engine;
+
+type Deadlines = BTreeMap<(Instant, u64), Engine>;
+
+// A single thread bumps the epoch of the engines whose calls reached their deadline. A guest
+// spinning on its own never yields to the async runtime, so a timer task could not fire
+struct DeadlineTicker {
+    deadlines: Mutex<Deadlines>,
+    changed: Condvar,
+    next_id: AtomicU64,
+}
+
+fn ticker() -> &'static DeadlineTicker {
+    static TICKER: OnceLock<DeadlineTicker> = OnceLock::new();
+    TICKER.get_or_init(|| {
+        std::thread::Builder::new()
+            .name("limes-deadlines".to_string())
+            .spawn(|| ticker().run())
+            .expect("failed to spawn the deadline thread");
+        DeadlineTicker {
+            deadlines: Mutex::new(BTreeMap::new()),
+            changed: Condvar::new(),
+            next_id: AtomicU64::new(0),
+        }
+    })
+}
+
+impl DeadlineTicker {
+    fn lock(&self) -> MutexGuard<'_, Deadlines> {
+        self.deadlines.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn run(&self) {
+        let mut deadlines = self.lock();
+        loop {
+            let now = Instant::now();
+            while let Some(entry) = deadlines.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                entry.remove().increment_epoch();
+            }
+            deadlines = match deadlines.keys().next() {
+                Some((deadline, _)) => {
+                    let timeout = deadline.saturating_duration_since(now);
+                    let (deadlines, _) = self
+                        .changed
+                        .wait_timeout(deadlines, timeout)
+                        .unwrap_or_else(|e| e.into_inner());
+                    deadlines
+                }
+                None => self
+                    .changed
+                    .wait(deadlines)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// Bumps the epoch of the engine once the deadline passed, unless dropped before
+pub(crate) struct DeadlineWatch {
+    key: (Instant, u64),
+}
+
+impl DeadlineWatch {
+    pub(crate) fn new(engine: &Engine, deadline: Instant) -> Self {
+        let ticker = ticker();
+        let key = (deadline, ticker.next_id.fetch_add(1, Ordering::Relaxed));
+        ticker.lock().insert(key, engine.clone());
+        ticker.changed.notify_one();
+        Self { key }
+    }
+}
+
+impl Drop for DeadlineWatch {
+    fn drop(&mut self) {
+        ticker().lock().remove(&self.key);
+    }
+}
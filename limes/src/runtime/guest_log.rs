@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::fmt::Write;
+use wasmtime::component::Linker;
+
+wasmtime::component::bindgen!({
+    path: "wit/log.wit",
+    world: "limes:log/host",
+});
+
+use self::limes::log::logging::{self, Field, Level};
+
+/// Target of the host log records written by the guests
+pub const GUEST_LOG_TARGET: &str = "limes::guest";
+/// Longest message kept, longer ones are truncated
+pub const MAX_MESSAGE_BYTES: usize = 4096;
+
+/// Lowest level of the guest lines reaching the host log
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestLogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<GuestLogLevel> for log::LevelFilter {
+    fn from(level: GuestLogLevel) -> Self {
+        match level {
+            GuestLogLevel::Off => log::LevelFilter::Off,
+            GuestLogLevel::Error => log::LevelFilter::Error,
+            GuestLogLevel::Warn => log::LevelFilter::Warn,
+            GuestLogLevel::Info => log::LevelFilter::Info,
+            GuestLogLevel::Debug => log::LevelFilter::Debug,
+            GuestLogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+impl From<Level> for log::Level {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Trace => log::Level::Trace,
+            Level::Debug => log::Level::Debug,
+            Level::Info => log::Level::Info,
+            Level::Warn => log::Level::Warn,
+            Level::Error => log::Level::Error,
+        }
+    }
+}
+
+/// How the `limes:log` calls of a lambda reach the host log
+#[derive(Clone, Debug)]
+pub struct GuestLogConfig {
+    /// Tag of every line, the lines of a lambda outside a runtime have none
+    pub function_id: Option<String>,
+    pub level: GuestLogLevel,
+    /// Lines written per invocation, the next ones are dropped
+    pub max_lines: usize,
+}
+
+impl Default for GuestLogConfig {
+    fn default() -> Self {
+        Self {
+            function_id: None,
+            level: GuestLogLevel::Info,
+            max_lines: 100,
+        }
+    }
+}
+
+/// Host side of `limes:log/logging` for one store, reset before every call
+#[derive(Default)]
+pub struct GuestLogger {
+    config: GuestLogConfig,
+    invocation_id: Option<String>,
+    lines: usize,
+    dropped: usize,
+}
+
+impl GuestLogger {
+    pub(crate) fn begin(&mut self, config: &GuestLogConfig, invocation_id: Option<&str>) {
+        self.config = config.clone();
+        self.invocation_id = invocation_id.map(str::to_string);
+        self.lines = 0;
+        self.dropped = 0;
+    }
+
+    /// Lines of the current invocation dropped by the limit
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn tags(&self) -> String {
+        format!(
+            "function={} invocation={}",
+            self.config.function_id.as_deref().unwrap_or("-"),
+            self.invocation_id.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+pub(crate) trait GuestLogView {
+    fn guest_log(&mut self) -> &mut GuestLogger;
+}
+
+pub(crate) fn add_to_linker<T: GuestLogView>(linker: &mut Linker<T>) -> anyhow::Result<()> {
+    logging::add_to_linker(linker, |state: &mut T| state.guest_log())
+}
+
+impl logging::Host for GuestLogger {
+    fn log(&mut self, level: Level, mut message: String, fields: Vec<Field>) {
+        let level = log::Level::from(level);
+        if level > log::LevelFilter::from(self.config.level)
+            || !log::log_enabled!(target: GUEST_LOG_TARGET, level)
+        {
+            return;
+        }
+        if self.lines >= self.config.max_lines {
+            if self.dropped == 0 {
+                log::warn!(
+                    target: GUEST_LOG_TARGET,
+                    "{} reached {} lines, the next ones are dropped",
+                    self.tags(),
+                    self.config.max_lines
+                );
+            }
+            self.dropped += 1;
+            return;
+        }
+        self.lines += 1;
+
+        if message.len() > MAX_MESSAGE_BYTES {
+            let mut end = MAX_MESSAGE_BYTES;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        let mut line = format!("{} {}", self.tags(), message);
+        for field in fields.iter() {
+            let _ = write!(line, " {}={:?}", field.key, field.value);
+        }
+        log::log!(target: GUEST_LOG_TARGET, level, "{}", line);
+    }
+}
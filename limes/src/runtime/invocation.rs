@@ -0,0 +1,112 @@
+use super::usage::Usage;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+pub type InvocationID = String;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl InvocationStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, InvocationStatus::Pending | InvocationStatus::Running)
+    }
+}
+
+/// Snapshot of an asynchronous invocation, as returned to the callers polling it
+#[derive(Clone, Debug, Serialize)]
+pub struct InvocationReport {
+    pub id: InvocationID,
+    pub function_id: String,
+    pub status: InvocationStatus,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// Time spent running the guest, once the invocation is finished
+    pub duration_ms: Option<u128>,
+    /// Resources consumed by the run, once the guest returned
+    pub usage: Option<Usage>,
+}
+
+// Bookkeeping of a submitted invocation, kept until its retention expires
+pub(crate) struct Invocation {
+    pub function_id: String,
+    pub status: InvocationStatus,
+    pub output: Option<Result<String, String>>,
+    pub usage: Option<Usage>,
+    pub started_at: Option<Instant>,
+    pub finished_at: Option<Instant>,
+    pub retention: Duration,
+    pub handle: Option<AbortHandle>,
+    // Notified with the report once the invocation finishes
+    pub callback_url: Option<String>,
+}
+
+impl Invocation {
+    pub fn new(function_id: String, retention: Duration) -> Self {
+        Self {
+            function_id,
+            status: InvocationStatus::Pending,
+            output: None,
+            usage: None,
+            started_at: None,
+            finished_at: None,
+            retention,
+            handle: None,
+            callback_url: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.status = InvocationStatus::Running;
+        self.started_at = Some(Instant::now());
+    }
+
+    pub fn finish(
+        &mut self,
+        status: InvocationStatus,
+        output: Option<Result<String, String>>,
+        usage: Option<Usage>,
+    ) {
+        self.status = status;
+        self.output = output;
+        self.usage = usage;
+        self.finished_at = Some(Instant::now());
+        self.handle = None;
+    }
+
+    pub fn expired(&self) -> bool {
+        self.finished_at
+            .is_some_and(|finished_at| finished_at.elapsed() >= self.retention)
+    }
+
+    pub fn report(&self, id: &str) -> InvocationReport {
+        let (result, error) = match &self.output {
+            Some(Ok(result)) => (Some(result.clone()), None),
+            Some(Err(error)) => (None, Some(error.clone())),
+            None => (None, None),
+        };
+        let duration_ms = match (self.started_at, self.finished_at) {
+            (Some(started_at), Some(finished_at)) => {
+                Some(finished_at.duration_since(started_at).as_millis())
+            }
+            _ => None,
+        };
+        InvocationReport {
+            id: id.to_string(),
+            function_id: self.function_id.clone(),
+            status: self.status,
+            result,
+            error,
+            duration_ms,
+            usage: self.usage.clone(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use super::invoke_error::InvokeError;
+use super::lambda::CallContext;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use wasmtime::component::Linker;
+
+wasmtime::component::bindgen!({
+    path: "wit/invoke.wit",
+    world: "limes:invoke/host",
+    async: true,
+});
+
+use self::limes::invoke::call;
+
+/// Interface a guest imports to call other functions
+pub const INVOKE_INTERFACE: &str = "limes:invoke/call";
+/// Calls nested below an invocation, unless the runtime is given another limit
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 8;
+/// Allowlist entry letting a function call any other
+pub const ALLOW_ANY: &str = "*";
+
+pub type InvokeFuture<'a> = Pin<Box<dyn Future<Output = Result<String, InvokeError>> + Send + 'a>>;
+
+/// Runs the functions called by the guests, the runtime implements it
+pub trait Invoker: Send + Sync {
+    fn invoke<'a>(
+        &'a self,
+        function_id: &'a str,
+        args: &'a str,
+        context: CallContext,
+    ) -> InvokeFuture<'a>;
+}
+
+/// What a function may call through `limes:invoke/call`
+#[derive(Clone)]
+pub struct InvokePolicy {
+    pub invoker: Arc<dyn Invoker>,
+    /// Function ids the guest may call, `ALLOW_ANY` allows every function
+    pub allow: Vec<String>,
+    pub max_depth: usize,
+}
+
+impl InvokePolicy {
+    pub fn allows(&self, function_id: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|allowed| allowed == ALLOW_ANY || allowed == function_id)
+    }
+}
+
+/// Host side of `limes:invoke/call` for one store, every call fails without a policy
+#[derive(Default)]
+pub struct InvokeHost {
+    policy: Option<InvokePolicy>,
+    // Context of the running call, the nested calls inherit it
+    context: CallContext,
+}
+
+impl InvokeHost {
+    pub fn new(policy: Option<InvokePolicy>) -> Self {
+        Self {
+            policy,
+            context: CallContext::default(),
+        }
+    }
+
+    pub(crate) fn begin(&mut self, context: CallContext) {
+        self.context = context;
+    }
+
+    /// True once the lambda running the call or one of its callers was stopped
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.context.is_stopped()
+    }
+
+    async fn invoke(&self, function_id: &str, args: &str) -> Result<String, InvokeError> {
+        let policy = self.policy.as_ref().ok_or(InvokeError::Unavailable)?;
+        if !policy.allows(function_id) {
+            return Err(InvokeError::NotAllowed(function_id.to_string()));
+        }
+        if self.context.depth >= policy.max_depth {
+            return Err(InvokeError::DepthExceeded(policy.max_depth));
+        }
+        if self.context.is_stopped() {
+            return Err(InvokeError::Cancelled);
+        }
+        let mut context = self.context.clone();
+        context.depth += 1;
+        policy.invoker.invoke(function_id, args, context).await
+    }
+}
+
+pub(crate) trait InvokeView: Send {
+    fn invoke(&mut self) -> &mut InvokeHost;
+}
+
+pub(crate) fn add_to_linker<T: InvokeView + 'static>(linker: &mut Linker<T>) -> anyhow::Result<()> {
+    call::add_to_linker(linker, |state: &mut T| state.invoke())
+}
+
+impl call::Host for InvokeHost {
+    async fn call(&mut self, function_id: String, args: String) -> Result<String, String> {
+        self.invoke(&function_id, &args)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
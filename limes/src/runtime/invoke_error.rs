@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InvokeError {
+    #[error("InvokeError: Function calls are not available to this function")]
+    Unavailable,
+    #[error("InvokeError: The function may not call `{0}`")]
+    NotAllowed(String),
+    #[error("InvokeError: Calls may not be nested deeper than {0}")]
+    DepthExceeded(usize),
+    #[error("InvokeError: The calling function was stopped")]
+    Cancelled,
+    #[error("InvokeError: The function `{0}` is not registered")]
+    FunctionNotFound(String),
+    #[error("InvokeError: The function `{0}` already runs as many calls as its concurrency")]
+    Busy(String),
+    #[error("InvokeError: `{0}` failed due to `{1}`")]
+    Failed(String, String),
+}
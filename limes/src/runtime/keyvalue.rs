@@ -0,0 +1,68 @@
+use crate::db::kv::KvNamespace;
+use crate::db::kv_error::KvError;
+use wasmtime::component::Linker;
+
+wasmtime::component::bindgen!({
+    path: "wit/keyvalue.wit",
+    world: "limes:keyvalue/host",
+    async: true,
+});
+
+use self::limes::keyvalue::store::{self, Error, KeyResponse};
+
+/// Interface a guest imports to reach its namespace
+pub const KEYVALUE_INTERFACE: &str = "limes:keyvalue/store";
+
+/// Host side of `limes:keyvalue/store`, every call fails with `no-such-store` without a namespace
+#[derive(Clone, Default)]
+pub struct KvHost(Option<KvNamespace>);
+
+impl KvHost {
+    pub fn new(namespace: Option<KvNamespace>) -> Self {
+        Self(namespace)
+    }
+
+    fn namespace(&self) -> Result<&KvNamespace, Error> {
+        self.0.as_ref().ok_or(Error::NoSuchStore)
+    }
+}
+
+pub(crate) trait KvView: Send {
+    fn keyvalue(&mut self) -> &mut KvHost;
+}
+
+pub(crate) fn add_to_linker<T: KvView + 'static>(linker: &mut Linker<T>) -> anyhow::Result<()> {
+    store::add_to_linker(linker, |state: &mut T| state.keyvalue())
+}
+
+impl From<KvError> for Error {
+    fn from(error: KvError) -> Self {
+        match error {
+            KvError::QuotaExceeded(_) => Error::QuotaExceeded,
+            error => Error::Other(error.to_string()),
+        }
+    }
+}
+
+impl store::Host for KvHost {
+    async fn get(&mut self, key: String) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.namespace()?.get(&key))
+    }
+
+    async fn set(&mut self, key: String, value: Vec<u8>) -> Result<(), Error> {
+        Ok(self.namespace()?.set(&key, value).await?)
+    }
+
+    async fn delete(&mut self, key: String) -> Result<(), Error> {
+        Ok(self.namespace()?.delete(&key).await?)
+    }
+
+    async fn exists(&mut self, key: String) -> Result<bool, Error> {
+        Ok(self.namespace()?.exists(&key))
+    }
+
+    async fn list_keys(&mut self, cursor: Option<String>) -> Result<KeyResponse, Error> {
+        let (keys, cursor) = self.namespace()?.list_keys(cursor.as_deref());
+        Ok(KeyResponse { keys, cursor })
+    }
+}
@@ -1,46 +1,267 @@
+use super::deadline::DeadlineWatch;
+use super::guest_log::{self, GuestLogConfig, GuestLogView, GuestLogger};
+use super::invoke::{self, InvokeHost, InvokePolicy, InvokeView};
+use super::keyvalue::{self, KvHost, KvView};
 use super::lambda_error::LambdaError;
-use std::collections::HashMap;
+use super::logs::{GuestErrorSlot, GuestOutput, LogBuffer, LogStream};
+use super::usage::{self, IoCounters, Usage, UsageTracker, UsageView};
+use crate::db::kv::KvNamespace;
+use crate::tools::snapshot::{RESTORE_EXPORT_PREFIX, SNAPSHOT_EXPORT_PREFIX};
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use wasmtime::component::{Component, Instance, Linker, ResourceTable, TypedFunc};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info_span, Instrument};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Instance, InstancePre, Linker, ResourceTable, TypedFunc};
 use wasmtime::*;
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
 use wasmtime_wasi::DirPerms;
 use wasmtime_wasi::FilePerms;
-use wasmtime_wasi::{IoView, SocketAddrUse, WasiCtx, WasiCtxBuilder, WasiView};
+use wasmtime_wasi::{I32Exit, IoView, SocketAddrUse, WasiCtx, WasiCtxBuilder, WasiView};
+
+/// Export a core module runs as a command
+pub const COMMAND_EXPORT: &str = "_start";
+
+const COMMAND_WARM_ERROR: &str = "a core module only runs on fresh instances";
 
 pub struct LambdaState {
-    wasi_ctx: WasiCtx, // WARN: Doesn't implement Sync to prevent memory movemnts
-    resource_table: ResourceTable,
-    limiter: StoreLimits,
+    // Serves both WASI versions, its table holds the resources of the component
+    wasi: WasiP1Ctx, // WARN: Doesn't implement Sync to prevent memory movemnts
+    usage: UsageTracker,
+    // Past it the epoch callback aborts the call
+    deadline: Option<Instant>,
+    keyvalue: KvHost,
+    guest_log: GuestLogger,
+    invoke: InvokeHost,
+    // Instances of the dependencies, in the order of `WasiFlags::set_dependencies`
+    dependencies: Vec<Instance>,
+    // Error the guest reported on stderr during the call
+    guest_error: GuestErrorSlot,
 }
 
 impl IoView for LambdaState {
     fn table(&mut self) -> &mut ResourceTable {
-        &mut self.resource_table
+        self.wasi.table()
     }
 }
 
 impl WasiView for LambdaState {
     fn ctx(&mut self) -> &mut WasiCtx {
-        &mut self.wasi_ctx
+        self.wasi.ctx()
+    }
+}
+
+impl UsageView for LambdaState {
+    fn usage(&mut self) -> &mut UsageTracker {
+        &mut self.usage
+    }
+}
+
+impl KvView for LambdaState {
+    fn keyvalue(&mut self) -> &mut KvHost {
+        &mut self.keyvalue
+    }
+}
+
+impl GuestLogView for LambdaState {
+    fn guest_log(&mut self) -> &mut GuestLogger {
+        &mut self.guest_log
+    }
+}
+
+impl InvokeView for LambdaState {
+    fn invoke(&mut self) -> &mut InvokeHost {
+        &mut self.invoke
+    }
+}
+
+type SocketAddrCheck = Box<
+    dyn Fn(SocketAddr, SocketAddrUse) -> Pin<Box<dyn Future<Output = bool> + Send + Sync>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+/// The code a lambda runs
+#[derive(Clone)]
+pub enum Program {
+    /// A component exporting `component:run/run`
+    Component(Arc<Component>),
+    /// A wasip1 core module run as a command: `_start` reads the args of the call on stdin and
+    /// what it writes on stdout is the result
+    Command(Arc<Module>),
+}
+
+impl Program {
+    pub fn engine(&self) -> &Engine {
+        match self {
+            Program::Component(component) => component.engine(),
+            Program::Command(module) => module.engine(),
+        }
+    }
+
+    pub fn component(&self) -> Option<&Arc<Component>> {
+        match self {
+            Program::Component(component) => Some(component),
+            Program::Command(_) => None,
+        }
     }
 }
 
 pub struct Lambda {
-    component: Arc<Component>,
+    program: Program,
+    timeout: Option<Duration>,
+    instance_mode: InstanceMode,
+    stores: StoreConfig,
+    // Only in warm mode
+    pool: Option<Arc<WarmPool>>,
+    guest_log: GuestLogConfig,
+}
+
+// What every store of a lambda is built from
+#[derive(Clone)]
+struct StoreConfig {
     memory_size: usize,
     tap_ip: Ipv4Addr,
     stop: Arc<AtomicBool>,
     wasi_flags: WasiFlags,
+    // Stdout and stderr of every instance
+    logs: Arc<LogBuffer>,
+}
+
+// The idle instances of a warm lambda and what it takes to build more. It is shared with the
+// tasks replacing the instances that are not put back after a call
+struct WarmPool {
+    config: WarmPoolConfig,
+    engine: Engine,
+    stores: StoreConfig,
+    instance_pre: InstancePre<LambdaState>,
+    dependency_pres: Vec<InstancePre<LambdaState>>,
+    snapshot_exports: Vec<(String, String)>,
+    idle: Mutex<Vec<WarmInstance>>,
+    // Instances being built in the background
+    refilling: AtomicUsize,
+}
+
+/// What a call inherits from the invocation it belongs to
+#[derive(Clone, Debug, Default)]
+pub struct CallContext {
+    /// Tags the `limes:log` lines of the call
+    pub invocation_id: Option<String>,
+    /// Calls made through `limes:invoke` between the invocation and this one
+    pub depth: usize,
+    /// The call times out past it, even before the timeout of the lambda
+    pub deadline: Option<Instant>,
+    /// Stop flags of the calling lambdas, stopping any of them stops the call
+    pub callers: Vec<Arc<AtomicBool>>,
+}
+
+impl CallContext {
+    pub fn new(invocation_id: Option<String>) -> Self {
+        Self {
+            invocation_id,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.callers.iter().any(|stop| stop.load(Ordering::Relaxed))
+    }
+}
+
+struct Call<'a> {
+    args: &'a str,
+    context: &'a CallContext,
+}
+
+/// Chooses between instance isolation and instance reuse for a function
+#[derive(Clone, Debug, Default)]
+pub enum InstanceMode {
+    /// Every call builds a brand new store and instance, then throws it away
+    #[default]
+    Fresh,
+    /// Calls are served by a pool of pre-instantiated stores
+    Warm(WarmPoolConfig),
+}
+
+/// What happens to a warm instance once a call returned successfully
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResetPolicy {
+    /// Run `post_return` and put the instance back, the guest state is kept
+    PostReturn,
+    /// Use the pre-instantiated store for a single call, then drop it. A new instance is
+    /// built in the background to refill the pool
+    Discard,
+    /// Capture memories and globals after instantiation, and after the optional `init_export`,
+    /// then restore them before every call. Needs a component instrumented by
+    /// `tools::snapshot::instrument_component`
+    Snapshot { init_export: Option<String> },
+}
+
+#[derive(Clone, Debug)]
+pub struct WarmPoolConfig {
+    size: usize,
+    idle_ttl: Duration,
+    max_reuse: usize,
+    reset_policy: ResetPolicy,
+}
+
+impl WarmPoolConfig {
+    pub fn new(
+        size: usize,
+        idle_ttl: Duration,
+        max_reuse: usize,
+        reset_policy: ResetPolicy,
+    ) -> Self {
+        Self {
+            size,
+            idle_ttl,
+            max_reuse,
+            reset_policy,
+        }
+    }
+
+    pub fn reset_policy(&self) -> &ResetPolicy {
+        &self.reset_policy
+    }
 }
 
+impl Default for WarmPoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 2,
+            idle_ttl: Duration::from_secs(60),
+            max_reuse: 100,
+            reset_policy: ResetPolicy::PostReturn,
+        }
+    }
+}
+
+struct WarmInstance {
+    store: Store<LambdaState>,
+    instance: Instance,
+    uses: usize,
+    last_used: Instant,
+}
+
+#[derive(Clone)]
 pub struct WasiFlags {
     socket_addr_check: Option<()>,
     file_mapper: Option<HashMap<String, (String, DirPerms, FilePerms)>>,
+    env: Vec<(String, String)>,
+    outbound: Option<Vec<OutboundRule>>,
+    keyvalue: Option<KvNamespace>,
+    invoke: Option<InvokePolicy>,
+    dependencies: Vec<Arc<Component>>,
+    args: Vec<String>,
 }
 
 impl WasiFlags {
@@ -51,16 +272,93 @@ impl WasiFlags {
         Self {
             socket_addr_check,
             file_mapper,
+            env: Vec::new(),
+            outbound: None,
+            keyvalue: None,
+            invoke: None,
+            dependencies: Vec::new(),
+            args: Vec::new(),
         }
     }
+
+    /// Environment variables seen by the guest
+    pub fn set_env(&mut self, env: Vec<(String, String)>) -> &mut Self {
+        self.env = env;
+        self
+    }
+
+    /// Addresses the guest may connect to, every address when None
+    pub fn set_outbound(&mut self, outbound: Option<Vec<OutboundRule>>) -> &mut Self {
+        self.outbound = outbound;
+        self
+    }
+
+    pub fn outbound(&self) -> Option<&Vec<OutboundRule>> {
+        self.outbound.as_ref()
+    }
+
+    /// Namespace behind `limes:keyvalue/store`, its calls fail with `no-such-store` when None
+    pub fn set_keyvalue(&mut self, keyvalue: Option<KvNamespace>) -> &mut Self {
+        self.keyvalue = keyvalue;
+        self
+    }
+
+    /// Functions the guest may call through `limes:invoke/call`, every call fails when None
+    pub fn set_invoke(&mut self, invoke: Option<InvokePolicy>) -> &mut Self {
+        self.invoke = invoke;
+        self
+    }
+
+    /// Components instantiated in the store before the function, in this order, the imports
+    /// they export are linked to them. They must be built by the engine of the function
+    pub fn set_dependencies(&mut self, dependencies: Vec<Arc<Component>>) -> &mut Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    /// Command line seen by the guest, by convention the first one is the name of the program
+    pub fn set_args(&mut self, args: Vec<String>) -> &mut Self {
+        self.args = args;
+        self
+    }
 }
 
 impl Default for WasiFlags {
     fn default() -> Self {
-        Self {
-            socket_addr_check: Some(()),
-            file_mapper: None,
+        Self::new(Some(()), None)
+    }
+}
+
+/// An address the guest may connect to, any port when `port` is None
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboundRule {
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+}
+
+impl OutboundRule {
+    pub fn allows(&self, socket: &SocketAddr) -> bool {
+        socket.ip() == self.ip && self.port.is_none_or(|port| port == socket.port())
+    }
+}
+
+impl FromStr for OutboundRule {
+    type Err = String;
+
+    // `10.0.0.2`, `10.0.0.2:5432` or `[::1]:80`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(socket) = value.parse::<SocketAddr>() {
+            return Ok(Self {
+                ip: socket.ip(),
+                port: Some(socket.port()),
+            });
         }
+        value
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| Self { ip, port: None })
+            .map_err(|_| format!("`{}` is not an ip or an ip:port", value))
     }
 }
 
@@ -70,73 +368,443 @@ impl Lambda {
         memory_size: usize,
         tap_ip: Ipv4Addr,
         wasi_flags: WasiFlags,
+    ) -> Result<Self, LambdaError> {
+        Self::with_instance_mode(
+            component,
+            memory_size,
+            tap_ip,
+            wasi_flags,
+            InstanceMode::Fresh,
+        )
+        .await
+    }
+
+    pub async fn with_instance_mode(
+        component: Arc<Component>,
+        memory_size: usize,
+        tap_ip: Ipv4Addr,
+        wasi_flags: WasiFlags,
+        instance_mode: InstanceMode,
+    ) -> Result<Self, LambdaError> {
+        Self::with_program(
+            Program::Component(component),
+            memory_size,
+            tap_ip,
+            wasi_flags,
+            instance_mode,
+        )
+        .await
+    }
+
+    /// Runs a wasip1 core module as a command, every call on a fresh instance
+    pub async fn from_command(
+        module: Arc<Module>,
+        memory_size: usize,
+        tap_ip: Ipv4Addr,
+        wasi_flags: WasiFlags,
+    ) -> Result<Self, LambdaError> {
+        Self::with_program(
+            Program::Command(module),
+            memory_size,
+            tap_ip,
+            wasi_flags,
+            InstanceMode::Fresh,
+        )
+        .await
+    }
+
+    pub async fn with_program(
+        program: Program,
+        memory_size: usize,
+        tap_ip: Ipv4Addr,
+        wasi_flags: WasiFlags,
+        instance_mode: InstanceMode,
     ) -> Result<Self, LambdaError> {
         if memory_size < 1024 * 1024 * 2 {
             return Err(LambdaError::NotEnoughtMemory);
         }
         let stop = Arc::new(AtomicBool::new(false));
-        Ok(Self {
-            component,
+        if let Program::Command(module) = &program {
+            if !matches!(module.get_export(COMMAND_EXPORT), Some(ExternType::Func(_))) {
+                return Err(LambdaError::FunctionRetrievError(format!(
+                    "the core module does not export `{}`",
+                    COMMAND_EXPORT
+                )));
+            }
+        }
+        let stores = StoreConfig {
             memory_size,
             tap_ip,
             stop,
             wasi_flags,
+            logs: Arc::new(LogBuffer::default()),
+        };
+
+        // Pre-instantiate the warm pool, a broken component fails here and not on first run
+        let pool = match (&program, &instance_mode) {
+            (_, InstanceMode::Fresh) => None,
+            (Program::Command(_), InstanceMode::Warm(_)) => {
+                return Err(LambdaError::InstanceBuilderError(
+                    COMMAND_WARM_ERROR.to_string(),
+                ))
+            }
+            (Program::Component(component), InstanceMode::Warm(config)) => {
+                Some(WarmPool::new(component, config, &stores).await?)
+            }
+        };
+        Ok(Self {
+            program,
+            timeout: None,
+            instance_mode,
+            stores,
+            pool,
+            guest_log: GuestLogConfig::default(),
         })
     }
 
     pub async fn run(&self, args: &str) -> Result<String, LambdaError> {
-        // Setup the Linker and Wasi support
-        let engine = self.component.engine();
-        let mut linker = Linker::new(engine);
-        wasmtime_wasi::add_to_linker_async(&mut linker)
-            .map_err(|e| LambdaError::WasiAsyncLinkerError(e.to_string()))?;
-        let wasi_ctx = self.build_wasi_ctx();
-        let mut store = self.build_store(engine, wasi_ctx);
+        self.run_with_usage(args).await.0
+    }
 
-        // Store register epoch_deadline_callback
-        let stop = self.stop.clone();
-        store.epoch_deadline_callback(move |_| {
-            if !stop.load(Ordering::Relaxed) {
-                return Ok(UpdateDeadline::Yield(1));
+    // Same as run, also reporting the resources consumed by the call
+    pub async fn run_with_usage(&self, args: &str) -> (Result<String, LambdaError>, Usage) {
+        self.run_invocation(args, &CallContext::default()).await
+    }
+
+    // Same as run_with_usage, the call is bound by the deadline and the callers of `context`
+    pub async fn run_invocation(
+        &self,
+        args: &str,
+        context: &CallContext,
+    ) -> (Result<String, LambdaError>, Usage) {
+        let mut usage = Usage::default();
+        let call = Call { args, context };
+        let result = async {
+            match (&self.program, &self.instance_mode) {
+                (Program::Command(module), InstanceMode::Fresh) => {
+                    self.run_command(module, &call, &mut usage).await
+                }
+                // Refused by `with_program`
+                (Program::Command(_), InstanceMode::Warm(_)) => Err(
+                    LambdaError::InstanceBuilderError(COMMAND_WARM_ERROR.to_string()),
+                ),
+                (Program::Component(component), InstanceMode::Fresh) => {
+                    self.run_fresh(component, &call, &mut usage).await
+                }
+                (Program::Component(_), InstanceMode::Warm(_)) => {
+                    self.run_warm(&call, &mut usage).await
+                }
             }
-            Err(LambdaError::ForceStop.into())
-        });
+        }
+        .instrument(info_span!("limes.lambda.run"))
+        .await;
+        (result, usage)
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.stores.memory_size
+    }
+
+    /// Aborts the calls running longer than `timeout` with `LambdaError::Timeout`
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
 
-        // Get the function Instance from Component
+    /// True once `stop` was called, the lambda refuses every later call
+    pub fn is_stopped(&self) -> bool {
+        self.stores.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Level, limit and function id of the lines written through `limes:log`
+    pub fn set_guest_log(&mut self, guest_log: GuestLogConfig) -> &mut Self {
+        self.guest_log = guest_log;
+        self
+    }
+
+    /// Lines the instances of the lambda wrote on stdout and stderr
+    pub fn logs(&self) -> Arc<LogBuffer> {
+        self.stores.logs.clone()
+    }
+
+    /// Number of idle pre-instantiated stores ready to serve a call
+    pub fn warm_instances(&self) -> usize {
+        self.pool.as_ref().map_or(0, |pool| pool.idle_len())
+    }
+
+    async fn run_fresh(
+        &self,
+        component: &Component,
+        call: &Call<'_>,
+        usage: &mut Usage,
+    ) -> Result<String, LambdaError> {
+        // Setup the Linker and Wasi support
+        let instantiate_start = Instant::now();
+        let engine = component.engine();
+        let (linker, mut store) = info_span!("limes.lambda.linker").in_scope(|| {
+            let linker = Self::linker(component, &self.stores.wasi_flags.dependencies)?;
+            let store = self.stores.build_store(engine, None);
+            Ok::<_, LambdaError>((linker, store))
+        })?;
+
+        // Get the function Instance from Component, its dependencies are instantiated first
+        for dependency in self.stores.wasi_flags.dependencies.iter() {
+            let instance = linker
+                .instantiate_async(&mut store, dependency)
+                .await
+                .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+            store.data_mut().dependencies.push(instance);
+        }
         let instance = linker
-            .instantiate_async(&mut store, &self.component)
+            .instantiate_async(&mut store, component)
+            .instrument(info_span!("limes.lambda.instantiate"))
             .await
             .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
 
         // Get the run function
         let func = self.get_func_run(&instance, &mut store)?;
+        usage.instantiate = instantiate_start.elapsed();
 
         // Exec the function
-        let result = func
-            .call_async(&mut store, (args,))
+        let fuel = self.begin_usage(&mut store, call);
+        let execute_start = Instant::now();
+        let result = self.call_run(&mut store, func, call.args).await;
+        usage.execute = execute_start.elapsed();
+        Self::finish_usage(&store, fuel, usage);
+        let result = result.map_err(|e| self.exec_error(&store, e, usage))?.0;
+
+        Ok(result)
+    }
+
+    async fn run_command(
+        &self,
+        module: &Module,
+        call: &Call<'_>,
+        usage: &mut Usage,
+    ) -> Result<String, LambdaError> {
+        let instantiate_start = Instant::now();
+        let stdin = MemoryInputPipe::new(call.args.to_string());
+        let stdout = MemoryOutputPipe::new(self.stores.memory_size);
+        let mut store = self
+            .stores
+            .build_store(module.engine(), Some((stdin, stdout.clone())));
+        let linker = Self::command_linker(module.engine())?;
+        let instance = linker
+            .instantiate_async(&mut store, module)
+            .instrument(info_span!("limes.lambda.instantiate"))
             .await
-            .map_err(|_| match self.stop.load(Ordering::Relaxed) {
-                true => LambdaError::ForceStop,
-                false => LambdaError::FunctionExecError,
-            })?
-            .0;
-
-        // Reset the store even though it will be de-allocated.
-        // I will remove it soon and change the way the function exec.
-        // let _ = func.post_return_async(&mut store).await;
+            .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+        let start = instance
+            .get_typed_func::<(), ()>(&mut store, COMMAND_EXPORT)
+            .map_err(|e| LambdaError::FunctionRetrievError(e.to_string()))?;
+        usage.instantiate = instantiate_start.elapsed();
+
+        let fuel = self.begin_usage(&mut store, call);
+        let execute_start = Instant::now();
+        let deadline = store.data().deadline;
+        let run = start
+            .call_async(&mut store, ())
+            .instrument(info_span!("limes.lambda.call"));
+        let result = self.bounded(deadline, run).await;
+        usage.execute = execute_start.elapsed();
+        Self::finish_usage(&store, fuel, usage);
+
+        // `proc_exit(0)` ends the command as returning from `_start` does
+        if let Err(e) = result {
+            match e.downcast_ref::<I32Exit>() {
+                Some(I32Exit(0)) if !self.is_stopped() => {}
+                Some(I32Exit(code)) if !self.is_stopped() => {
+                    return Err(LambdaError::CommandExit(*code))
+                }
+                _ => return Err(self.exec_error(&store, e, usage)),
+            }
+        }
+        Ok(String::from_utf8_lossy(&stdout.contents()).into_owned())
+    }
+
+    async fn run_warm(&self, call: &Call<'_>, usage: &mut Usage) -> Result<String, LambdaError> {
+        let pool = self.pool.as_ref().ok_or(LambdaError::InstanceBuilderError(
+            "the lambda has no warm pool".to_string(),
+        ))?;
+        let instantiate_start = Instant::now();
+        let mut warm = async {
+            let mut warm = match pool.checkout() {
+                Some(warm) => warm,
+                None => pool.instantiate().await?,
+            };
+
+            // A used instance goes back to the state captured after instantiation
+            if warm.uses > 0 {
+                for (_, restore) in pool.snapshot_exports.iter() {
+                    warm.call_state_export(restore).await?;
+                }
+            }
+            Ok::<_, LambdaError>(warm)
+        }
+        .instrument(info_span!("limes.lambda.instantiate", warm = true))
+        .await?;
+
+        // Exec the function, a trapped instance is never put back in the pool
+        let func = self.get_func_run(&warm.instance, &mut warm.store)?;
+        usage.instantiate = instantiate_start.elapsed();
+        let fuel = self.begin_usage(&mut warm.store, call);
+        let execute_start = Instant::now();
+        let result = self.call_run(&mut warm.store, func, call.args).await;
+        usage.execute = execute_start.elapsed();
+        Self::finish_usage(&warm.store, fuel, usage);
+        let result = match result {
+            Ok((result,)) => result,
+            Err(e) => {
+                pool.refill();
+                return Err(self.exec_error(&warm.store, e, usage));
+            }
+        };
+
+        // An instance that is not put back is replaced in the background
+        warm.uses += 1;
+        if pool.config.reset_policy != ResetPolicy::Discard
+            && warm.uses < pool.config.max_reuse
+            && func.post_return_async(&mut warm.store).await.is_ok()
+        {
+            pool.put_back(warm);
+        } else {
+            pool.refill();
+        }
         Ok(result)
     }
 
+    // Calls `run`, bounded by the deadline of the store. Dropping the call on timeout covers a
+    // guest awaiting the host, the epoch bump covers a guest spinning on its own
+    async fn call_run(
+        &self,
+        store: &mut Store<LambdaState>,
+        func: TypedFunc<(&str,), (String,)>,
+        args: &str,
+    ) -> Result<(String,)> {
+        let deadline = store.data().deadline;
+        let call = func
+            .call_async(&mut *store, (args,))
+            .instrument(info_span!("limes.lambda.call"));
+        self.bounded(deadline, call).await
+    }
+
+    async fn bounded<R>(
+        &self,
+        deadline: Option<Instant>,
+        call: impl Future<Output = Result<R>>,
+    ) -> Result<R> {
+        let Some(deadline) = deadline else {
+            return call.await;
+        };
+        let _watch = DeadlineWatch::new(self.program.engine(), deadline);
+        match tokio::time::timeout_at(deadline.into(), call).await {
+            Ok(result) => result,
+            Err(_) => Err(LambdaError::Timeout.into()),
+        }
+    }
+
+    // Resets the counters of the store, returns the fuel left when the engine consumes fuel
+    fn begin_usage(&self, store: &mut Store<LambdaState>, call: &Call) -> Option<u64> {
+        let context = call.context;
+        let deadline = match (self.timeout, context.deadline) {
+            (Some(timeout), Some(deadline)) => Some(deadline.min(Instant::now() + timeout)),
+            (Some(timeout), None) => Some(Instant::now() + timeout),
+            (None, deadline) => deadline,
+        };
+        // The nested calls of the guest are stopped along with this lambda
+        let mut callers = context.callers.clone();
+        callers.push(self.stores.stop.clone());
+
+        let state = store.data_mut();
+        state.usage.begin();
+        state
+            .guest_log
+            .begin(&self.guest_log, context.invocation_id.as_deref());
+        state.invoke.begin(CallContext {
+            invocation_id: context.invocation_id.clone(),
+            depth: context.depth,
+            deadline,
+            callers,
+        });
+        state.deadline = deadline;
+        *state.guest_error.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        store.get_fuel().ok()
+    }
+
+    fn finish_usage(store: &Store<LambdaState>, fuel: Option<u64>, usage: &mut Usage) {
+        store.data().usage.finish(usage);
+        usage.fuel_used = fuel
+            .zip(store.get_fuel().ok())
+            .map(|(before, after)| before.saturating_sub(after));
+    }
+
+    fn exec_error(
+        &self,
+        store: &Store<LambdaState>,
+        error: anyhow::Error,
+        usage: &mut Usage,
+    ) -> LambdaError {
+        if self.is_stopped() {
+            return LambdaError::ForceStop;
+        }
+        match error.downcast_ref::<LambdaError>() {
+            Some(LambdaError::Timeout) => return LambdaError::Timeout,
+            Some(LambdaError::ForceStop) => return LambdaError::ForceStop,
+            _ => {}
+        }
+        usage.trap = Some(match error.downcast_ref::<Trap>() {
+            Some(trap) => format!("{:?}", trap),
+            None => "Host".to_string(),
+        });
+        // A guest failing on purpose tells why before trapping
+        let guest_error = store.data().guest_error.lock();
+        match guest_error.unwrap_or_else(|e| e.into_inner()).take() {
+            Some(error) => LambdaError::GuestError(error),
+            None => LambdaError::FunctionExecError,
+        }
+    }
+
+    // Pairs of snapshot and restore exports added by the instrumentation, one per core instance
+    fn find_snapshot_exports(component: &Component) -> Result<Vec<(String, String)>, LambdaError> {
+        let engine = component.engine();
+        let component_type = component.component_type();
+        let snapshot_exports: Vec<(String, String)> = component_type
+            .exports(engine)
+            .filter_map(|(name, _)| name.strip_prefix(SNAPSHOT_EXPORT_PREFIX))
+            .map(|n| {
+                (
+                    format!("{}{}", SNAPSHOT_EXPORT_PREFIX, n),
+                    format!("{}{}", RESTORE_EXPORT_PREFIX, n),
+                )
+            })
+            .collect();
+        if snapshot_exports.is_empty() {
+            return Err(LambdaError::SnapshotError(
+                "the component was not instrumented for snapshots".to_string(),
+            ));
+        }
+        Ok(snapshot_exports)
+    }
+
     pub async fn stop(&self) -> Result<(), LambdaError> {
-        let engine = self.component.engine();
-        if self.stop.load(Ordering::Relaxed) {
+        let engine = self.program.engine();
+        if self.is_stopped() {
             return Err(LambdaError::FunctionNotRunning);
         }
-        self.stop.store(true, Ordering::Relaxed);
+        self.stores.stop.store(true, Ordering::Relaxed);
         engine.increment_epoch();
         Ok(())
     }
 
+    // Makes the running instances reach a yield point, so a dropped call is torn down promptly
+    pub fn interrupt(&self) {
+        self.program.engine().increment_epoch();
+    }
+
     fn get_func_run(
         &self,
         instance: &Instance,
@@ -144,23 +812,141 @@ impl Lambda {
     ) -> Result<TypedFunc<(&str,), (String,)>, LambdaError> {
         let interface_idx = instance
             .get_export(&mut *store, None, "component:run/run")
-            .ok_or(LambdaError::FunctionInterfaceError)
-            .map_err(|e| e)?;
+            .ok_or(LambdaError::FunctionInterfaceError)?;
 
         let func_idx = instance
             .get_export(&mut *store, Some(&interface_idx), "run")
-            .ok_or(LambdaError::FunctionInterfaceRetrievError)
-            .map_err(|e| e)?;
+            .ok_or(LambdaError::FunctionInterfaceRetrievError)?;
 
-        Ok(instance
+        instance
             .get_typed_func::<(&str,), (String,)>(&mut *store, func_idx)
-            .map_err(|e| LambdaError::FunctionRetrievError(e.to_string()))?)
+            .map_err(|e| LambdaError::FunctionRetrievError(e.to_string()))
+    }
+
+    /// Checks every import of `component` and of its dependencies is provided by the host or
+    /// by a dependency, so a component that can not be linked is refused before any run
+    pub fn check_imports(
+        component: &Component,
+        dependencies: &[Arc<Component>],
+    ) -> Result<(), LambdaError> {
+        let linker = Self::linker(component, dependencies)?;
+        for component in dependencies
+            .iter()
+            .map(|dependency| dependency.as_ref())
+            .chain(std::iter::once(component))
+        {
+            linker
+                .instantiate_pre(component)
+                .map_err(|e| LambdaError::InstanceBuilderError(format!("{:#}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Checks every import of a core module is a wasip1 function
+    pub fn check_command(module: &Module) -> Result<(), LambdaError> {
+        Self::command_linker(module.engine())?
+            .instantiate_pre(module)
+            .map_err(|e| LambdaError::InstanceBuilderError(format!("{:#}", e)))?;
+        Ok(())
+    }
+
+    fn command_linker(engine: &Engine) -> Result<wasmtime::Linker<LambdaState>, LambdaError> {
+        let mut linker = wasmtime::Linker::new(engine);
+        preview1::add_to_linker_async(&mut linker, |state: &mut LambdaState| &mut state.wasi)
+            .map_err(|e| LambdaError::WasiAsyncLinkerError(e.to_string()))?;
+        Ok(linker)
+    }
+
+    fn linker(
+        component: &Component,
+        dependencies: &[Arc<Component>],
+    ) -> Result<Linker<LambdaState>, LambdaError> {
+        let mut linker = Linker::new(component.engine());
+        wasmtime_wasi::add_to_linker_async(&mut linker)
+            .and_then(|_| usage::add_metered_filesystem(&mut linker))
+            .and_then(|_| keyvalue::add_to_linker(&mut linker))
+            .and_then(|_| guest_log::add_to_linker(&mut linker))
+            .and_then(|_| invoke::add_to_linker(&mut linker))
+            .map_err(|e| LambdaError::WasiAsyncLinkerError(e.to_string()))?;
+        Self::link_dependencies(&mut linker, component, dependencies)
+            .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+        Ok(linker)
+    }
+
+    // Defines the interfaces the dependencies export and the components import, every function
+    // forwards the call to the instance of the dependency living in the same store
+    fn link_dependencies(
+        linker: &mut Linker<LambdaState>,
+        component: &Component,
+        dependencies: &[Arc<Component>],
+    ) -> Result<()> {
+        let engine = component.engine();
+        let mut imported = HashSet::new();
+        for component in std::iter::once(component)
+            .chain(dependencies.iter().map(|dependency| dependency.as_ref()))
+        {
+            for (name, _) in component.component_type().imports(engine) {
+                imported.insert(name.to_string());
+            }
+        }
+        for (index, dependency) in dependencies.iter().enumerate() {
+            for (name, item) in dependency.component_type().exports(engine) {
+                let ComponentItem::ComponentInstance(instance_type) = item else {
+                    continue;
+                };
+                if !imported.contains(name) {
+                    continue;
+                }
+                let (_, instance_index) = dependency
+                    .export_index(None, name)
+                    .ok_or_else(|| anyhow::anyhow!("`{}` is not exported", name))?;
+                let mut linker_instance = linker.instance(name)?;
+                for (func_name, func_item) in instance_type.exports(engine) {
+                    let ComponentItem::ComponentFunc(_) = func_item else {
+                        continue;
+                    };
+                    let (_, func_index) = dependency
+                        .export_index(Some(&instance_index), func_name)
+                        .ok_or_else(|| anyhow::anyhow!("`{}` is not exported", func_name))?;
+                    linker_instance.func_new_async(
+                        func_name,
+                        move |mut store, params, results| {
+                            Box::new(async move {
+                                let instance =
+                                    *store.data().dependencies.get(index).ok_or_else(|| {
+                                        anyhow::anyhow!(
+                                            "the dependency {} is not instantiated",
+                                            index
+                                        )
+                                    })?;
+                                let func =
+                                    instance.get_func(&mut store, func_index).ok_or_else(|| {
+                                        anyhow::anyhow!("the export is not a function")
+                                    })?;
+                                func.call_async(&mut store, params, results).await?;
+                                func.post_return_async(&mut store).await
+                            })
+                        },
+                    )?;
+                }
+            }
+        }
+        Ok(())
     }
+}
 
-    fn build_wasi_ctx(&self) -> WasiCtx {
+impl StoreConfig {
+    // A command reads `stdio.0` and writes its result on `stdio.1`, the stdout of a component
+    // goes to the logs
+    fn build_wasi_ctx(
+        &self,
+        io: Arc<IoCounters>,
+        stdio: Option<(MemoryInputPipe, MemoryOutputPipe)>,
+        guest_error: GuestErrorSlot,
+    ) -> WasiP1Ctx {
         let mut wasictx = WasiCtxBuilder::new();
-        if let Some(_) = self.wasi_flags.socket_addr_check {
-            let ip_checker = self.gen_check_ip_closure();
+        if self.wasi_flags.socket_addr_check.is_some() {
+            let ip_checker = self.gen_check_ip_closure(io);
             wasictx.socket_addr_check(ip_checker);
         }
         if let Some(map) = &self.wasi_flags.file_mapper {
@@ -173,48 +959,217 @@ impl Lambda {
                     .expect("Could not map the files from host to the guest runtime");
             }
         }
-        #[cfg(debug_assertions)]
-        {
-            wasictx.inherit_stdio();
+        for (key, value) in self.wasi_flags.env.iter() {
+            wasictx.env(key, value);
         }
-        wasictx.build()
+        match stdio {
+            Some((stdin, stdout)) => wasictx.stdin(stdin).stdout(stdout),
+            None => wasictx.stdout(GuestOutput::new(LogStream::Stdout, self.logs.clone())),
+        };
+        wasictx
+            .args(&self.wasi_flags.args)
+            .stderr(GuestOutput::with_errors(
+                LogStream::Stderr,
+                self.logs.clone(),
+                Some(guest_error),
+            ));
+        wasictx.build_p1()
     }
 
-    fn build_store(&self, engine: &Engine, wasi: WasiCtx) -> Store<LambdaState> {
-        let resource = ResourceTable::new();
+    fn build_store(
+        &self,
+        engine: &Engine,
+        stdio: Option<(MemoryInputPipe, MemoryOutputPipe)>,
+    ) -> Store<LambdaState> {
         let store_limits = StoreLimitsBuilder::new()
             .memory_size(self.memory_size)
             .build();
+        let io = Arc::new(IoCounters::default());
+        let guest_error = GuestErrorSlot::default();
         let state = LambdaState {
-            wasi_ctx: wasi,
-            resource_table: resource,
-            limiter: store_limits,
+            wasi: self.build_wasi_ctx(io.clone(), stdio, guest_error.clone()),
+            usage: UsageTracker::new(store_limits, io),
+            deadline: None,
+            keyvalue: KvHost::new(self.wasi_flags.keyvalue.clone()),
+            guest_log: GuestLogger::default(),
+            invoke: InvokeHost::new(self.wasi_flags.invoke.clone()),
+            dependencies: Vec::new(),
+            guest_error,
         };
         let mut store = Store::new(engine, state);
-        store.limiter(|data| &mut data.limiter);
+        store.limiter(|data| &mut data.usage);
+
+        // Only engines consuming fuel accept it, the others report no fuel used
+        let _ = store.set_fuel(u64::MAX);
+
+        // Store register epoch_deadline_callback
+        let stop = self.stop.clone();
+        store.epoch_deadline_callback(move |store| {
+            if stop.load(Ordering::Relaxed) || store.data().invoke.is_stopped() {
+                return Err(LambdaError::ForceStop.into());
+            }
+            if store.data().deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(LambdaError::Timeout.into());
+            }
+            Ok(UpdateDeadline::Yield(1))
+        });
         store
     }
 
     // Closure for ip checks
-    fn gen_check_ip_closure(
-        &self,
-    ) -> Box<
-        dyn Fn(SocketAddr, SocketAddrUse) -> Pin<Box<dyn Future<Output = bool> + Send + Sync>>
-            + Send
-            + Sync
-            + 'static,
-    > {
-        let local_tap_ip = self.tap_ip.clone();
+    fn gen_check_ip_closure(&self, io: Arc<IoCounters>) -> SocketAddrCheck {
+        let local_tap_ip = self.tap_ip;
+        let outbound = Arc::new(self.wasi_flags.outbound.clone());
         Box::new(move |socket, socket_check| {
+            let io = io.clone();
+            let outbound = outbound.clone();
             Box::pin(async move {
                 match socket_check {
                     SocketAddrUse::TcpBind | SocketAddrUse::UdpBind => match socket {
                         SocketAddr::V4(socket_v4) => socket_v4.ip().eq(&local_tap_ip),
                         SocketAddr::V6(_) => false,
                     },
+                    SocketAddrUse::TcpConnect | SocketAddrUse::UdpConnect => {
+                        let allowed = outbound
+                            .as_ref()
+                            .as_ref()
+                            .is_none_or(|rules| rules.iter().any(|rule| rule.allows(&socket)));
+                        if allowed {
+                            io.add_socket();
+                        }
+                        allowed
+                    }
                     _ => true,
                 }
             })
         })
     }
 }
+
+impl WarmPool {
+    async fn new(
+        component: &Arc<Component>,
+        config: &WarmPoolConfig,
+        stores: &StoreConfig,
+    ) -> Result<Arc<Self>, LambdaError> {
+        let linker = Lambda::linker(component, &stores.wasi_flags.dependencies)?;
+        let instance_pre = linker
+            .instantiate_pre(component)
+            .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+        let mut dependency_pres = Vec::new();
+        for dependency in stores.wasi_flags.dependencies.iter() {
+            let dependency_pre = linker
+                .instantiate_pre(dependency)
+                .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+            dependency_pres.push(dependency_pre);
+        }
+        let snapshot_exports = match config.reset_policy {
+            ResetPolicy::Snapshot { .. } => Lambda::find_snapshot_exports(component)?,
+            _ => Vec::new(),
+        };
+        let pool = Self {
+            config: config.clone(),
+            engine: component.engine().clone(),
+            stores: stores.clone(),
+            instance_pre,
+            dependency_pres,
+            snapshot_exports,
+            idle: Mutex::new(Vec::with_capacity(config.size)),
+            refilling: AtomicUsize::new(0),
+        };
+        for _ in 0..config.size {
+            let warm = pool.instantiate().await?;
+            pool.put_back(warm);
+        }
+        Ok(Arc::new(pool))
+    }
+
+    // Number of instances idle for less than the ttl, the others are dropped
+    fn idle_len(&self) -> usize {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.retain(|warm| warm.last_used.elapsed() < self.config.idle_ttl);
+        idle.len()
+    }
+
+    // Take the most recently used instance, dropping the ones idle for too long
+    fn checkout(&self) -> Option<WarmInstance> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.retain(|warm| warm.last_used.elapsed() < self.config.idle_ttl);
+        idle.pop()
+    }
+
+    fn put_back(&self, mut warm: WarmInstance) {
+        warm.last_used = Instant::now();
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < self.config.size {
+            idle.push(warm);
+        }
+    }
+
+    // Builds an instance in the background to take the place of one that was not put back,
+    // unless the pool is full or the lambda stopped
+    fn refill(self: &Arc<Self>) {
+        if self.stores.stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let pending = self.refilling.fetch_add(1, Ordering::Relaxed);
+        if self.idle_len() + pending >= self.config.size {
+            self.refilling.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+        let pool = self.clone();
+        tokio::spawn(async move {
+            if let Ok(warm) = pool.instantiate().await {
+                pool.put_back(warm);
+            }
+            pool.refilling.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    async fn instantiate(&self) -> Result<WarmInstance, LambdaError> {
+        let mut store = self.stores.build_store(&self.engine, None);
+        for dependency_pre in self.dependency_pres.iter() {
+            let instance = dependency_pre
+                .instantiate_async(&mut store)
+                .await
+                .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+            store.data_mut().dependencies.push(instance);
+        }
+        let instance = self
+            .instance_pre
+            .instantiate_async(&mut store)
+            .await
+            .map_err(|e| LambdaError::InstanceBuilderError(e.to_string()))?;
+        let mut warm = WarmInstance {
+            store,
+            instance,
+            uses: 0,
+            last_used: Instant::now(),
+        };
+
+        if let ResetPolicy::Snapshot { init_export } = &self.config.reset_policy {
+            if let Some(init_export) = init_export {
+                warm.call_state_export(init_export).await?;
+            }
+            for (snapshot, _) in self.snapshot_exports.iter() {
+                warm.call_state_export(snapshot).await?;
+            }
+        }
+        Ok(warm)
+    }
+}
+
+impl WarmInstance {
+    async fn call_state_export(&mut self, name: &str) -> Result<(), LambdaError> {
+        let func = self
+            .instance
+            .get_typed_func::<(), ()>(&mut self.store, name)
+            .map_err(|e| LambdaError::SnapshotError(e.to_string()))?;
+        func.call_async(&mut self.store, ())
+            .await
+            .map_err(|e| LambdaError::SnapshotError(e.to_string()))?;
+        func.post_return_async(&mut self.store)
+            .await
+            .map_err(|e| LambdaError::SnapshotError(e.to_string()))
+    }
+}
@@ -1,5 +1,26 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Error a `limes-guest` function failed with, read from the
+/// `{"error":{"code":..,"message":..}}` line it writes on stderr before trapping
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GuestError {
+    pub code: String,
+    pub message: String,
+}
+
+impl GuestError {
+    pub fn from_line(line: &str) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct ErrorLine {
+            error: GuestError,
+        }
+        serde_json::from_str::<ErrorLine>(line)
+            .ok()
+            .map(|line| line.error)
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum LambdaError {
     #[error("Wasm memory function error")]
@@ -16,6 +37,8 @@ pub enum LambdaError {
     ModuleNotFound,
     #[error("Wasm instance build error: `{0}`")]
     InstanceBuilderError(String),
+    #[error("The command exited with code `{0}`")]
+    CommandExit(i32),
     #[error("Wasm function was forced to stop")]
     ForceStop,
     #[error("Wasm could not build the Engine")]
@@ -28,4 +51,10 @@ pub enum LambdaError {
     WasiAsyncLinkerError(String),
     #[error("Allocate at least 2Mb of memory")]
     NotEnoughtMemory,
+    #[error("Wasm instance snapshot error: `{0}`")]
+    SnapshotError(String),
+    #[error("Wasm function exceeded its timeout")]
+    Timeout,
+    #[error("Wasm function failed with `{}`: {}", .0.code, .0.message)]
+    GuestError(GuestError),
 }
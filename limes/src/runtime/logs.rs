@@ -0,0 +1,205 @@
+use super::lambda_error::GuestError;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmtime_wasi::{OutputStream, Pollable, StdoutStream, StreamResult};
+
+// Longer lines are split, so a guest never writing a newline can not grow a line forever
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// A line written by a guest
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LogLine {
+    /// Position of the line in the function output, increasing by one per line
+    pub seq: u64,
+    /// Unix time in milliseconds
+    pub timestamp_ms: u64,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/// The last lines written by every instance of a function, older lines are dropped once
+/// `capacity` is reached
+pub struct LogBuffer {
+    capacity: usize,
+    lines: Mutex<LogLines>,
+}
+
+#[derive(Default)]
+struct LogLines {
+    next_seq: u64,
+    lines: VecDeque<LogLine>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(LogLines::default()),
+        }
+    }
+
+    pub fn push(&self, stream: LogStream, line: String) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = lines.next_seq;
+        lines.next_seq += 1;
+        if lines.lines.len() >= self.capacity {
+            lines.lines.pop_front();
+        }
+        lines.lines.push_back(LogLine {
+            seq,
+            timestamp_ms,
+            stream,
+            line,
+        });
+    }
+
+    /// The lines kept with a `seq` greater than `after`, only the last `tail` when given
+    pub fn lines(&self, after: Option<u64>, tail: Option<usize>) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        let mut selected: Vec<LogLine> = lines
+            .lines
+            .iter()
+            .filter(|line| after.is_none_or(|after| line.seq > after))
+            .cloned()
+            .collect();
+        if let Some(tail) = tail {
+            selected.drain(..selected.len().saturating_sub(tail));
+        }
+        selected
+    }
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+/// Last guest error line written on the stderr of a store
+pub type GuestErrorSlot = Arc<Mutex<Option<GuestError>>>;
+
+/// Stdout or stderr of a store, writing the guest output line by line into a `LogBuffer`.
+/// The last line is kept even without a trailing newline once the store is dropped
+pub struct GuestOutput {
+    sink: Arc<LineSink>,
+}
+
+impl GuestOutput {
+    pub fn new(stream: LogStream, buffer: Arc<LogBuffer>) -> Self {
+        Self::with_errors(stream, buffer, None)
+    }
+
+    /// Same as `new`, the guest error lines are also kept in `errors`
+    pub fn with_errors(
+        stream: LogStream,
+        buffer: Arc<LogBuffer>,
+        errors: Option<GuestErrorSlot>,
+    ) -> Self {
+        Self {
+            sink: Arc::new(LineSink {
+                stream,
+                buffer,
+                errors,
+                partial: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+}
+
+impl StdoutStream for GuestOutput {
+    fn stream(&self) -> Box<dyn OutputStream> {
+        Box::new(GuestOutputStream {
+            sink: self.sink.clone(),
+        })
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+struct LineSink {
+    stream: LogStream,
+    buffer: Arc<LogBuffer>,
+    errors: Option<GuestErrorSlot>,
+    // Bytes written after the last newline
+    partial: Mutex<Vec<u8>>,
+}
+
+impl LineSink {
+    fn write(&self, bytes: &[u8]) {
+        let mut partial = self.partial.lock().unwrap_or_else(|e| e.into_inner());
+        for byte in bytes.iter() {
+            match byte {
+                b'\n' => self.push(&mut partial),
+                _ => {
+                    partial.push(*byte);
+                    if partial.len() >= MAX_LINE_LEN {
+                        self.push(&mut partial);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&self, partial: &mut Vec<u8>) {
+        let line = String::from_utf8_lossy(partial)
+            .trim_end_matches('\r')
+            .to_string();
+        partial.clear();
+        if let Some(errors) = &self.errors {
+            if let Some(error) = GuestError::from_line(&line) {
+                *errors.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+            }
+        }
+        self.buffer.push(self.stream, line);
+    }
+}
+
+impl Drop for LineSink {
+    fn drop(&mut self) {
+        let mut partial = std::mem::take(self.partial.get_mut().unwrap_or_else(|e| e.into_inner()));
+        if !partial.is_empty() {
+            self.push(&mut partial);
+        }
+    }
+}
+
+struct GuestOutputStream {
+    sink: Arc<LineSink>,
+}
+
+#[async_trait::async_trait]
+impl Pollable for GuestOutputStream {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for GuestOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.sink.write(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(MAX_LINE_LEN)
+    }
+}
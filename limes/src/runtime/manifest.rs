@@ -0,0 +1,435 @@
+use super::guest_log::GuestLogLevel;
+use super::lambda::{InstanceMode, OutboundRule, ResetPolicy, WarmPoolConfig, WasiFlags};
+use super::runtime_error::RuntimeError;
+use crate::db::kv::KvQuota;
+use crate::tools::units::{
+    deserialize_bytes, deserialize_bytes_opt, deserialize_duration, deserialize_duration_opt,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wasmtime_wasi::{DirPerms, FilePerms};
+
+/// Name of the manifest looked up next to the module
+pub const MANIFEST_FILE: &str = "limes.toml";
+/// The only world a function can implement, it exports `component:run/run`
+pub const RUN_WORLD: &str = "component:run/runnable";
+/// Export of the run interface required by `RUN_WORLD`
+pub const RUN_INTERFACE: &str = "component:run/run";
+
+/// Everything needed to deploy a function: where the module is and how its function runs.
+/// Written as `limes.toml` next to the component, every field but `module` is optional
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FunctionManifest {
+    /// Informative name of the function, the module is published under it with `version`
+    pub name: Option<String>,
+    /// Version the module is published as, `name@version` then refers to it
+    pub version: Option<String>,
+    /// Path of the component, relative to the manifest
+    pub module: PathBuf,
+    #[serde(default = "default_world")]
+    pub world: String,
+    /// Export run once at registration, its state is baked in the function
+    pub preinit: Option<String>,
+    /// Registered modules exporting the interfaces the component imports
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// `module` is a wasip1 core module wrapped into a component at registration
+    #[serde(default)]
+    pub adapt_wasip1: bool,
+    /// Linear memory of the function, e.g. `64MiB`, an equal share of the runtime by default
+    #[serde(default, deserialize_with = "deserialize_bytes_opt")]
+    pub memory: Option<usize>,
+    /// Longest a call may run, e.g. `5s`
+    #[serde(default, deserialize_with = "deserialize_duration_opt")]
+    pub timeout: Option<Duration>,
+    /// Calls running at once, the others wait for a free slot
+    pub concurrency: Option<usize>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Command line of the guest, the name of the program first
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Host directories mapped into the guest
+    #[serde(default, rename = "mount")]
+    pub mounts: Vec<Mount>,
+    #[serde(default)]
+    pub network: NetworkPolicy,
+    /// Serves the calls from pre-instantiated stores instead of fresh ones
+    pub warm_pool: Option<WarmPoolSection>,
+    /// Namespace behind `limes:keyvalue/store`
+    #[serde(default)]
+    pub kv: KvSection,
+    /// Lines written through `limes:log/logging`
+    #[serde(default)]
+    pub log: LogSection,
+    /// Functions reachable through `limes:invoke/call`
+    #[serde(default)]
+    pub invoke: InvokeSection,
+}
+
+fn default_world() -> String {
+    RUN_WORLD.to_string()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mount {
+    /// Host directory, relative to the manifest
+    pub host: PathBuf,
+    pub guest: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkPolicy {
+    /// Without it the guest can not open any socket
+    pub enabled: bool,
+    /// The only address the guest may bind, the runtime default when missing
+    pub tap_ip: Option<Ipv4Addr>,
+    /// `ip` or `ip:port` the guest may connect to, any address when missing
+    pub outbound: Option<Vec<String>>,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tap_ip: None,
+            outbound: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvokeSection {
+    /// Function ids the function may call, `*` allows any, none by default
+    pub allow: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    /// Lowest level reaching the host log
+    pub level: GuestLogLevel,
+    /// Lines kept per invocation, the next ones are dropped
+    pub max_lines: usize,
+}
+
+impl Default for LogSection {
+    fn default() -> Self {
+        Self {
+            level: GuestLogLevel::Info,
+            max_lines: 100,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KvScope {
+    /// Kept until the function is removed
+    #[default]
+    Function,
+    /// Shared by the functions of the module, kept until the module is removed
+    Module,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KvSection {
+    pub scope: KvScope,
+    /// Shared by every function naming it and kept when they are removed, overrides `scope`
+    pub namespace: Option<String>,
+    /// Bytes of keys and values the function may store, e.g. `1MiB`
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub quota: usize,
+    pub max_keys: usize,
+}
+
+impl Default for KvSection {
+    fn default() -> Self {
+        let quota = KvQuota::default();
+        Self {
+            scope: KvScope::Function,
+            namespace: None,
+            quota: quota.max_bytes,
+            max_keys: quota.max_keys,
+        }
+    }
+}
+
+impl KvSection {
+    pub fn quota(&self) -> KvQuota {
+        KvQuota {
+            max_bytes: self.quota,
+            max_keys: self.max_keys,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetSection {
+    #[default]
+    PostReturn,
+    Discard,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmPoolSection {
+    pub size: usize,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub idle_ttl: Duration,
+    pub max_reuse: usize,
+    pub reset: ResetSection,
+}
+
+impl Default for WarmPoolSection {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            idle_ttl: Duration::from_secs(60),
+            max_reuse: 1000,
+            reset: ResetSection::PostReturn,
+        }
+    }
+}
+
+impl From<&WarmPoolSection> for WarmPoolConfig {
+    fn from(pool: &WarmPoolSection) -> Self {
+        WarmPoolConfig::new(
+            pool.size,
+            pool.idle_ttl,
+            pool.max_reuse,
+            match pool.reset {
+                ResetSection::PostReturn => ResetPolicy::PostReturn,
+                ResetSection::Discard => ResetPolicy::Discard,
+            },
+        )
+    }
+}
+
+/// Ids of a function deployed from a manifest
+#[derive(Clone, Debug, Serialize)]
+pub struct Deployment {
+    pub module_id: String,
+    pub function_id: String,
+}
+
+// Everything a function is initialized with
+#[derive(Clone)]
+pub(crate) struct FunctionSettings {
+    pub tap_ip: Ipv4Addr,
+    pub instance_mode: InstanceMode,
+    pub memory: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub concurrency: Option<usize>,
+    pub wasi_flags: WasiFlags,
+    pub kv: KvSection,
+    pub log: LogSection,
+    pub invoke: InvokeSection,
+}
+
+impl FunctionSettings {
+    pub fn new(tap_ip: Ipv4Addr, instance_mode: InstanceMode) -> Self {
+        Self {
+            tap_ip,
+            instance_mode,
+            memory: None,
+            timeout: None,
+            concurrency: None,
+            wasi_flags: WasiFlags::default(),
+            kv: KvSection::default(),
+            log: LogSection::default(),
+            invoke: InvokeSection::default(),
+        }
+    }
+}
+
+impl FunctionManifest {
+    /// Reads `path`, or `path/limes.toml` when it is a directory. The module and the mounts
+    /// are resolved relative to the directory of the manifest
+    pub fn from_file(path: &Path) -> Result<Self, RuntimeError> {
+        let path = match path.is_dir() {
+            true => path.join(MANIFEST_FILE),
+            false => path.to_path_buf(),
+        };
+        let text = std::fs::read_to_string(&path).map_err(|e| {
+            RuntimeError::ManifestError(format!("could not read {}: {}", path.display(), e))
+        })?;
+        let mut manifest = Self::from_toml(&text)?;
+        if let Some(dir) = path.parent() {
+            manifest.module = dir.join(&manifest.module);
+            for mount in manifest.mounts.iter_mut() {
+                mount.host = dir.join(&mount.host);
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, RuntimeError> {
+        let manifest: Self =
+            toml::from_str(text).map_err(|e| RuntimeError::ManifestError(e.to_string()))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Reads the component the manifest points to
+    pub fn read_module(&self) -> Result<Vec<u8>, RuntimeError> {
+        std::fs::read(&self.module).map_err(|e| {
+            RuntimeError::ManifestError(format!("could not read {}: {}", self.module.display(), e))
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), RuntimeError> {
+        let invalid = |message: String| Err(RuntimeError::ManifestError(message));
+        if self.world != RUN_WORLD {
+            return invalid(format!(
+                "world `{}` is not supported, use `{}`",
+                self.world, RUN_WORLD
+            ));
+        }
+        if self.version.is_some() && self.name.is_none() {
+            return invalid("version needs a name to be published under".to_string());
+        }
+        if self.preinit.is_some() && !self.dependencies.is_empty() {
+            return invalid("preinit can not be combined with dependencies".to_string());
+        }
+        if self.memory.is_some_and(|memory| memory < 1024 * 1024 * 2) {
+            return invalid("memory must be at least 2MiB".to_string());
+        }
+        if self.timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return invalid("timeout must be > 0".to_string());
+        }
+        if self.concurrency == Some(0) {
+            return invalid("concurrency must be > 0".to_string());
+        }
+        if self.warm_pool.as_ref().is_some_and(|pool| pool.size == 0) {
+            return invalid("warm_pool.size must be > 0".to_string());
+        }
+        if self
+            .env
+            .keys()
+            .any(|key| key.is_empty() || key.contains('='))
+        {
+            return invalid("env names must be non empty and without `=`".to_string());
+        }
+        for mount in self.mounts.iter() {
+            if !mount.guest.starts_with('/') {
+                return invalid(format!(
+                    "mount guest path `{}` must be absolute",
+                    mount.guest
+                ));
+            }
+        }
+        if self
+            .kv
+            .namespace
+            .as_ref()
+            .is_some_and(|name| name.is_empty())
+        {
+            return invalid("kv.namespace must be non empty".to_string());
+        }
+        if self.kv.quota == 0 || self.kv.max_keys == 0 {
+            return invalid("kv.quota and kv.max_keys must be > 0".to_string());
+        }
+        if self.log.max_lines == 0 {
+            return invalid("log.max_lines must be > 0".to_string());
+        }
+        if self.invoke.allow.iter().any(|id| id.is_empty()) {
+            return invalid("invoke.allow entries must be non empty".to_string());
+        }
+        self.outbound_rules()?;
+        Ok(())
+    }
+
+    /// Checks the manifest against the module it deploys, a wasip1 core module runs as a
+    /// command on fresh instances only
+    pub fn validate_module(&self, bytes: &[u8]) -> Result<(), RuntimeError> {
+        self.validate()?;
+        if !wasmparser::Parser::is_core_wasm(bytes) {
+            return Ok(());
+        }
+        let invalid = |setting: &str| {
+            Err(RuntimeError::ManifestError(format!(
+                "{} is not supported by a core module",
+                setting
+            )))
+        };
+        if self.preinit.is_some() {
+            return invalid("preinit");
+        }
+        if self.warm_pool.is_some() {
+            return invalid("warm_pool");
+        }
+        Ok(())
+    }
+
+    fn outbound_rules(&self) -> Result<Option<Vec<OutboundRule>>, RuntimeError> {
+        self.network
+            .outbound
+            .as_ref()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|rule| rule.parse().map_err(RuntimeError::ManifestError))
+                    .collect()
+            })
+            .transpose()
+    }
+
+    pub(crate) fn settings(
+        &self,
+        default_tap_ip: Ipv4Addr,
+    ) -> Result<FunctionSettings, RuntimeError> {
+        let mut file_mapper = HashMap::new();
+        for mount in self.mounts.iter() {
+            if !mount.host.is_dir() {
+                return Err(RuntimeError::ManifestError(format!(
+                    "mount host path {} is not a directory",
+                    mount.host.display()
+                )));
+            }
+            let perms = match mount.read_only {
+                true => (DirPerms::READ, FilePerms::READ),
+                false => (DirPerms::all(), FilePerms::all()),
+            };
+            file_mapper.insert(
+                mount.host.to_string_lossy().to_string(),
+                (mount.guest.clone(), perms.0, perms.1),
+            );
+        }
+        let mut wasi_flags = WasiFlags::new(
+            self.network.enabled.then_some(()),
+            (!file_mapper.is_empty()).then_some(file_mapper),
+        );
+        wasi_flags
+            .set_env(self.env.clone().into_iter().collect())
+            .set_args(self.args.clone())
+            .set_outbound(self.outbound_rules()?);
+
+        let instance_mode = match &self.warm_pool {
+            Some(pool) => InstanceMode::Warm(pool.into()),
+            None => InstanceMode::Fresh,
+        };
+        Ok(FunctionSettings {
+            tap_ip: self.network.tap_ip.unwrap_or(default_tap_ip),
+            instance_mode,
+            memory: self.memory,
+            timeout: self.timeout,
+            concurrency: self.concurrency,
+            wasi_flags,
+            kv: self.kv.clone(),
+            log: self.log.clone(),
+            invoke: self.invoke.clone(),
+        })
+    }
+}
@@ -0,0 +1,323 @@
+use super::lambda_error::LambdaError;
+use super::usage::Usage;
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Label used for the ids seen after the cardinality cap was reached
+pub const OVERFLOW_LABEL: &str = "other";
+
+// Keeps `max` distinct ids at a time, the others share the overflow label until one is released
+struct LabelCap {
+    max: usize,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl LabelCap {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn label(&self, id: &str) -> String {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(id) {
+            return id.to_string();
+        }
+        if seen.len() < self.max {
+            seen.insert(id.to_string());
+            return id.to_string();
+        }
+        OVERFLOW_LABEL.to_string()
+    }
+
+    // Frees the label of a removed id, false when it never had its own
+    fn release(&self, id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.remove(id)
+    }
+}
+
+// Labels a function was reported with, to remove its series once it is gone
+#[derive(Default)]
+struct FunctionSeries {
+    modules: HashSet<String>,
+    trap_kinds: HashSet<String>,
+}
+
+/// Prometheus metrics of a runtime, served in the text format by the `/metrics` endpoint
+pub struct Metrics {
+    registry: Registry,
+    modules: LabelCap,
+    functions: LabelCap,
+    function_series: Mutex<HashMap<String, FunctionSeries>>,
+    module_registrations: IntCounterVec,
+    compile_seconds: HistogramVec,
+    instantiate_seconds: HistogramVec,
+    execute_seconds: HistogramVec,
+    peak_memory_bytes: HistogramVec,
+    traps: IntCounterVec,
+    force_stops: IntCounterVec,
+    timeouts: IntCounterVec,
+    memory_reserved: IntGauge,
+    active_invocations: IntGaugeVec,
+    queue_depth: IntGauge,
+}
+
+impl Metrics {
+    /// `label_cap` bounds the number of distinct module and function ids used as labels
+    pub fn new(label_cap: usize) -> Self {
+        let registry = Registry::new();
+        let buckets = exponential_buckets(0.0005, 2.0, 18).unwrap();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            let opts = HistogramOpts::new(name, help).buckets(buckets.clone());
+            let histogram = HistogramVec::new(opts, labels).unwrap();
+            registry.register(Box::new(histogram.clone())).unwrap();
+            histogram
+        };
+
+        let module_registrations = counter(
+            "limes_module_registrations_total",
+            "Modules registered",
+            &["module"],
+        );
+        let compile_seconds = histogram(
+            "limes_module_compile_seconds",
+            "Time spent compiling a registered module",
+            &["module"],
+        );
+        let instantiate_seconds = histogram(
+            "limes_instantiate_seconds",
+            "Time spent preparing an instance before the call",
+            &["module", "function"],
+        );
+        let execute_seconds = histogram(
+            "limes_execute_seconds",
+            "Time spent running the guest",
+            &["module", "function"],
+        );
+        let peak_memory_bytes = HistogramVec::new(
+            HistogramOpts::new(
+                "limes_peak_memory_bytes",
+                "Highest linear memory size reached by a run",
+            )
+            .buckets(exponential_buckets(65536.0, 2.0, 16).unwrap()),
+            &["module", "function"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(peak_memory_bytes.clone()))
+            .unwrap();
+        let traps = counter(
+            "limes_traps_total",
+            "Runs ended by a trap, by trap kind",
+            &["module", "function", "kind"],
+        );
+        let force_stops = counter(
+            "limes_force_stops_total",
+            "Runs ended by a stop request",
+            &["module", "function"],
+        );
+        let timeouts = counter(
+            "limes_timeouts_total",
+            "Runs ended by their deadline",
+            &["module", "function"],
+        );
+        let memory_reserved = IntGauge::new(
+            "limes_memory_reserved_bytes",
+            "Memory reserved by functions",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(memory_reserved.clone()))
+            .unwrap();
+        let active_invocations = IntGaugeVec::new(
+            Opts::new("limes_active_invocations", "Runs in progress per engine"),
+            &["engine"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(active_invocations.clone()))
+            .unwrap();
+        let queue_depth = IntGauge::new(
+            "limes_queue_depth",
+            "Asynchronous invocations waiting to start",
+        )
+        .unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+
+        Self {
+            registry,
+            modules: LabelCap::new(label_cap),
+            functions: LabelCap::new(label_cap),
+            function_series: Mutex::new(HashMap::new()),
+            module_registrations,
+            compile_seconds,
+            instantiate_seconds,
+            execute_seconds,
+            peak_memory_bytes,
+            traps,
+            force_stops,
+            timeouts,
+            memory_reserved,
+            active_invocations,
+            queue_depth,
+        }
+    }
+
+    pub fn record_registration(&self, module_id: &str, compile_time: Duration) {
+        let module = self.modules.label(module_id);
+        self.module_registrations
+            .with_label_values(&[&module])
+            .inc();
+        self.compile_seconds
+            .with_label_values(&[&module])
+            .observe(compile_time.as_secs_f64());
+    }
+
+    pub fn record_run(
+        &self,
+        module_id: &str,
+        function_id: &str,
+        result: &Result<String, LambdaError>,
+        usage: &Usage,
+    ) {
+        let module = self.modules.label(module_id);
+        let function = self.functions.label(function_id);
+        self.track_series(&module, &function, usage.trap.as_deref());
+        let labels = [module.as_str(), function.as_str()];
+        self.instantiate_seconds
+            .with_label_values(&labels)
+            .observe(usage.instantiate.as_secs_f64());
+        self.execute_seconds
+            .with_label_values(&labels)
+            .observe(usage.execute.as_secs_f64());
+        self.peak_memory_bytes
+            .with_label_values(&labels)
+            .observe(usage.peak_memory_bytes as f64);
+        if let Some(kind) = &usage.trap {
+            self.traps
+                .with_label_values(&[&module, &function, kind])
+                .inc();
+        }
+        match result {
+            Err(LambdaError::ForceStop) => self.force_stops.with_label_values(&labels).inc(),
+            Err(LambdaError::Timeout) => self.record_timeout(module_id, function_id),
+            _ => {}
+        }
+    }
+
+    pub fn record_timeout(&self, module_id: &str, function_id: &str) {
+        let module = self.modules.label(module_id);
+        let function = self.functions.label(function_id);
+        self.track_series(&module, &function, None);
+        self.timeouts.with_label_values(&[&module, &function]).inc();
+    }
+
+    fn track_series(&self, module: &str, function: &str, trap_kind: Option<&str>) {
+        if function == OVERFLOW_LABEL {
+            return;
+        }
+        let mut series = self
+            .function_series
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let series = series.entry(function.to_string()).or_default();
+        series.modules.insert(module.to_string());
+        if let Some(kind) = trap_kind {
+            series.trap_kinds.insert(kind.to_string());
+        }
+    }
+
+    /// Removes the series of a removed function and gives its label to the next new function
+    pub fn remove_function(&self, function_id: &str) {
+        if !self.functions.release(function_id) {
+            return;
+        }
+        let mut series = self
+            .function_series
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(removed) = series.remove(function_id) {
+            for module in removed.modules.iter() {
+                self.remove_run_series(module, function_id, &removed.trap_kinds);
+            }
+        }
+    }
+
+    /// Removes the series of a removed module, along with the runs of functions under it
+    pub fn remove_module(&self, module_id: &str) {
+        if !self.modules.release(module_id) {
+            return;
+        }
+        let _ = self.module_registrations.remove_label_values(&[module_id]);
+        let _ = self.compile_seconds.remove_label_values(&[module_id]);
+        let mut series = self
+            .function_series
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for (function, series) in series.iter_mut() {
+            if series.modules.remove(module_id) {
+                self.remove_run_series(module_id, function, &series.trap_kinds);
+            }
+        }
+    }
+
+    // A missing series was never observed, which is fine
+    fn remove_run_series(&self, module: &str, function: &str, trap_kinds: &HashSet<String>) {
+        let labels = [module, function];
+        let _ = self.instantiate_seconds.remove_label_values(&labels);
+        let _ = self.execute_seconds.remove_label_values(&labels);
+        let _ = self.peak_memory_bytes.remove_label_values(&labels);
+        let _ = self.force_stops.remove_label_values(&labels);
+        let _ = self.timeouts.remove_label_values(&labels);
+        for kind in trap_kinds {
+            let _ = self.traps.remove_label_values(&[module, function, kind]);
+        }
+    }
+
+    pub fn add_memory_reserved(&self, bytes: i64) {
+        self.memory_reserved.add(bytes);
+    }
+
+    // The run stays counted as active until the guard is dropped, even when aborted
+    pub fn track_active(&self, engine: usize) -> ActiveGuard {
+        let gauge = self
+            .active_invocations
+            .with_label_values(&[&engine.to_string()]);
+        gauge.inc();
+        ActiveGuard(gauge)
+    }
+
+    pub fn queue_depth(&self) -> &IntGauge {
+        &self.queue_depth
+    }
+
+    /// All the metrics in the Prometheus text exposition format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Metrics are always encodable as text");
+        String::from_utf8(buffer).expect("The text format is utf-8")
+    }
+}
+
+pub struct ActiveGuard(IntGauge);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
@@ -1,4 +1,17 @@
+pub(crate) mod deadline;
+pub mod guest_log;
+pub mod invocation;
+pub mod invoke;
+pub mod invoke_error;
+pub mod keyvalue;
 pub mod lambda;
 pub mod lambda_error;
+pub mod logs;
+pub mod manifest;
+pub mod metrics;
+#[allow(clippy::module_inception)]
 pub mod runtime;
 pub mod runtime_error;
+pub mod usage;
+pub mod versions;
+pub mod webhook;
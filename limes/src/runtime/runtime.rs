@@ -1,15 +1,37 @@
-use super::lambda;
-use super::lambda::Lambda;
+use super::guest_log::GuestLogConfig;
+use super::invocation::{Invocation, InvocationID, InvocationReport, InvocationStatus};
+use super::invoke::{InvokeFuture, InvokePolicy, Invoker, DEFAULT_MAX_CALL_DEPTH};
+use super::invoke_error::InvokeError;
+use super::lambda::{CallContext, InstanceMode, Lambda, OutboundRule, Program, ResetPolicy};
+use super::lambda_error::LambdaError;
+use super::logs::LogLine;
+use super::manifest::{Deployment, FunctionManifest, FunctionSettings, KvScope, RUN_INTERFACE};
+use super::metrics::Metrics;
 use super::runtime_error::RuntimeError;
+use super::usage::Usage;
+use super::versions::{ModuleName, ModuleNames};
+use super::webhook::{Notifier, WebhookConfig};
+use crate::db::kv::{KvNamespace, KvStore};
+use crate::tools::inspect::{self, ModuleReport};
+use crate::tools::{adapt, preinit, snapshot};
 use crc32fast::Hasher;
 use dashmap::DashMap;
 use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashSet};
 use std::net::Ipv4Addr;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, RwLock, Semaphore, TryAcquireError};
+use tracing::{field, info_span, Instrument, Span};
+use wasmtime::component::types::ComponentItem;
 use wasmtime::component::Component;
 use wasmtime::Config;
 use wasmtime::Engine;
+use wasmtime::Module;
 use wasmtime::OptLevel;
 
 pub struct RuntimeBuilder {
@@ -17,6 +39,15 @@ pub struct RuntimeBuilder {
     memory: Option<usize>,
     max_functions: Option<usize>,
     currently_allocated_functions: Option<usize>,
+    invocation_retention: Option<Duration>,
+    webhook_config: Option<WebhookConfig>,
+    metrics_label_cap: Option<usize>,
+    opt_level: Option<OptLevel>,
+    consume_fuel: Option<bool>,
+    kv_dir: Option<PathBuf>,
+    max_call_depth: Option<usize>,
+    default_outbound: Option<Vec<OutboundRule>>,
+    preinit_timeout: Option<Duration>,
 }
 
 impl RuntimeBuilder {
@@ -35,13 +66,74 @@ impl RuntimeBuilder {
         self
     }
 
+    // How long the results of asynchronous invocations are kept when the caller does not say
+    pub fn set_invocation_retention(&mut self, retention: Duration) -> &mut Self {
+        self.invocation_retention = Some(retention);
+        self
+    }
+
+    pub fn set_webhook_config(&mut self, config: WebhookConfig) -> &mut Self {
+        self.webhook_config = Some(config);
+        self
+    }
+
+    // Max number of distinct module and function ids used as metric labels
+    pub fn set_metrics_label_cap(&mut self, cap: usize) -> &mut Self {
+        self.metrics_label_cap = Some(cap);
+        self
+    }
+
+    pub fn set_opt_level(&mut self, opt_level: OptLevel) -> &mut Self {
+        self.opt_level = Some(opt_level);
+        self
+    }
+
+    // Fuel consumption slows the guests down, it is only needed to report the fuel used
+    pub fn set_consume_fuel(&mut self, consume_fuel: bool) -> &mut Self {
+        self.consume_fuel = Some(consume_fuel);
+        self
+    }
+
+    // Directory keeping the key-value namespaces, they only live in memory without it
+    pub fn set_kv_dir(&mut self, dir: PathBuf) -> &mut Self {
+        self.kv_dir = Some(dir);
+        self
+    }
+
+    // Calls a guest may nest through `limes:invoke/call` below an invocation
+    pub fn set_max_call_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    // Addresses the functions whose settings have no outbound rules may connect to, every
+    // address without it
+    pub fn set_default_outbound(&mut self, rules: Vec<OutboundRule>) -> &mut Self {
+        self.default_outbound = Some(rules);
+        self
+    }
+
+    // How long the init export of a pre-initialized module may run at registration
+    pub fn set_preinit_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.preinit_timeout = Some(timeout);
+        self
+    }
+
     pub fn build(&self) -> Result<Runtime, RuntimeError> {
         let mut engines_config = Config::new();
         engines_config
             .async_support(true)
             .wasm_component_model(true)
-            .cranelift_opt_level(OptLevel::SpeedAndSize);
+            .epoch_interruption(true)
+            .consume_fuel(self.consume_fuel.unwrap())
+            .cranelift_opt_level(self.opt_level.unwrap());
         let engines: Vec<Arc<Engine>> = self.gen_engines(self.vcpus.unwrap(), &engines_config)?;
+        let kv_store = match &self.kv_dir {
+            Some(dir) => {
+                KvStore::open(dir).map_err(|e| RuntimeError::KvStoreError(e.to_string()))?
+            }
+            None => KvStore::in_memory(),
+        };
 
         Ok(Runtime {
             vcpus: self.vcpus.unwrap(),
@@ -54,6 +146,16 @@ impl RuntimeBuilder {
             engine_rotatory_index: Arc::new(RwLock::new(0)),
             modules: Arc::new(DashMap::new()),
             functions: Arc::new(DashMap::new()),
+            preinitialized: Arc::new(DashMap::new()),
+            preinit_timeout: self.preinit_timeout.unwrap(),
+            invocation_retention: self.invocation_retention.unwrap(),
+            invocations: Arc::new(DashMap::new()),
+            notifier: Notifier::new(self.webhook_config.clone().unwrap()),
+            metrics: Arc::new(Metrics::new(self.metrics_label_cap.unwrap())),
+            kv_store: Arc::new(kv_store),
+            max_call_depth: self.max_call_depth.unwrap(),
+            default_outbound: self.default_outbound.clone(),
+            names: Arc::new(ModuleNames::default()),
         })
     }
 
@@ -71,28 +173,247 @@ impl RuntimeBuilder {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FunctionHandlerStatus {
     Ready,
     Running,
     Stopped,
 }
 
-#[allow(dead_code)]
+/// A registered module as listed by the runtime
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ModuleInfo {
+    pub module_id: ModuleID,
+    pub size_bytes: usize,
+    /// Functions initialized from the module
+    pub functions: usize,
+    /// Modules whose exports the module imports
+    #[serde(default)]
+    pub dependencies: Vec<ModuleID>,
+    /// `name@version` under which the module is published
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+/// How `register_module_with_options` registers a module
+#[derive(Clone, Debug, Default)]
+pub struct RegisterOptions {
+    /// Modules exporting the interfaces the component imports
+    pub dependencies: Vec<ModuleID>,
+    /// Wraps a wasip1 core module into a component with the preview1 adapter first, instead
+    /// of running it as a command
+    pub adapt_wasip1: bool,
+}
+
+/// An initialized function as listed by the runtime
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FunctionInfo {
+    pub function_id: FunctionID,
+    pub module_id: ModuleID,
+    pub status: FunctionHandlerStatus,
+    /// Calls running right now
+    pub running: usize,
+    pub memory_bytes: usize,
+    pub timeout_ms: Option<u64>,
+    pub warm_instances: usize,
+}
+
 pub struct FunctionHandler {
+    // Replaced when the function is updated, running calls keep the previous one
+    revision: Mutex<Arc<Revision>>,
+    // Calls currently running, the function is Running while it is not zero
+    running: Arc<AtomicUsize>,
+}
+
+// The lambda of a function with the module and the settings it was built from
+struct Revision {
     lambda: Lambda,
-    status: FunctionHandlerStatus,
+    module_id: ModuleID,
+    engine_index: usize,
+    // Kept to build the lambda of another module the same way
+    settings: FunctionSettings,
+    // Name of the key-value namespace of the lambda
+    namespace: String,
+    // Bounds the calls running at once when the function has a concurrency
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl Revision {
+    // A function scoped namespace is dropped along with the function
+    fn owns_namespace(&self) -> bool {
+        self.settings.kv.namespace.is_none() && self.settings.kv.scope == KvScope::Function
+    }
+}
+
+impl FunctionHandler {
+    fn revision(&self) -> Arc<Revision> {
+        self.revision.lock().unwrap().clone()
+    }
+
+    // Runs the lambda once a permit is free, keeping the metrics of the call
+    async fn run(
+        &self,
+        func_id: &str,
+        metrics: &Metrics,
+        args: &str,
+        context: &CallContext,
+    ) -> (Result<String, LambdaError>, Usage) {
+        let revision = self.revision();
+        let permit = match &revision.permits {
+            Some(permits) => permits.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        self.run_revision(revision, permit, func_id, metrics, args, context)
+            .await
+    }
+
+    // A nested call does not wait for a permit, its caller may hold the one it would wait for
+    async fn try_run(
+        &self,
+        func_id: &str,
+        metrics: &Metrics,
+        args: &str,
+        context: &CallContext,
+    ) -> Result<(Result<String, LambdaError>, Usage), TryAcquireError> {
+        let revision = self.revision();
+        let permit = revision
+            .permits
+            .as_ref()
+            .map(|permits| permits.clone().try_acquire_owned())
+            .transpose()?;
+        Ok(self
+            .run_revision(revision, permit, func_id, metrics, args, context)
+            .await)
+    }
+
+    async fn run_revision(
+        &self,
+        revision: Arc<Revision>,
+        _permit: Option<OwnedSemaphorePermit>,
+        func_id: &str,
+        metrics: &Metrics,
+        args: &str,
+        context: &CallContext,
+    ) -> (Result<String, LambdaError>, Usage) {
+        let _active = metrics.track_active(revision.engine_index);
+        let _running = RunningGuard::new(&self.running);
+        let span = info_span!(
+            "limes.exec",
+            module_id = %revision.module_id,
+            function_id = %func_id,
+            invocation_id = field::Empty,
+            depth = context.depth
+        );
+        if let Some(invocation_id) = &context.invocation_id {
+            span.record("invocation_id", invocation_id.as_str());
+        }
+        let (result, usage) = revision
+            .lambda
+            .run_invocation(args, context)
+            .instrument(span)
+            .await;
+        metrics.record_run(&revision.module_id, func_id, &result, &usage);
+        (result, usage)
+    }
+
+    fn status(&self) -> FunctionHandlerStatus {
+        if self.revision().lambda.is_stopped() {
+            return FunctionHandlerStatus::Stopped;
+        }
+        match self.running.load(Ordering::Relaxed) {
+            0 => FunctionHandlerStatus::Ready,
+            _ => FunctionHandlerStatus::Running,
+        }
+    }
+
+    fn info(&self, func_id: &str) -> FunctionInfo {
+        let revision = self.revision();
+        FunctionInfo {
+            function_id: func_id.to_string(),
+            module_id: revision.module_id.clone(),
+            status: self.status(),
+            running: self.running.load(Ordering::Relaxed),
+            memory_bytes: revision.lambda.memory_size(),
+            timeout_ms: revision
+                .lambda
+                .timeout()
+                .map(|timeout| timeout.as_millis() as u64),
+            warm_instances: revision.lambda.warm_instances(),
+        }
+    }
+}
+
+// Counts a call as running until dropped, also when the call is cancelled
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl RunningGuard {
+    fn new(running: &Arc<AtomicUsize>) -> Self {
+        running.fetch_add(1, Ordering::Relaxed);
+        Self(running.clone())
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 #[allow(dead_code)]
 pub struct ModuleHandler {
-    component: Arc<Component>,
+    program: Program,
     hash: u32,
+    bytes: Arc<Vec<u8>>,
+    engine_index: usize,
+    dependencies: Vec<ModuleID>,
+    // Compiled on first use by a function with ResetPolicy::Snapshot
+    snapshot_component: OnceCell<Arc<Component>>,
+}
+
+impl ModuleHandler {
+    async fn get_snapshot_component(&self) -> Result<Arc<Component>, RuntimeError> {
+        let component = self
+            .snapshot_component
+            .get_or_try_init(|| async {
+                let instrumented = snapshot::instrument_component(&self.bytes)
+                    .map_err(|e| RuntimeError::FunctionInitError(e.to_string()))?;
+                Component::from_binary(self.program.engine(), &instrumented)
+                    .map(Arc::new)
+                    .map_err(|e| RuntimeError::FunctionInitError(e.to_string()))
+            })
+            .await?;
+        Ok(component.clone())
+    }
+
+    // Dependencies are components, `resolve_dependencies` refuses core modules
+    fn component(&self) -> Result<&Arc<Component>, RuntimeError> {
+        self.program.component().ok_or_else(|| {
+            RuntimeError::DependencyError("a core module is not a library component".to_string())
+        })
+    }
 }
 
 type ModuleID = String;
 type FunctionID = String;
 
+// Imports left to the linker of the lambda instead of the dependencies
+const HOST_PACKAGES: [&str; 2] = ["wasi:", "limes:"];
+
+// Namespace shared by the functions of a module with the module kv scope
+fn module_namespace(id: &str) -> String {
+    format!("module/{}", id)
+}
+
+// Namespace of a function running the module, as its kv settings pick it
+fn function_namespace(settings: &FunctionSettings, module_id: &str, func_id: &str) -> String {
+    match (&settings.kv.namespace, settings.kv.scope) {
+        (Some(name), _) => format!("named/{}", name),
+        (None, KvScope::Module) => module_namespace(module_id),
+        (None, KvScope::Function) => format!("function/{}", func_id),
+    }
+}
+
 pub struct Runtime {
     vcpus: usize,
     memory: usize,
@@ -103,40 +424,393 @@ pub struct Runtime {
     engine_rotatory_index: Arc<RwLock<usize>>,
     modules: Arc<DashMap<ModuleID, Arc<ModuleHandler>>>,
     functions: Arc<DashMap<FunctionID, Arc<RwLock<FunctionHandler>>>>,
+    // Pre-initialized bytes by SHA-256 of the original bytes and the init export
+    preinitialized: Arc<DashMap<[u8; 32], Arc<Vec<u8>>>>,
+    preinit_timeout: Duration,
+    invocation_retention: Duration,
+    invocations: Arc<DashMap<InvocationID, Invocation>>,
+    notifier: Notifier,
+    metrics: Arc<Metrics>,
+    kv_store: Arc<KvStore>,
+    max_call_depth: usize,
+    default_outbound: Option<Vec<OutboundRule>>,
+    names: Arc<ModuleNames>,
+}
+
+// Runs the functions called through `limes:invoke/call`. It does not keep the functions alive,
+// they hold it
+struct RuntimeInvoker {
+    functions: Weak<DashMap<FunctionID, Arc<RwLock<FunctionHandler>>>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Invoker for RuntimeInvoker {
+    fn invoke<'a>(
+        &'a self,
+        function_id: &'a str,
+        args: &'a str,
+        context: CallContext,
+    ) -> InvokeFuture<'a> {
+        Box::pin(async move {
+            let func_handler = self
+                .functions
+                .upgrade()
+                .and_then(|functions| functions.get(function_id).map(|h| h.value().clone()))
+                .ok_or_else(|| InvokeError::FunctionNotFound(function_id.to_string()))?;
+            let (result, _) = func_handler
+                .read()
+                .await
+                .try_run(function_id, &self.metrics, args, &context)
+                .await
+                .map_err(|_| InvokeError::Busy(function_id.to_string()))?;
+            result.map_err(|e| InvokeError::Failed(function_id.to_string(), e.to_string()))
+        })
+    }
 }
 
 impl Runtime {
     // Costruct the Runtime
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> RuntimeBuilder {
         RuntimeBuilder {
             vcpus: Some(1),
             memory: Some(1024 * 1024 * 2 * 100), // Instance for 100 instance of 2Mb each
             max_functions: Some(100),
             currently_allocated_functions: Some(0),
+            invocation_retention: Some(Duration::from_secs(600)),
+            webhook_config: Some(WebhookConfig::default()),
+            metrics_label_cap: Some(100),
+            opt_level: Some(OptLevel::SpeedAndSize),
+            consume_fuel: Some(false),
+            kv_dir: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            default_outbound: None,
+            preinit_timeout: Some(Duration::from_secs(10)),
         }
     }
 
+    #[tracing::instrument(name = "limes.register_module", skip_all, fields(module_id))]
     pub async fn register_module(&self, bytes: Vec<u8>) -> Result<ModuleID, RuntimeError> {
-        let engine = self.get_engine().await;
+        let (engine_index, engine) = self.get_engine().await;
+        let hash = self.gen_module_hash(&bytes);
+        self.add_module(engine_index, &engine, hash, Arc::new(bytes), Vec::new())
+    }
+
+    // Registers a component importing interfaces exported by registered modules. Every import
+    // that is not WASI or a limes host interface must be exported by exactly one dependency,
+    // or one of their own dependencies. The dependencies are instantiated in the store of
+    // every instance of its functions
+    #[tracing::instrument(name = "limes.register_module", skip_all, fields(module_id))]
+    pub async fn register_module_with_dependencies(
+        &self,
+        bytes: Vec<u8>,
+        dependencies: Vec<ModuleID>,
+    ) -> Result<ModuleID, RuntimeError> {
+        // Built by the engine of a dependency, so it is not compiled again for each function
+        let (engine_index, engine) = match dependencies.first() {
+            Some(first) => {
+                let engine_index = self
+                    .modules
+                    .get(first)
+                    .ok_or(RuntimeError::ModuleNotRegistered)?
+                    .engine_index;
+                (engine_index, self.engines[engine_index].clone())
+            }
+            None => self.get_engine().await,
+        };
+        let hash = self.gen_module_hash(&bytes);
+        self.add_module(engine_index, &engine, hash, Arc::new(bytes), dependencies)
+    }
+
+    // Registers the module as register_module_with_dependencies does, a wasip1 core module is
+    // first adapted into a component when asked. The component and its hash then go through the
+    // same checks as any other
+    pub async fn register_module_with_options(
+        &self,
+        bytes: Vec<u8>,
+        options: RegisterOptions,
+    ) -> Result<ModuleID, RuntimeError> {
+        let bytes = match options.adapt_wasip1 {
+            true => Self::adapt_wasip1(&bytes)?,
+            false => bytes,
+        };
+        self.register_module_with_dependencies(bytes, options.dependencies)
+            .await
+    }
+
+    fn adapt_wasip1(bytes: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+        info_span!("limes.adapt")
+            .in_scope(|| adapt::adapt_wasip1(bytes))
+            .map_err(|e| RuntimeError::ModuleAdaptError(format!("{:#}", e)))
+    }
+
+    // Runs `init_export` once and registers the component with the state it produced baked
+    // in, so every function instance skips that initialization. The result is cached per
+    // bytes and export, registering the same module again does not run the init twice
+    #[tracing::instrument(
+        name = "limes.register_module",
+        skip_all,
+        fields(module_id, init_export = init_export)
+    )]
+    pub async fn register_module_with_preinit(
+        &self,
+        bytes: Vec<u8>,
+        init_export: &str,
+    ) -> Result<ModuleID, RuntimeError> {
+        let (engine_index, engine) = self.get_engine().await;
         let hash = self.gen_module_hash(&bytes);
+        let key: [u8; 32] = Sha256::new()
+            .chain_update((bytes.len() as u64).to_le_bytes())
+            .chain_update(&bytes)
+            .chain_update(init_export.as_bytes())
+            .finalize()
+            .into();
+
+        let cached = self
+            .preinitialized
+            .get(&key)
+            .map(|preinitialized| preinitialized.value().clone());
+        let preinitialized = match cached {
+            Some(preinitialized) => preinitialized,
+            None => Arc::new(
+                preinit::preinitialize_component(
+                    &engine,
+                    &bytes,
+                    init_export,
+                    self.preinit_timeout,
+                )
+                .instrument(info_span!("limes.preinit"))
+                .await
+                .map_err(|e| RuntimeError::ModulePreinitError(e.to_string()))?,
+            ),
+        };
+        let module_id = self.add_module(
+            engine_index,
+            &engine,
+            hash,
+            preinitialized.clone(),
+            Vec::new(),
+        )?;
+        self.preinitialized.insert(key, preinitialized);
+        Ok(module_id)
+    }
+
+    fn add_module(
+        &self,
+        engine_index: usize,
+        engine: &Engine,
+        hash: u32,
+        bytes: Arc<Vec<u8>>,
+        dependencies: Vec<ModuleID>,
+    ) -> Result<ModuleID, RuntimeError> {
         // FIX: Should check on local and db if already present
         // using the hash otherwise create a module and register it.
         // for the seek of time I will not check the presence of the module in memory.
         let module_id = nanoid!(20, &nanoid::alphabet::SAFE);
+        Span::current().record("module_id", module_id.as_str());
 
-        // Create component
-        let component = Arc::new(
-            wasmtime::component::Component::from_binary(&engine, &bytes)
-                .map_err(|_| RuntimeError::ComponentBuildError)?,
-        );
+        // Create component, or core module
+        let compile_start = Instant::now();
+        let program = info_span!("limes.compile", module_id = %module_id)
+            .in_scope(|| Self::compile(engine, &bytes))?;
+        if let Some(error) = inspect::inspect_program(&program).errors.into_iter().next() {
+            return Err(RuntimeError::ModuleValidationError(error));
+        }
+        self.check_module(engine_index, &program, &dependencies)?;
+        self.metrics
+            .record_registration(&module_id, compile_start.elapsed());
         self.modules.insert(
             module_id.clone(),
-            Arc::new(ModuleHandler { component, hash }),
+            Arc::new(ModuleHandler {
+                program,
+                hash,
+                bytes,
+                engine_index,
+                dependencies,
+                snapshot_component: OnceCell::new(),
+            }),
         );
 
         Ok(module_id)
     }
 
+    /// Compiles `bytes` and runs the checks of a registration without registering anything,
+    /// the report lists every problem found along with the imports and exports
+    pub fn validate_module(&self, bytes: &[u8], dependencies: &[ModuleID]) -> ModuleReport {
+        let engine_index = match dependencies
+            .first()
+            .and_then(|first| self.modules.get(first))
+        {
+            Some(module) => module.engine_index,
+            None => 0,
+        };
+        let program = match Self::compile(&self.engines[engine_index], bytes) {
+            Ok(program) => program,
+            Err(e) => {
+                return ModuleReport {
+                    errors: vec![e.to_string()],
+                    ..ModuleReport::default()
+                }
+            }
+        };
+        let mut report = inspect::inspect_program(&program);
+        if let Err(e) = self.check_module(engine_index, &program, dependencies) {
+            report.errors.push(e.to_string());
+        }
+        report
+    }
+
+    /// Imports and exports of a registered module
+    pub fn inspect_module(&self, id: &ModuleID) -> Result<ModuleReport, RuntimeError> {
+        let module = self
+            .modules
+            .get(id)
+            .ok_or(RuntimeError::ModuleNotRegistered)?;
+        Ok(inspect::inspect_program(&module.program))
+    }
+
+    // Core modules are told apart from components by their header, they run as wasip1 commands
+    fn compile(engine: &Engine, bytes: &[u8]) -> Result<Program, RuntimeError> {
+        let build_error = |e: anyhow::Error| RuntimeError::ComponentBuildError(format!("{:#}", e));
+        if wasmparser::Parser::is_core_wasm(bytes) {
+            let module = Module::from_binary(engine, bytes).map_err(build_error)?;
+            return Ok(Program::Command(Arc::new(module)));
+        }
+        let component = Component::from_binary(engine, bytes).map_err(build_error)?;
+        Ok(Program::Component(Arc::new(component)))
+    }
+
+    // Every import of the component and of its dependencies must be linkable, so a missing
+    // interface is reported at registration and not on the first call
+    fn check_module(
+        &self,
+        engine_index: usize,
+        program: &Program,
+        dependencies: &[ModuleID],
+    ) -> Result<(), RuntimeError> {
+        let component = match program {
+            Program::Component(component) => component,
+            Program::Command(_) if !dependencies.is_empty() => {
+                return Err(RuntimeError::DependencyError(
+                    "a core module can not import dependencies".to_string(),
+                ))
+            }
+            Program::Command(module) => {
+                return Lambda::check_command(module)
+                    .map_err(|e| RuntimeError::ModuleValidationError(e.to_string()))
+            }
+        };
+        if !dependencies.is_empty() {
+            Self::check_dependencies(component, &self.resolve_dependencies(dependencies)?)?;
+        }
+        let components =
+            self.dependency_components(engine_index, component.engine(), dependencies)?;
+        Lambda::check_imports(component, &components)
+            .map_err(|e| RuntimeError::ModuleValidationError(e.to_string()))
+    }
+
+    // The modules needed by `dependencies`, each one after its own dependencies
+    fn resolve_dependencies(
+        &self,
+        dependencies: &[ModuleID],
+    ) -> Result<Vec<(ModuleID, Arc<ModuleHandler>)>, RuntimeError> {
+        let mut resolved: Vec<(ModuleID, Arc<ModuleHandler>)> = Vec::new();
+        for id in dependencies {
+            let module = self
+                .modules
+                .get(id)
+                .map(|module| module.value().clone())
+                .ok_or(RuntimeError::ModuleNotRegistered)?;
+            if module.program.component().is_none() {
+                return Err(RuntimeError::DependencyError(format!(
+                    "`{}` is a core module, not a library component",
+                    id
+                )));
+            }
+            for dependency in self
+                .resolve_dependencies(&module.dependencies)?
+                .into_iter()
+                .chain(std::iter::once((id.clone(), module)))
+            {
+                if !resolved.iter().any(|(known, _)| *known == dependency.0) {
+                    resolved.push(dependency);
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn check_dependencies(
+        component: &Component,
+        dependencies: &[(ModuleID, Arc<ModuleHandler>)],
+    ) -> Result<(), RuntimeError> {
+        let mut exports: Vec<(String, &ModuleID)> = Vec::new();
+        for (id, module) in dependencies {
+            let component = module.component()?;
+            for (name, item) in component.component_type().exports(component.engine()) {
+                if let ComponentItem::ComponentInstance(_) = item {
+                    exports.push((name.to_string(), id));
+                }
+            }
+        }
+        // The dependencies are linked together with the component, their imports count too
+        let mut imports = BTreeSet::new();
+        let mut components = vec![component];
+        for (_, module) in dependencies {
+            components.push(module.component()?);
+        }
+        for component in components {
+            for (name, _) in component.component_type().imports(component.engine()) {
+                imports.insert(name.to_string());
+            }
+        }
+        for name in imports.iter() {
+            let exporters: HashSet<&ModuleID> = exports
+                .iter()
+                .filter(|(export, _)| export == name)
+                .map(|(_, id)| *id)
+                .collect();
+            match exporters.len() {
+                0 if HOST_PACKAGES.iter().any(|host| name.starts_with(host)) => {}
+                0 => {
+                    return Err(RuntimeError::DependencyError(format!(
+                        "no dependency exports `{}`",
+                        name
+                    )))
+                }
+                1 => {}
+                _ => {
+                    return Err(RuntimeError::DependencyError(format!(
+                        "`{}` is exported by more than one dependency",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The dependencies built by the engine at `engine_index`, a module registered on another
+    // engine is compiled again
+    fn dependency_components(
+        &self,
+        engine_index: usize,
+        engine: &Engine,
+        dependencies: &[ModuleID],
+    ) -> Result<Vec<Arc<Component>>, RuntimeError> {
+        self.resolve_dependencies(dependencies)?
+            .into_iter()
+            .map(|(_, dependency)| {
+                if dependency.engine_index == engine_index {
+                    return dependency.component().cloned();
+                }
+                Component::from_binary(engine, &dependency.bytes)
+                    .map(Arc::new)
+                    .map_err(|e| RuntimeError::ComponentBuildError(format!("{:#}", e)))
+            })
+            .collect()
+    }
+
     fn gen_module_hash(&self, bytes: &[u8]) -> u32 {
         let mut hasher = Hasher::new();
         hasher.update(bytes);
@@ -144,8 +818,34 @@ impl Runtime {
     }
 
     pub async fn remove_module(&self, id: ModuleID) -> Result<(), RuntimeError> {
+        let dependent = self
+            .modules
+            .iter()
+            .find(|module| module.dependencies.contains(&id))
+            .map(|module| module.key().clone());
+        if let Some(dependent) = dependent {
+            return Err(RuntimeError::ModuleInUse(dependent));
+        }
+        // Its namespace is dropped along with the module, not while a function still uses it
+        let namespace = module_namespace(&id);
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|function| (function.key().clone(), function.value().clone()))
+            .collect();
+        for (func_id, func_handler) in functions {
+            if func_handler.read().await.revision().namespace == namespace {
+                return Err(RuntimeError::ModuleNamespaceInUse(func_id));
+            }
+        }
         if self.modules.contains_key(&id) {
             self.modules.remove(&id);
+            self.names.remove_module(&id);
+            self.metrics.remove_module(&id);
+            // Pre-initialized bytes are shared with the modules, drop the ones left unused
+            self.preinitialized
+                .retain(|_, preinitialized| Arc::strong_count(preinitialized) > 1);
+            let _ = self.kv_store.remove_namespace(&module_namespace(&id)).await;
             return Ok(());
         }
         Err(RuntimeError::ModuleNotRegistered)
@@ -156,48 +856,394 @@ impl Runtime {
         id: ModuleID,
         tap_ip: Ipv4Addr,
     ) -> Result<FunctionID, RuntimeError> {
-        if *self.currently_allocated_functions.read().await >= self.max_functions {
+        self.init_function_with_mode(id, tap_ip, InstanceMode::Fresh)
+            .await
+    }
+
+    // Same as init_function, but lets the caller pick fresh isolation or a warm pool
+    pub async fn init_function_with_mode(
+        &self,
+        id: ModuleID,
+        tap_ip: Ipv4Addr,
+        instance_mode: InstanceMode,
+    ) -> Result<FunctionID, RuntimeError> {
+        self.init_function_with_settings(id, FunctionSettings::new(tap_ip, instance_mode))
+            .await
+    }
+
+    // Initializes a function of a registered module as the manifest describes, the module
+    // path of the manifest is not used
+    pub async fn init_function_with_manifest(
+        &self,
+        id: ModuleID,
+        manifest: &FunctionManifest,
+    ) -> Result<FunctionID, RuntimeError> {
+        manifest.validate()?;
+        let settings = manifest.settings(Ipv4Addr::LOCALHOST)?;
+        self.init_function_with_settings(id, settings).await
+    }
+
+    // Registers the module, publishes it as `name@version` when the manifest has a version, and
+    // initializes its function in one step. A module whose function can not be initialized is
+    // removed again
+    pub async fn deploy(
+        &self,
+        manifest: &FunctionManifest,
+        bytes: Vec<u8>,
+    ) -> Result<Deployment, RuntimeError> {
+        self.deploy_with_tap_ip(manifest, bytes, Ipv4Addr::LOCALHOST)
+            .await
+    }
+
+    // Same as deploy, `default_tap_ip` is used when the manifest has no tap ip
+    pub async fn deploy_with_tap_ip(
+        &self,
+        manifest: &FunctionManifest,
+        bytes: Vec<u8>,
+        default_tap_ip: Ipv4Addr,
+    ) -> Result<Deployment, RuntimeError> {
+        let settings = manifest.settings(default_tap_ip)?;
+        let module_id = self.register_manifest_module(manifest, bytes).await?;
+        let initialized = self
+            .init_function_with_settings(module_id.clone(), settings)
+            .await;
+        match initialized {
+            Ok(function_id) => Ok(Deployment {
+                module_id,
+                function_id,
+            }),
+            Err(e) => {
+                let _ = self.remove_module(module_id).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Registers the module as the manifest describes it, with its preinit export or its
+    /// dependencies, and publishes it as `name@version` when the manifest has a version
+    pub async fn register_manifest_module(
+        &self,
+        manifest: &FunctionManifest,
+        bytes: Vec<u8>,
+    ) -> Result<ModuleID, RuntimeError> {
+        let bytes = match manifest.adapt_wasip1 {
+            true => Self::adapt_wasip1(&bytes)?,
+            false => bytes,
+        };
+        manifest.validate_module(&bytes)?;
+        let module_id = match &manifest.preinit {
+            Some(export) => self.register_module_with_preinit(bytes, export).await?,
+            None => {
+                self.register_module_with_dependencies(bytes, manifest.dependencies.clone())
+                    .await?
+            }
+        };
+        if let (Some(name), Some(version)) = (&manifest.name, &manifest.version) {
+            if let Err(e) = self.publish_module(&module_id, name, version) {
+                let _ = self.remove_module(module_id).await;
+                return Err(e);
+            }
+        }
+        Ok(module_id)
+    }
+
+    // Reads the manifest, or the `limes.toml` of a directory, and deploys its module
+    pub async fn deploy_from_file(
+        &self,
+        path: &std::path::Path,
+    ) -> Result<Deployment, RuntimeError> {
+        let manifest = FunctionManifest::from_file(path)?;
+        let bytes = manifest.read_module()?;
+        self.deploy(&manifest, bytes).await
+    }
+
+    #[tracing::instrument(
+        name = "limes.init_function",
+        skip(self, settings),
+        fields(module_id = %id, function_id)
+    )]
+    async fn init_function_with_settings(
+        &self,
+        id: ModuleID,
+        settings: FunctionSettings,
+    ) -> Result<FunctionID, RuntimeError> {
+        // Held until the function is stored, so two inits can not both take the last slot
+        let mut caf = self.currently_allocated_functions.write().await;
+        if *caf >= self.max_functions {
             return Err(RuntimeError::MaxFunctionDeplaymentReached);
         }
 
-        let component = self
+        // `id` is a module id or a `name@version` and `name@alias` reference
+        let id = self
+            .resolve_module(&id)
+            .map_err(|_| RuntimeError::ComponentNotFound)?;
+        let module = self
             .modules
             .get(&id)
-            .ok_or(RuntimeError::ComponentNotFound)
-            .map_err(|e| e)?
+            .ok_or(RuntimeError::ComponentNotFound)?
             .value()
-            .component
             .clone();
 
-        // Temporary
-        let func_mem_size = self.memory / self.max_functions;
-        let mut caf = self.currently_allocated_functions.write().await;
-        *caf += 1;
-
-        let wasi_flags = lambda::WasiFlags::default();
-        let lambda = Lambda::new(component.clone(), func_mem_size, tap_ip, wasi_flags)
-            .await
-            .map_err(|e| RuntimeError::FunctionInitError(e.to_string()))?;
-
         let func_id = nanoid!(10, &nanoid::alphabet::SAFE);
+        Span::current().record("function_id", func_id.as_str());
         if self.functions.contains_key(&func_id) {
             return Err(RuntimeError::FunctionAlreadyInitialized);
         }
+        let kv_namespace = function_namespace(&settings, &id, &func_id);
+        let namespace = self.kv_store.namespace(&kv_namespace, settings.kv.quota());
+
+        let lambda = self
+            .build_lambda(&module, &func_id, &settings, namespace)
+            .await?;
+        *caf += 1;
+        let memory_size = lambda.memory_size();
 
         self.functions.insert(
             func_id.clone(),
             Arc::new(RwLock::new(FunctionHandler {
-                lambda,
-                status: FunctionHandlerStatus::Ready,
+                revision: Mutex::new(Arc::new(Revision {
+                    lambda,
+                    module_id: id,
+                    engine_index: module.engine_index,
+                    permits: settings
+                        .concurrency
+                        .map(|permits| Arc::new(Semaphore::new(permits))),
+                    settings,
+                    namespace: kv_namespace,
+                })),
+                running: Arc::new(AtomicUsize::new(0)),
             })),
         );
+        self.metrics.add_memory_reserved(memory_size as i64);
 
         Ok(func_id)
     }
 
+    // Builds the lambda of a function from the module, `settings` are left as they are so the
+    // function can be built again from another module
+    async fn build_lambda(
+        &self,
+        module: &ModuleHandler,
+        func_id: &str,
+        settings: &FunctionSettings,
+        namespace: KvNamespace,
+    ) -> Result<Lambda, RuntimeError> {
+        let has_run_export = match &module.program {
+            Program::Component(component) => component
+                .component_type()
+                .exports(component.engine())
+                .any(|(name, _)| name == RUN_INTERFACE),
+            Program::Command(_) => true,
+        };
+        if !has_run_export {
+            return Err(RuntimeError::FunctionInitError(format!(
+                "the module does not export `{}`",
+                RUN_INTERFACE
+            )));
+        }
+        let func_mem_size = settings.memory.unwrap_or(self.memory / self.max_functions);
+        if func_mem_size > self.memory {
+            return Err(RuntimeError::FunctionInitError(
+                "the function asks for more memory than the runtime has".to_string(),
+            ));
+        }
+        let program = match (&module.program, &settings.instance_mode) {
+            (Program::Component(_), InstanceMode::Warm(config))
+                if matches!(config.reset_policy(), ResetPolicy::Snapshot { .. }) =>
+            {
+                Program::Component(module.get_snapshot_component().await?)
+            }
+            (program, _) => program.clone(),
+        };
+
+        let invoker = RuntimeInvoker {
+            functions: Arc::downgrade(&self.functions),
+            metrics: self.metrics.clone(),
+        };
+        let mut wasi_flags = settings.wasi_flags.clone();
+        if wasi_flags.outbound().is_none() {
+            wasi_flags.set_outbound(self.default_outbound.clone());
+        }
+        wasi_flags
+            .set_dependencies(self.dependency_components(
+                module.engine_index,
+                module.program.engine(),
+                &module.dependencies,
+            )?)
+            .set_keyvalue(Some(namespace))
+            .set_invoke(Some(InvokePolicy {
+                invoker: Arc::new(invoker),
+                allow: settings.invoke.allow.clone(),
+                max_depth: self.max_call_depth,
+            }));
+
+        let mut lambda = Lambda::with_program(
+            program,
+            func_mem_size,
+            settings.tap_ip,
+            wasi_flags,
+            settings.instance_mode.clone(),
+        )
+        .await
+        .map_err(|e| RuntimeError::FunctionInitError(e.to_string()))?;
+        lambda
+            .set_timeout(settings.timeout)
+            .set_guest_log(GuestLogConfig {
+                function_id: Some(func_id.to_string()),
+                level: settings.log.level,
+                max_lines: settings.log.max_lines,
+            });
+        Ok(lambda)
+    }
+
+    /// Moves the function to another module, a module id or a `name@version` and `name@alias`
+    /// reference. The new lambda is built with the settings of the function before it replaces
+    /// the old one: calls started before finish on the old module, later calls run the new one
+    pub async fn update_function(
+        &self,
+        func_id: FunctionID,
+        reference: &str,
+    ) -> Result<ModuleID, RuntimeError> {
+        self.update_function_with_settings(func_id, reference, None)
+            .await
+    }
+
+    /// Same as update_function, the function also takes the settings of the manifest. It keeps
+    /// its id, and so its function scoped namespace
+    pub async fn update_function_with_manifest(
+        &self,
+        func_id: FunctionID,
+        reference: &str,
+        manifest: &FunctionManifest,
+    ) -> Result<ModuleID, RuntimeError> {
+        manifest.validate()?;
+        let settings = manifest.settings(Ipv4Addr::LOCALHOST)?;
+        self.update_function_with_settings(func_id, reference, Some(settings))
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "limes.update_function",
+        skip(self, settings),
+        fields(function_id = %func_id, module_id)
+    )]
+    async fn update_function_with_settings(
+        &self,
+        func_id: FunctionID,
+        reference: &str,
+        settings: Option<FunctionSettings>,
+    ) -> Result<ModuleID, RuntimeError> {
+        let func_handler = self.get_function(&func_id)?;
+        let id = self.resolve_module(reference)?;
+        Span::current().record("module_id", id.as_str());
+        let module = self
+            .modules
+            .get(&id)
+            .ok_or(RuntimeError::ModuleNotRegistered)?
+            .value()
+            .clone();
+
+        let current = func_handler.read().await.revision();
+        if current.lambda.is_stopped() {
+            return Err(RuntimeError::FunctionInitError(
+                "a stopped function can not be updated".to_string(),
+            ));
+        }
+        // Without new settings the calls still running on the current revision keep counting
+        // against the concurrency
+        let (settings, permits) = match settings {
+            Some(settings) => {
+                let permits = settings
+                    .concurrency
+                    .map(|permits| Arc::new(Semaphore::new(permits)));
+                (settings, permits)
+            }
+            None => (current.settings.clone(), current.permits.clone()),
+        };
+        // A module scoped namespace follows the function to the new module
+        let kv_namespace = function_namespace(&settings, &id, &func_id);
+        let namespace = self.kv_store.namespace(&kv_namespace, settings.kv.quota());
+        let lambda = self
+            .build_lambda(&module, &func_id, &settings, namespace)
+            .await?;
+        let memory_size = lambda.memory_size() as i64;
+
+        let handler = func_handler.read().await;
+        let previous = std::mem::replace(
+            &mut *handler.revision.lock().unwrap(),
+            Arc::new(Revision {
+                lambda,
+                module_id: id.clone(),
+                engine_index: module.engine_index,
+                settings,
+                namespace: kv_namespace,
+                permits,
+            }),
+        );
+        let revision = handler.revision();
+        if previous.owns_namespace() && previous.namespace != revision.namespace {
+            let _ = self.kv_store.remove_namespace(&previous.namespace).await;
+        }
+        self.metrics
+            .add_memory_reserved(memory_size - previous.lambda.memory_size() as i64);
+        Ok(id)
+    }
+
+    /// Publishes a registered module as `name@version` and moves `name@latest` to it
+    pub fn publish_module(
+        &self,
+        id: &ModuleID,
+        name: &str,
+        version: &str,
+    ) -> Result<ModuleName, RuntimeError> {
+        if !self.modules.contains_key(id) {
+            return Err(RuntimeError::ModuleNotRegistered);
+        }
+        self.names.publish(name, version, id)?;
+        self.module_name(name)
+    }
+
+    /// Points `name@alias` to a published version of the name
+    pub fn set_module_alias(
+        &self,
+        name: &str,
+        alias: &str,
+        version: &str,
+    ) -> Result<ModuleName, RuntimeError> {
+        self.names.set_alias(name, alias, version)
+    }
+
+    pub fn remove_module_alias(&self, name: &str, alias: &str) -> Result<(), RuntimeError> {
+        self.names.remove_alias(name, alias)
+    }
+
+    /// Versions and aliases of a module name
+    pub fn module_name(&self, name: &str) -> Result<ModuleName, RuntimeError> {
+        self.names
+            .get(name)
+            .ok_or(RuntimeError::ModuleNotRegistered)
+    }
+
+    /// Module id of a module id or of a `name@version` and `name@alias` reference
+    pub fn resolve_module(&self, reference: &str) -> Result<ModuleID, RuntimeError> {
+        if self.modules.contains_key(reference) {
+            return Ok(reference.to_string());
+        }
+        self.names
+            .resolve(reference)
+            .ok_or(RuntimeError::ModuleNotRegistered)
+    }
+
     pub async fn remove_function(&self, func_id: FunctionID) -> bool {
-        if self.functions.contains_key(&func_id) {
-            self.functions.remove(&func_id);
+        if let Some((_, func_handler)) = self.functions.remove(&func_id) {
+            let revision = func_handler.read().await.revision();
+            if revision.owns_namespace() {
+                let _ = self.kv_store.remove_namespace(&revision.namespace).await;
+            }
+            let memory_size = revision.lambda.memory_size();
+            self.metrics.add_memory_reserved(-(memory_size as i64));
+            self.metrics.remove_function(&func_id);
+            *self.currently_allocated_functions.write().await -= 1;
             return true;
         }
         false
@@ -208,55 +1254,298 @@ impl Runtime {
         func_id: FunctionID,
         args: &str,
     ) -> Result<String, RuntimeError> {
+        self.exec_function_with_usage(func_id, args)
+            .await
+            .map(|(result, _)| result)
+    }
+
+    // Same as exec_function, also returning the resources consumed by the run
+    pub async fn exec_function_with_usage(
+        &self,
+        func_id: FunctionID,
+        args: &str,
+    ) -> Result<(String, Usage), RuntimeError> {
         // NOTE: For future use, check multiple function execute tracking
         let func_handler = self
             .functions
             .get(&func_id)
-            .ok_or(RuntimeError::FunctionNotRegistered)
-            .map_err(|e| e)?
+            .ok_or(RuntimeError::FunctionNotRegistered)?
             .value()
             .clone();
 
-        // Exec function
-        let lambda = &func_handler.read().await.lambda;
-        let result = lambda
-            .run(args)
+        // Exec function, the id only tags the guest log lines of the call
+        let context = CallContext::new(Some(nanoid!(20, &nanoid::alphabet::SAFE)));
+        let (result, usage) = func_handler
+            .read()
+            .await
+            .run(&func_id, &self.metrics, args, &context)
+            .await;
+        let result = result.map_err(|e| match e {
+            LambdaError::GuestError(error) => RuntimeError::GuestError(error),
+            e => RuntimeError::FunctionExecError(e.to_string()),
+        })?;
+
+        Ok((result, usage))
+    }
+
+    // Queues the execution and returns at once, the outcome is fetched later by invocation id
+    pub async fn submit_function(
+        &self,
+        func_id: FunctionID,
+        args: String,
+        retention: Option<Duration>,
+    ) -> Result<InvocationID, RuntimeError> {
+        self.submit_function_with_callback(func_id, args, retention, None)
             .await
-            .map_err(|e| RuntimeError::FunctionExecError(e.to_string()))?;
+    }
+
+    // Same as submit_function, the report is also POSTed to `callback_url` once finished
+    pub async fn submit_function_with_callback(
+        &self,
+        func_id: FunctionID,
+        args: String,
+        retention: Option<Duration>,
+        callback_url: Option<String>,
+    ) -> Result<InvocationID, RuntimeError> {
+        self.prune_invocations();
+        let func_handler = self
+            .functions
+            .get(&func_id)
+            .ok_or(RuntimeError::FunctionNotRegistered)?
+            .value()
+            .clone();
+
+        let invocation_id = nanoid!(20, &nanoid::alphabet::SAFE);
+        let retention = retention.unwrap_or(self.invocation_retention);
+        let mut invocation = Invocation::new(func_id.clone(), retention);
+        invocation.callback_url = callback_url;
+        self.invocations.insert(invocation_id.clone(), invocation);
+        self.metrics.queue_depth().inc();
+
+        let invocations = self.invocations.clone();
+        let metrics = self.metrics.clone();
+        let notifier = self.notifier.clone();
+        let id = invocation_id.clone();
+        let span =
+            info_span!("limes.invocation", invocation_id = %invocation_id, function_id = %func_id);
+        let task = tokio::spawn(
+            async move {
+                // The map guards must never be held across an await
+                match invocations.get_mut(&id) {
+                    Some(mut invocation) => invocation.start(),
+                    None => return,
+                }
+                metrics.queue_depth().dec();
+                let (output, usage) = func_handler
+                    .read()
+                    .await
+                    .run(
+                        &func_id,
+                        &metrics,
+                        &args,
+                        &CallContext::new(Some(id.clone())),
+                    )
+                    .await;
+                let output = output.map_err(|e| e.to_string());
+                let status = match output {
+                    Ok(_) => InvocationStatus::Completed,
+                    Err(_) => InvocationStatus::Failed,
+                };
+                let callback = invocations.get_mut(&id).and_then(|mut invocation| {
+                    invocation.finish(status, Some(output), Some(usage));
+                    let report = invocation.report(&id);
+                    invocation.callback_url.clone().map(|url| (url, report))
+                });
+                if let Some((url, report)) = callback {
+                    notifier.notify(&url, &report).await;
+                }
+            }
+            .instrument(span),
+        );
 
-        Ok(result)
+        if let Some(mut invocation) = self.invocations.get_mut(&invocation_id) {
+            if !invocation.status.is_finished() {
+                invocation.handle = Some(task.abort_handle());
+            }
+        }
+        Ok(invocation_id)
     }
 
+    pub async fn get_invocation(
+        &self,
+        invocation_id: InvocationID,
+    ) -> Result<InvocationReport, RuntimeError> {
+        self.prune_invocations();
+        self.invocations
+            .get(&invocation_id)
+            .map(|invocation| invocation.report(&invocation_id))
+            .ok_or(RuntimeError::InvocationNotFound)
+    }
+
+    pub async fn invocation_result(
+        &self,
+        invocation_id: InvocationID,
+    ) -> Result<String, RuntimeError> {
+        let report = self.get_invocation(invocation_id).await?;
+        match report.status {
+            InvocationStatus::Completed => Ok(report.result.unwrap_or_default()),
+            InvocationStatus::Failed => Err(RuntimeError::FunctionExecError(
+                report.error.unwrap_or_default(),
+            )),
+            InvocationStatus::Cancelled => Err(RuntimeError::InvocationCancelled),
+            _ => Err(RuntimeError::InvocationNotFinished),
+        }
+    }
+
+    // A pending invocation never starts, a running one is dropped at its next yield point
+    pub async fn cancel_invocation(&self, invocation_id: InvocationID) -> Result<(), RuntimeError> {
+        let was_running = {
+            let mut invocation = self
+                .invocations
+                .get_mut(&invocation_id)
+                .ok_or(RuntimeError::InvocationNotFound)?;
+            if invocation.status.is_finished() {
+                return Err(RuntimeError::InvocationAlreadyFinished);
+            }
+            if let Some(handle) = invocation.handle.take() {
+                handle.abort();
+            }
+            let was_running = invocation.status == InvocationStatus::Running;
+            if invocation.status == InvocationStatus::Pending {
+                self.metrics.queue_depth().dec();
+            }
+            invocation.finish(InvocationStatus::Cancelled, None, None);
+            if let Some(url) = invocation.callback_url.clone() {
+                let notifier = self.notifier.clone();
+                let report = invocation.report(&invocation_id);
+                tokio::spawn(async move { notifier.notify(&url, &report).await });
+            }
+            was_running
+        };
+
+        // The functions it called may run on any engine
+        if was_running {
+            self.interrupt_engines();
+        }
+        Ok(())
+    }
+
+    fn prune_invocations(&self) {
+        self.invocations
+            .retain(|_, invocation| !invocation.expired());
+    }
+
+    #[tracing::instrument(name = "limes.stop_function", skip(self), fields(function_id = %func_id))]
     pub async fn stop_function(&self, func_id: FunctionID) -> Result<(), RuntimeError> {
         // NOTE: For future use, check multiple function and execution tracking
         let func_handler = self
             .functions
             .get(&func_id)
-            .ok_or(RuntimeError::FunctionNotRegistered)
-            .map_err(|e| e)?
+            .ok_or(RuntimeError::FunctionNotRegistered)?
             .value()
             .clone();
 
-        let lambda = &func_handler.read().await.lambda;
-        lambda
+        let revision = func_handler.read().await.revision();
+        revision
+            .lambda
             .stop()
             .await
             .map_err(|e| RuntimeError::FunctionStopError(e.to_string()))?;
+        // Its nested calls check the stop flag once their engine reaches a yield point
+        self.interrupt_engines();
 
         Ok(())
     }
 
-    async fn get_engine(&self) -> Arc<Engine> {
+    fn interrupt_engines(&self) {
+        for engine in self.engines.iter() {
+            engine.increment_epoch();
+        }
+    }
+
+    pub async fn list_modules(&self) -> Vec<ModuleInfo> {
+        let mut modules: Vec<ModuleInfo> = self
+            .modules
+            .iter()
+            .map(|module| ModuleInfo {
+                module_id: module.key().clone(),
+                size_bytes: module.bytes.len(),
+                functions: 0,
+                dependencies: module.dependencies.clone(),
+                versions: self.names.versions_of(module.key()),
+            })
+            .collect();
+        for function in self.list_functions().await {
+            if let Some(module) = modules
+                .iter_mut()
+                .find(|module| module.module_id == function.module_id)
+            {
+                module.functions += 1;
+            }
+        }
+        modules.sort_by(|a, b| a.module_id.cmp(&b.module_id));
+        modules
+    }
+
+    pub async fn list_functions(&self) -> Vec<FunctionInfo> {
+        let handlers: Vec<(FunctionID, Arc<RwLock<FunctionHandler>>)> = self
+            .functions
+            .iter()
+            .map(|function| (function.key().clone(), function.value().clone()))
+            .collect();
+        let mut functions = Vec::with_capacity(handlers.len());
+        for (func_id, handler) in handlers {
+            functions.push(handler.read().await.info(&func_id));
+        }
+        functions.sort_by(|a, b| a.function_id.cmp(&b.function_id));
+        functions
+    }
+
+    pub async fn function_info(&self, func_id: FunctionID) -> Result<FunctionInfo, RuntimeError> {
+        let func_handler = self.get_function(&func_id)?;
+        let info = func_handler.read().await.info(&func_id);
+        Ok(info)
+    }
+
+    // Output of the function instances with a `seq` after `after`, the last `tail` lines when given
+    pub async fn function_logs(
+        &self,
+        func_id: FunctionID,
+        after: Option<u64>,
+        tail: Option<usize>,
+    ) -> Result<Vec<LogLine>, RuntimeError> {
+        let func_handler = self.get_function(&func_id)?;
+        let logs = func_handler.read().await.revision().lambda.logs();
+        Ok(logs.lines(after, tail))
+    }
+
+    fn get_function(&self, func_id: &str) -> Result<Arc<RwLock<FunctionHandler>>, RuntimeError> {
+        self.functions
+            .get(func_id)
+            .map(|handler| handler.value().clone())
+            .ok_or(RuntimeError::FunctionNotRegistered)
+    }
+
+    /// Key-value namespaces of the functions
+    pub fn kv_store(&self) -> &Arc<KvStore> {
+        &self.kv_store
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    async fn get_engine(&self) -> (usize, Arc<Engine>) {
         #[allow(unused_assignments)]
         let mut engine_rotatory_index = self.engine_rotatory_index.write().await;
-        let index = if *engine_rotatory_index + 1 > self.vcpus {
+        let index = if *engine_rotatory_index + 1 >= self.vcpus {
             *engine_rotatory_index = 0;
             0
         } else {
             *engine_rotatory_index += 1;
             *engine_rotatory_index
         };
-        Arc::clone(self.engines.get(index).unwrap())
+        (index, Arc::clone(self.engines.get(index).unwrap()))
     }
 }
 
@@ -1,11 +1,18 @@
+use super::lambda_error::GuestError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum RuntimeError {
     #[error("RuntimeError: Could not initialize the engine")]
     EngineInitError,
-    #[error("RuntimeError: Could not initialize the Module due to byte code errors")]
-    ComponentBuildError,
+    #[error("RuntimeError: Could not compile the module due to `{0}`")]
+    ComponentBuildError(String),
+    #[error("RuntimeError: Invalid module due to `{0}`")]
+    ModuleValidationError(String),
+    #[error("RuntimeError: Could not pre-initialize the module due to `{0}`")]
+    ModulePreinitError(String),
+    #[error("RuntimeError: Could not adapt the wasip1 module due to `{0}`")]
+    ModuleAdaptError(String),
     #[error("RuntimeError: Module already registered")]
     ModuleAlreadyReg,
     #[error("RuntimeError: Lambda function failed to execute")]
@@ -20,10 +27,34 @@ pub enum RuntimeError {
     FunctionAlreadyInitialized,
     #[error("RuntimeError: Function was not able to execute due to `{0}`")]
     FunctionExecError(String),
+    #[error("RuntimeError: The function failed with `{}`: {}", .0.code, .0.message)]
+    GuestError(GuestError),
     #[error("RuntimeError: Function was not able to stop due to `{0}`")]
     FunctionStopError(String),
     #[error("RuntimeError: The selected module was not registered")]
     ModuleNotRegistered,
     #[error("RuntimeError: The selected function was not registered")]
     FunctionNotRegistered,
+    #[error("RuntimeError: The selected invocation was not found or its retention expired")]
+    InvocationNotFound,
+    #[error("RuntimeError: The invocation is still pending or running")]
+    InvocationNotFinished,
+    #[error("RuntimeError: The invocation was cancelled")]
+    InvocationCancelled,
+    #[error("RuntimeError: The invocation already finished")]
+    InvocationAlreadyFinished,
+    #[error("RuntimeError: Invalid manifest due to `{0}`")]
+    ManifestError(String),
+    #[error("RuntimeError: Could not open the key-value store due to `{0}`")]
+    KvStoreError(String),
+    #[error("RuntimeError: Could not link the dependencies due to `{0}`")]
+    DependencyError(String),
+    #[error("RuntimeError: The module is a dependency of `{0}`")]
+    ModuleInUse(String),
+    #[error("RuntimeError: The key-value namespace of the module is used by `{0}`")]
+    ModuleNamespaceInUse(String),
+    #[error("RuntimeError: Invalid module name due to `{0}`")]
+    ModuleNameError(String),
+    #[error("RuntimeError: `{0}` is already published with another module")]
+    ModuleVersionExists(String),
 }
@@ -0,0 +1,568 @@
+use bytes::Bytes;
+use serde::{Serialize, Serializer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime::component::{Linker, Resource};
+use wasmtime::{ResourceLimiter, StoreLimits};
+use wasmtime_wasi::bindings::filesystem::types::{self, HostDescriptor, HostDirectoryEntryStream};
+use wasmtime_wasi::pipe::{ClosedInputStream, ClosedOutputStream};
+use wasmtime_wasi::{
+    DynInputStream, DynOutputStream, FsError, FsResult, InputStream, IoImpl, IoView, OutputStream,
+    Pollable, StreamResult, WasiImpl, WasiView,
+};
+
+/// Resources consumed by a single run, returned along with its result
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Usage {
+    /// Linker setup and instantiation, or checkout and restore for a warm instance
+    #[serde(rename = "instantiate_us", serialize_with = "as_micros")]
+    pub instantiate: Duration,
+    #[serde(rename = "execute_us", serialize_with = "as_micros")]
+    pub execute: Duration,
+    /// Highest total size of the linear memories during the call
+    pub peak_memory_bytes: u64,
+    /// Table elements added during the call
+    pub table_growth: u64,
+    /// Only reported by engines with fuel consumption enabled
+    pub fuel_used: Option<u64>,
+    /// Bytes moved through files of the preopened dirs
+    pub preopen_bytes_read: u64,
+    pub preopen_bytes_written: u64,
+    /// TCP and UDP connections allowed by the socket address check
+    pub sockets_opened: u64,
+    /// Kind of the trap that ended the call, `Host` when a host call failed
+    pub trap: Option<String>,
+}
+
+fn as_micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_micros() as u64)
+}
+
+// Updated by the host side of the guest I/O, shared with the socket check closure
+#[derive(Default)]
+pub(crate) struct IoCounters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    sockets_opened: AtomicU64,
+}
+
+impl IoCounters {
+    pub fn add_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_socket(&self) {
+        self.sockets_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.bytes_read.store(0, Ordering::Relaxed);
+        self.bytes_written.store(0, Ordering::Relaxed);
+        self.sockets_opened.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Store limiter enforcing the StoreLimits while measuring memory and table growth
+pub(crate) struct UsageTracker {
+    limits: StoreLimits,
+    io: Arc<IoCounters>,
+    memory: usize,
+    peak_memory: usize,
+    table_elements: usize,
+    table_base: usize,
+}
+
+impl UsageTracker {
+    pub fn new(limits: StoreLimits, io: Arc<IoCounters>) -> Self {
+        Self {
+            limits,
+            io,
+            memory: 0,
+            peak_memory: 0,
+            table_elements: 0,
+            table_base: 0,
+        }
+    }
+
+    pub fn io(&self) -> &Arc<IoCounters> {
+        &self.io
+    }
+
+    // Starts measuring a call, warm instances keep the memory and tables of the previous ones
+    pub fn begin(&mut self) {
+        self.peak_memory = self.memory;
+        self.table_base = self.table_elements;
+        self.io.reset();
+    }
+
+    pub fn finish(&self, usage: &mut Usage) {
+        usage.peak_memory_bytes = self.peak_memory as u64;
+        usage.table_growth = self.table_elements.saturating_sub(self.table_base) as u64;
+        usage.preopen_bytes_read = self.io.bytes_read.load(Ordering::Relaxed);
+        usage.preopen_bytes_written = self.io.bytes_written.load(Ordering::Relaxed);
+        usage.sockets_opened = self.io.sockets_opened.load(Ordering::Relaxed);
+    }
+}
+
+impl ResourceLimiter for UsageTracker {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if allowed {
+            self.memory += desired - current;
+            self.peak_memory = self.peak_memory.max(self.memory);
+        }
+        Ok(allowed)
+    }
+
+    fn memory_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.table_growing(current, desired, maximum)?;
+        if allowed {
+            self.table_elements += desired - current;
+        }
+        Ok(allowed)
+    }
+
+    fn table_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+pub(crate) trait UsageView: WasiView {
+    fn usage(&mut self) -> &mut UsageTracker;
+}
+
+/// Serves `wasi:filesystem/types` through MeteredFs, replacing the one added by wasmtime_wasi
+pub(crate) fn add_metered_filesystem<T: UsageView>(linker: &mut Linker<T>) -> anyhow::Result<()> {
+    let closure = type_annotate::<T, _>(|t| MeteredFs(WasiImpl(IoImpl(t))));
+    linker.allow_shadowing(true);
+    let result = types::add_to_linker_get_host(linker, closure);
+    linker.allow_shadowing(false);
+    result
+}
+
+fn type_annotate<T: UsageView, F>(val: F) -> F
+where
+    F: Fn(&mut T) -> MeteredFs<&mut T>,
+{
+    val
+}
+
+// The wasmtime_wasi filesystem, counting the bytes of reads and writes on files
+struct MeteredFs<T>(WasiImpl<T>);
+
+impl<T: UsageView> MeteredFs<&mut T> {
+    fn io(&mut self) -> Arc<IoCounters> {
+        self.0 .0 .0.usage().io().clone()
+    }
+}
+
+impl<T: UsageView> types::Host for MeteredFs<&mut T> {
+    fn convert_error_code(&mut self, err: FsError) -> anyhow::Result<types::ErrorCode> {
+        types::Host::convert_error_code(&mut self.0, err)
+    }
+
+    fn filesystem_error_code(
+        &mut self,
+        err: Resource<anyhow::Error>,
+    ) -> anyhow::Result<Option<types::ErrorCode>> {
+        types::Host::filesystem_error_code(&mut self.0, err)
+    }
+}
+
+impl<T: UsageView> HostDescriptor for MeteredFs<&mut T> {
+    async fn advise(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: types::Filesize,
+        len: types::Filesize,
+        advice: types::Advice,
+    ) -> FsResult<()> {
+        HostDescriptor::advise(&mut self.0, fd, offset, len, advice).await
+    }
+
+    async fn sync_data(&mut self, fd: Resource<types::Descriptor>) -> FsResult<()> {
+        HostDescriptor::sync_data(&mut self.0, fd).await
+    }
+
+    async fn get_flags(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<types::DescriptorFlags> {
+        HostDescriptor::get_flags(&mut self.0, fd).await
+    }
+
+    async fn get_type(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<types::DescriptorType> {
+        HostDescriptor::get_type(&mut self.0, fd).await
+    }
+
+    async fn set_size(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        size: types::Filesize,
+    ) -> FsResult<()> {
+        HostDescriptor::set_size(&mut self.0, fd, size).await
+    }
+
+    async fn set_times(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        atim: types::NewTimestamp,
+        mtim: types::NewTimestamp,
+    ) -> FsResult<()> {
+        HostDescriptor::set_times(&mut self.0, fd, atim, mtim).await
+    }
+
+    async fn read(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        len: types::Filesize,
+        offset: types::Filesize,
+    ) -> FsResult<(Vec<u8>, bool)> {
+        let io = self.io();
+        let (buffer, end) = HostDescriptor::read(&mut self.0, fd, len, offset).await?;
+        io.add_read(buffer.len() as u64);
+        Ok((buffer, end))
+    }
+
+    async fn write(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        buf: Vec<u8>,
+        offset: types::Filesize,
+    ) -> FsResult<types::Filesize> {
+        let io = self.io();
+        let written = HostDescriptor::write(&mut self.0, fd, buf, offset).await?;
+        io.add_written(written);
+        Ok(written)
+    }
+
+    async fn read_directory(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<Resource<types::DirectoryEntryStream>> {
+        HostDescriptor::read_directory(&mut self.0, fd).await
+    }
+
+    async fn sync(&mut self, fd: Resource<types::Descriptor>) -> FsResult<()> {
+        HostDescriptor::sync(&mut self.0, fd).await
+    }
+
+    async fn create_directory_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::create_directory_at(&mut self.0, fd, path).await
+    }
+
+    async fn stat(&mut self, fd: Resource<types::Descriptor>) -> FsResult<types::DescriptorStat> {
+        HostDescriptor::stat(&mut self.0, fd).await
+    }
+
+    async fn stat_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: types::PathFlags,
+        path: String,
+    ) -> FsResult<types::DescriptorStat> {
+        HostDescriptor::stat_at(&mut self.0, fd, path_flags, path).await
+    }
+
+    async fn set_times_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: types::PathFlags,
+        path: String,
+        atim: types::NewTimestamp,
+        mtim: types::NewTimestamp,
+    ) -> FsResult<()> {
+        HostDescriptor::set_times_at(&mut self.0, fd, path_flags, path, atim, mtim).await
+    }
+
+    async fn link_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        old_path_flags: types::PathFlags,
+        old_path: String,
+        new_descriptor: Resource<types::Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::link_at(
+            &mut self.0,
+            fd,
+            old_path_flags,
+            old_path,
+            new_descriptor,
+            new_path,
+        )
+        .await
+    }
+
+    async fn open_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: types::PathFlags,
+        path: String,
+        oflags: types::OpenFlags,
+        flags: types::DescriptorFlags,
+    ) -> FsResult<Resource<types::Descriptor>> {
+        HostDescriptor::open_at(&mut self.0, fd, path_flags, path, oflags, flags).await
+    }
+
+    fn drop(&mut self, fd: Resource<types::Descriptor>) -> anyhow::Result<()> {
+        HostDescriptor::drop(&mut self.0, fd)
+    }
+
+    async fn readlink_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<String> {
+        HostDescriptor::readlink_at(&mut self.0, fd, path).await
+    }
+
+    async fn remove_directory_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::remove_directory_at(&mut self.0, fd, path).await
+    }
+
+    async fn rename_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        old_path: String,
+        new_fd: Resource<types::Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::rename_at(&mut self.0, fd, old_path, new_fd, new_path).await
+    }
+
+    async fn symlink_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        src_path: String,
+        dest_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::symlink_at(&mut self.0, fd, src_path, dest_path).await
+    }
+
+    async fn unlink_file_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::unlink_file_at(&mut self.0, fd, path).await
+    }
+
+    fn read_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: types::Filesize,
+    ) -> FsResult<Resource<DynInputStream>> {
+        let io = self.io();
+        let stream = HostDescriptor::read_via_stream(&mut self.0, fd, offset)?;
+        let slot = self.0.table().get_mut(&stream)?;
+        let inner = std::mem::replace(slot, Box::new(ClosedInputStream));
+        *slot = Box::new(MeteredInputStream { inner, io });
+        Ok(stream)
+    }
+
+    fn write_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: types::Filesize,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        let stream = HostDescriptor::write_via_stream(&mut self.0, fd, offset)?;
+        self.meter_output(stream)
+    }
+
+    fn append_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        let stream = HostDescriptor::append_via_stream(&mut self.0, fd)?;
+        self.meter_output(stream)
+    }
+
+    async fn is_same_object(
+        &mut self,
+        a: Resource<types::Descriptor>,
+        b: Resource<types::Descriptor>,
+    ) -> anyhow::Result<bool> {
+        HostDescriptor::is_same_object(&mut self.0, a, b).await
+    }
+
+    async fn metadata_hash(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<types::MetadataHashValue> {
+        HostDescriptor::metadata_hash(&mut self.0, fd).await
+    }
+
+    async fn metadata_hash_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: types::PathFlags,
+        path: String,
+    ) -> FsResult<types::MetadataHashValue> {
+        HostDescriptor::metadata_hash_at(&mut self.0, fd, path_flags, path).await
+    }
+}
+
+impl<T: UsageView> MeteredFs<&mut T> {
+    fn meter_output(
+        &mut self,
+        stream: Resource<DynOutputStream>,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        let io = self.io();
+        let slot = self.0.table().get_mut(&stream)?;
+        let inner = std::mem::replace(slot, Box::new(ClosedOutputStream));
+        *slot = Box::new(MeteredOutputStream { inner, io });
+        Ok(stream)
+    }
+}
+
+impl<T: UsageView> HostDirectoryEntryStream for MeteredFs<&mut T> {
+    async fn read_directory_entry(
+        &mut self,
+        stream: Resource<types::DirectoryEntryStream>,
+    ) -> FsResult<Option<types::DirectoryEntry>> {
+        HostDirectoryEntryStream::read_directory_entry(&mut self.0, stream).await
+    }
+
+    fn drop(&mut self, stream: Resource<types::DirectoryEntryStream>) -> anyhow::Result<()> {
+        HostDirectoryEntryStream::drop(&mut self.0, stream)
+    }
+}
+
+struct MeteredInputStream {
+    inner: DynInputStream,
+    io: Arc<IoCounters>,
+}
+
+#[async_trait::async_trait]
+impl Pollable for MeteredInputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for MeteredInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let bytes = self.inner.read(size)?;
+        self.io.add_read(bytes.len() as u64);
+        Ok(bytes)
+    }
+
+    async fn blocking_read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let bytes = self.inner.blocking_read(size).await?;
+        self.io.add_read(bytes.len() as u64);
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, nelem: usize) -> StreamResult<usize> {
+        self.inner.skip(nelem)
+    }
+
+    async fn blocking_skip(&mut self, nelem: usize) -> StreamResult<usize> {
+        self.inner.blocking_skip(nelem).await
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+}
+
+struct MeteredOutputStream {
+    inner: DynOutputStream,
+    io: Arc<IoCounters>,
+}
+
+#[async_trait::async_trait]
+impl Pollable for MeteredOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for MeteredOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let len = bytes.len() as u64;
+        self.inner.write(bytes)?;
+        self.io.add_written(len);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.inner.check_write()
+    }
+
+    async fn blocking_write_and_flush(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let len = bytes.len() as u64;
+        self.inner.blocking_write_and_flush(bytes).await?;
+        self.io.add_written(len);
+        Ok(())
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        self.inner.write_zeroes(nelem)?;
+        self.io.add_written(nelem as u64);
+        Ok(())
+    }
+
+    async fn blocking_write_zeroes_and_flush(&mut self, nelem: usize) -> StreamResult<()> {
+        self.inner.blocking_write_zeroes_and_flush(nelem).await?;
+        self.io.add_written(nelem as u64);
+        Ok(())
+    }
+
+    async fn write_ready(&mut self) -> StreamResult<usize> {
+        self.inner.write_ready().await
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+}
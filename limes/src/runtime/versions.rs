@@ -0,0 +1,165 @@
+use super::runtime_error::RuntimeError;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Alias following the last published version of a name
+pub const LATEST_ALIAS: &str = "latest";
+
+/// Versions of a named module and the aliases pointing to them
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ModuleName {
+    pub name: String,
+    /// Module id of every version
+    pub versions: BTreeMap<String, String>,
+    /// Version every alias points to
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl ModuleName {
+    /// Module id of a version, or of the version an alias points to
+    pub fn resolve(&self, tag: &str) -> Option<&String> {
+        let version = self.aliases.get(tag).map_or(tag, String::as_str);
+        self.versions.get(version)
+    }
+}
+
+/// Names given to registered modules, `name@version` and `name@alias` refer to one of them.
+/// A version always points to the same module, an alias can be moved
+#[derive(Default)]
+pub struct ModuleNames {
+    names: DashMap<String, ModuleName>,
+}
+
+impl ModuleNames {
+    /// Publishes the module as `name@version` and moves `latest` to it. Publishing the same
+    /// module again is a no-op, another module under a published version is refused
+    pub fn publish(&self, name: &str, version: &str, module_id: &str) -> Result<(), RuntimeError> {
+        check_label("name", name)?;
+        check_label("version", version)?;
+        if version == LATEST_ALIAS {
+            return Err(RuntimeError::ModuleNameError(format!(
+                "`{}` is reserved for the last version",
+                LATEST_ALIAS
+            )));
+        }
+        let mut entry = self
+            .names
+            .entry(name.to_string())
+            .or_insert_with(|| ModuleName {
+                name: name.to_string(),
+                ..ModuleName::default()
+            });
+        if entry.aliases.contains_key(version) {
+            return Err(RuntimeError::ModuleNameError(format!(
+                "`{}` is an alias of `{}`",
+                version, name
+            )));
+        }
+        match entry.versions.get(version) {
+            Some(published) if published == module_id => return Ok(()),
+            Some(_) => {
+                return Err(RuntimeError::ModuleVersionExists(format!(
+                    "{}@{}",
+                    name, version
+                )))
+            }
+            None => {}
+        }
+        entry
+            .versions
+            .insert(version.to_string(), module_id.to_string());
+        entry
+            .aliases
+            .insert(LATEST_ALIAS.to_string(), version.to_string());
+        Ok(())
+    }
+
+    /// Points `name@alias` to a published version
+    pub fn set_alias(
+        &self,
+        name: &str,
+        alias: &str,
+        version: &str,
+    ) -> Result<ModuleName, RuntimeError> {
+        check_label("alias", alias)?;
+        let mut entry = self
+            .names
+            .get_mut(name)
+            .ok_or(RuntimeError::ModuleNotRegistered)?;
+        if entry.versions.contains_key(alias) {
+            return Err(RuntimeError::ModuleNameError(format!(
+                "`{}` is a version of `{}`",
+                alias, name
+            )));
+        }
+        if !entry.versions.contains_key(version) {
+            return Err(RuntimeError::ModuleNotRegistered);
+        }
+        entry.aliases.insert(alias.to_string(), version.to_string());
+        Ok(entry.clone())
+    }
+
+    pub fn remove_alias(&self, name: &str, alias: &str) -> Result<(), RuntimeError> {
+        self.names
+            .get_mut(name)
+            .and_then(|mut entry| entry.aliases.remove(alias))
+            .map(drop)
+            .ok_or(RuntimeError::ModuleNotRegistered)
+    }
+
+    pub fn get(&self, name: &str) -> Option<ModuleName> {
+        self.names.get(name).map(|entry| entry.clone())
+    }
+
+    /// Module id behind `name@version` or `name@alias`
+    pub fn resolve(&self, reference: &str) -> Option<String> {
+        let (name, tag) = reference.split_once('@')?;
+        self.names.get(name)?.resolve(tag).cloned()
+    }
+
+    /// `name@version` of every version published for the module
+    pub fn versions_of(&self, module_id: &str) -> Vec<String> {
+        let mut versions: Vec<String> = self
+            .names
+            .iter()
+            .flat_map(|entry| {
+                entry
+                    .versions
+                    .iter()
+                    .filter(|(_, id)| *id == module_id)
+                    .map(|(version, _)| format!("{}@{}", entry.name, version))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// Drops the versions of a removed module and the aliases pointing to them, a name left
+    /// without versions is dropped too
+    pub fn remove_module(&self, module_id: &str) {
+        self.names.retain(|_, entry| {
+            let ModuleName {
+                versions, aliases, ..
+            } = entry;
+            versions.retain(|_, id| id != module_id);
+            aliases.retain(|_, version| versions.contains_key(version));
+            !versions.is_empty()
+        });
+    }
+}
+
+fn check_label(kind: &str, value: &str) -> Result<(), RuntimeError> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(RuntimeError::ModuleNameError(format!(
+            "invalid {} `{}`, use letters, digits, `.`, `_` and `-`",
+            kind, value
+        )));
+    }
+    Ok(())
+}
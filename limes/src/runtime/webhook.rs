@@ -0,0 +1,151 @@
+use super::invocation::InvocationReport;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Header carrying `sha256=<hex>`, the HMAC of the request body keyed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Limes-Signature";
+/// Header carrying the id of the invocation the callback is about
+pub const INVOCATION_HEADER: &str = "X-Limes-Invocation";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum WebhookError {
+    #[error("WebhookError: Could not encode the invocation report due to `{0}`")]
+    EncodeError(String),
+    #[error("WebhookError: The callback was refused with status `{0}`")]
+    Refused(u16),
+    #[error("WebhookError: Gave up after {0} attempts, last error `{1}`")]
+    RetriesExhausted(u32, String),
+}
+
+/// Delivery settings shared by every callback of a runtime
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    secret: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn new(
+        max_retries: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+        secret: Option<String>,
+    ) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff,
+            secret,
+        }
+    }
+
+    // The delay doubles after each failed attempt, up to max_backoff
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            secret: None,
+        }
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `body`, as sent in the signature header
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// POSTs the report to `url`, retrying network errors, 429 and 5xx answers with backoff
+pub async fn deliver(
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+    url: &str,
+    report: &InvocationReport,
+) -> Result<(), WebhookError> {
+    let body = serde_json::to_vec(report).map_err(|e| WebhookError::EncodeError(e.to_string()))?;
+
+    let mut last_error = String::new();
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(config.backoff(attempt - 1)).await;
+        }
+
+        let mut request = client
+            .post(url)
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(INVOCATION_HEADER, &report.id)
+            .body(body.clone());
+        if let Some(secret) = &config.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(WebhookError::Refused(status.as_u16()));
+                }
+                last_error = status.to_string();
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+        log::debug!(
+            "Callback for invocation {} failed at attempt {}: {}",
+            report.id,
+            attempt + 1,
+            last_error
+        );
+    }
+    Err(WebhookError::RetriesExhausted(
+        config.max_retries + 1,
+        last_error,
+    ))
+}
+
+// Shared by the invocation tasks of a runtime to report to their callbacks
+#[derive(Clone)]
+pub(crate) struct Notifier {
+    client: reqwest::Client,
+    config: Arc<WebhookConfig>,
+}
+
+impl Notifier {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config: Arc::new(config),
+        }
+    }
+
+    // Failures are only logged, the report stays available for polling
+    pub async fn notify(&self, url: &str, report: &InvocationReport) {
+        if let Err(e) = deliver(&self.client, &self.config, url, report).await {
+            log::warn!(
+                "Callback for invocation {} to {} failed: {}",
+                report.id,
+                url,
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use super::api_error::ApiError;
+use super::telemetry;
+use crate::runtime::invocation::InvocationReport;
+use crate::runtime::logs::LogLine;
+use crate::runtime::manifest::{Deployment, FunctionManifest};
+use crate::runtime::runtime::{FunctionInfo, ModuleInfo, RegisterOptions, Runtime};
+use crate::runtime::versions::ModuleName;
+use crate::tools::inspect::ModuleReport;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{middleware, Extension, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+type ApiResult<T> = Result<T, ApiError>;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct InitFunctionRequest {
+    pub tap_ip: Option<Ipv4Addr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SubmitRequest {
+    #[serde(default)]
+    pub args: String,
+    /// Seconds the result is kept once the invocation finished
+    pub retention_secs: Option<u64>,
+    /// Url receiving the invocation report once finished
+    pub callback_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishRequest {
+    pub module_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AliasRequest {
+    pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFunctionRequest {
+    /// Module id, or `name@version` and `name@alias`
+    pub module: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ModuleQuery {
+    /// Comma separated ids of the modules exporting the imports of the component
+    pub dependencies: Option<String>,
+    /// The body is a wasip1 core module to wrap into a component
+    #[serde(default)]
+    pub adapt_wasip1: bool,
+}
+
+impl ModuleQuery {
+    fn dependencies(&self) -> Vec<String> {
+        self.dependencies
+            .iter()
+            .flat_map(|dependencies| dependencies.split(','))
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogsQuery {
+    /// Only the lines with a greater `seq`
+    pub after: Option<u64>,
+    /// Only the last lines
+    pub tail: Option<usize>,
+}
+
+/// Settings of the api that are not part of the runtime
+#[derive(Clone, Debug)]
+pub struct ApiOptions {
+    /// Address functions may connect to when the init request does not pick one
+    pub default_tap_ip: Ipv4Addr,
+    /// Accepted bearer tokens, when empty the api is open
+    pub tokens: Vec<String>,
+    /// Lets deployed manifests map host directories into their functions
+    pub allow_mounts: bool,
+    /// Largest body of the routes uploading a component, in bytes
+    pub max_upload_size: usize,
+}
+
+/// Components can be larger than the 2 MiB axum accepts by default
+pub const DEFAULT_MAX_UPLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+impl Default for ApiOptions {
+    fn default() -> Self {
+        Self {
+            default_tap_ip: Ipv4Addr::LOCALHOST,
+            tokens: Vec::new(),
+            allow_mounts: false,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+        }
+    }
+}
+
+/// Routes of the Limes HTTP api, sharing a single runtime
+pub fn router(runtime: Arc<Runtime>) -> Router {
+    router_with_options(runtime, ApiOptions::default())
+}
+
+pub fn router_with_options(runtime: Arc<Runtime>, options: ApiOptions) -> Router {
+    let upload_limit = DefaultBodyLimit::max(options.max_upload_size);
+    let options = Arc::new(options);
+    Router::new()
+        .route(
+            "/modules",
+            get(list_modules).post(register_module).layer(upload_limit),
+        )
+        .route(
+            "/modules/validate",
+            post(validate_module).layer(upload_limit),
+        )
+        .route("/deployments", post(deploy).layer(upload_limit))
+        .route("/modules/{id}", get(inspect_module).delete(remove_module))
+        .route("/modules/{id}/functions", post(init_function))
+        .route("/names/{name}", get(module_name))
+        .route("/names/{name}/versions/{version}", put(publish_module))
+        .route(
+            "/names/{name}/aliases/{alias}",
+            put(set_module_alias).delete(remove_module_alias),
+        )
+        .route("/functions", get(list_functions))
+        .route(
+            "/functions/{id}",
+            get(function_info).delete(remove_function),
+        )
+        .route("/functions/{id}/module", put(update_function))
+        .route("/functions/{id}/exec", post(exec_function))
+        .route("/functions/{id}/stop", post(stop_function))
+        .route("/functions/{id}/logs", get(function_logs))
+        .route("/functions/{id}/invocations", post(submit_function))
+        .route(
+            "/invocations/{id}",
+            get(get_invocation).delete(cancel_invocation),
+        )
+        .route("/invocations/{id}/result", get(invocation_result))
+        .route("/metrics", get(metrics))
+        .layer(middleware::from_fn_with_state(
+            options.clone(),
+            require_token,
+        ))
+        .layer(Extension(options))
+        .layer(middleware::from_fn(telemetry::propagate_trace_context))
+        .with_state(runtime)
+}
+
+async fn require_token(
+    State(options): State<Arc<ApiOptions>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if options.tokens.is_empty() {
+        return Ok(next.run(request).await);
+    }
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if options.tokens.iter().any(|accepted| accepted == token) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+async fn list_modules(State(runtime): State<Arc<Runtime>>) -> Json<Vec<ModuleInfo>> {
+    Json(runtime.list_modules().await)
+}
+
+async fn register_module(
+    State(runtime): State<Arc<Runtime>>,
+    Query(query): Query<ModuleQuery>,
+    body: Bytes,
+) -> ApiResult<Json<Value>> {
+    let options = RegisterOptions {
+        dependencies: query.dependencies(),
+        adapt_wasip1: query.adapt_wasip1,
+    };
+    let module_id = runtime
+        .register_module_with_options(body.to_vec(), options)
+        .await?;
+    Ok(Json(json!({ "module_id": module_id })))
+}
+
+// Always 200, the report lists the problems a registration of the body would run into
+async fn validate_module(
+    State(runtime): State<Arc<Runtime>>,
+    Query(query): Query<ModuleQuery>,
+    body: Bytes,
+) -> Json<ModuleReport> {
+    Json(runtime.validate_module(&body, &query.dependencies()))
+}
+
+async fn inspect_module(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ModuleReport>> {
+    Ok(Json(runtime.inspect_module(&id)?))
+}
+
+// Multipart body with a `manifest` part, the limes.toml text, and a `module` part, the component
+async fn deploy(
+    State(runtime): State<Arc<Runtime>>,
+    Extension(options): Extension<Arc<ApiOptions>>,
+    mut multipart: Multipart,
+) -> ApiResult<Json<Deployment>> {
+    let bad_request = |e: &dyn std::fmt::Display| ApiError::BadRequest(e.to_string());
+    let (mut manifest, mut module) = (None, None);
+    while let Some(field) = multipart.next_field().await.map_err(|e| bad_request(&e))? {
+        match field.name() {
+            Some("manifest") => {
+                manifest = Some(field.text().await.map_err(|e| bad_request(&e))?);
+            }
+            Some("module") => module = Some(field.bytes().await.map_err(|e| bad_request(&e))?),
+            _ => {}
+        }
+    }
+    let manifest = manifest.ok_or_else(|| bad_request(&"missing manifest part"))?;
+    let module = module.ok_or_else(|| bad_request(&"missing module part"))?;
+    let manifest = FunctionManifest::from_toml(&manifest)?;
+    if !manifest.mounts.is_empty() && !options.allow_mounts {
+        return Err(ApiError::BadRequest(
+            "mounts are not allowed on this server".to_string(),
+        ));
+    }
+    let deployment = runtime
+        .deploy_with_tap_ip(&manifest, module.to_vec(), options.default_tap_ip)
+        .await?;
+    Ok(Json(deployment))
+}
+
+async fn remove_module(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    runtime.remove_module(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn init_function(
+    State(runtime): State<Arc<Runtime>>,
+    Extension(options): Extension<Arc<ApiOptions>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ApiResult<Json<Value>> {
+    let request: InitFunctionRequest = parse_optional_json(&body)?;
+    let tap_ip = request.tap_ip.unwrap_or(options.default_tap_ip);
+    let function_id = runtime.init_function(id, tap_ip).await?;
+    Ok(Json(json!({ "function_id": function_id })))
+}
+
+async fn module_name(
+    State(runtime): State<Arc<Runtime>>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<ModuleName>> {
+    Ok(Json(runtime.module_name(&name)?))
+}
+
+async fn publish_module(
+    State(runtime): State<Arc<Runtime>>,
+    Path((name, version)): Path<(String, String)>,
+    body: Bytes,
+) -> ApiResult<Json<ModuleName>> {
+    let request: PublishRequest = parse_json(&body)?;
+    Ok(Json(runtime.publish_module(
+        &request.module_id,
+        &name,
+        &version,
+    )?))
+}
+
+async fn set_module_alias(
+    State(runtime): State<Arc<Runtime>>,
+    Path((name, alias)): Path<(String, String)>,
+    body: Bytes,
+) -> ApiResult<Json<ModuleName>> {
+    let request: AliasRequest = parse_json(&body)?;
+    Ok(Json(runtime.set_module_alias(
+        &name,
+        &alias,
+        &request.version,
+    )?))
+}
+
+async fn remove_module_alias(
+    State(runtime): State<Arc<Runtime>>,
+    Path((name, alias)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    runtime.remove_module_alias(&name, &alias)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_functions(State(runtime): State<Arc<Runtime>>) -> Json<Vec<FunctionInfo>> {
+    Json(runtime.list_functions().await)
+}
+
+async fn function_info(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<FunctionInfo>> {
+    Ok(Json(runtime.function_info(id).await?))
+}
+
+// Moves the function to another module, the calls already running finish on the previous one
+async fn update_function(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ApiResult<Json<FunctionInfo>> {
+    let request: UpdateFunctionRequest = parse_json(&body)?;
+    runtime.update_function(id.clone(), &request.module).await?;
+    Ok(Json(runtime.function_info(id).await?))
+}
+
+// Lines the function instances wrote on stdout and stderr, oldest first
+async fn function_logs(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> ApiResult<Json<Vec<LogLine>>> {
+    Ok(Json(
+        runtime.function_logs(id, query.after, query.tail).await?,
+    ))
+}
+
+async fn remove_function(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> StatusCode {
+    match runtime.remove_function(id).await {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+// Blocks until the guest returns, the body is passed verbatim as the function arguments
+async fn exec_function(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+    body: String,
+) -> ApiResult<Json<Value>> {
+    let (result, usage) = runtime.exec_function_with_usage(id, &body).await?;
+    Ok(Json(json!({ "result": result, "usage": usage })))
+}
+
+async fn stop_function(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    runtime.stop_function(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn submit_function(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Value>)> {
+    let request: SubmitRequest = parse_optional_json(&body)?;
+    let retention = request.retention_secs.map(Duration::from_secs);
+    if let Some(url) = &request.callback_url {
+        check_callback_url(url)?;
+    }
+    let invocation_id = runtime
+        .submit_function_with_callback(id, request.args, retention, request.callback_url)
+        .await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "invocation_id": invocation_id })),
+    ))
+}
+
+async fn get_invocation(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<InvocationReport>> {
+    Ok(Json(runtime.get_invocation(id).await?))
+}
+
+async fn invocation_result(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Value>> {
+    let result = runtime.invocation_result(id).await?;
+    Ok(Json(json!({ "result": result })))
+}
+
+async fn cancel_invocation(
+    State(runtime): State<Arc<Runtime>>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    runtime.cancel_invocation(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn metrics(State(runtime): State<Arc<Runtime>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        runtime.metrics().encode(),
+    )
+}
+
+// Callbacks are only POSTed to absolute http(s) urls
+fn check_callback_url(url: &str) -> ApiResult<()> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Ok(()),
+        Ok(_) => Err(ApiError::BadRequest(format!(
+            "the callback url `{}` is not http or https",
+            url
+        ))),
+        Err(e) => Err(ApiError::BadRequest(format!(
+            "the callback url `{}` is invalid: {}",
+            url, e
+        ))),
+    }
+}
+
+// An empty body stands for the default request
+fn parse_optional_json<T: Default + serde::de::DeserializeOwned>(body: &[u8]) -> ApiResult<T> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    parse_json(body)
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> ApiResult<T> {
+    serde_json::from_slice(body).map_err(|e| ApiError::BadRequest(e.to_string()))
+}
@@ -0,0 +1,60 @@
+use crate::runtime::runtime_error::RuntimeError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ApiError {
+    #[error("ApiError: {0}")]
+    Runtime(#[from] RuntimeError),
+    #[error("ApiError: Invalid request due to `{0}`")]
+    BadRequest(String),
+    #[error("ApiError: Missing or invalid bearer token")]
+    Unauthorized,
+}
+
+impl ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Runtime(e) => match e {
+                RuntimeError::ComponentBuildError(_)
+                | RuntimeError::ModuleValidationError(_)
+                | RuntimeError::ModulePreinitError(_)
+                | RuntimeError::ModuleAdaptError(_)
+                | RuntimeError::DependencyError(_)
+                | RuntimeError::ModuleNameError(_)
+                | RuntimeError::ManifestError(_)
+                | RuntimeError::GuestError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                RuntimeError::ComponentNotFound
+                | RuntimeError::ModuleNotRegistered
+                | RuntimeError::FunctionNotRegistered
+                | RuntimeError::InvocationNotFound => StatusCode::NOT_FOUND,
+                RuntimeError::ModuleAlreadyReg
+                | RuntimeError::ModuleInUse(_)
+                | RuntimeError::ModuleNamespaceInUse(_)
+                | RuntimeError::ModuleVersionExists(_)
+                | RuntimeError::FunctionAlreadyInitialized
+                | RuntimeError::InvocationNotFinished
+                | RuntimeError::InvocationCancelled
+                | RuntimeError::InvocationAlreadyFinished => StatusCode::CONFLICT,
+                RuntimeError::MaxFunctionDeplaymentReached => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut body = json!({ "error": self.to_string() });
+        // The code and message of a failed guest are kept for the callers to act on
+        if let ApiError::Runtime(RuntimeError::GuestError(error)) = &self {
+            body["guest_error"] = json!(error);
+        }
+        (self.status_code(), Json(body)).into_response()
+    }
+}
@@ -0,0 +1,267 @@
+use crate::runtime::logs::LogLine;
+use crate::runtime::runtime::{FunctionInfo, ModuleInfo};
+use crate::runtime::versions::ModuleName;
+use crate::tools::inspect::ModuleReport;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::Ipv4Addr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ClientError {
+    #[error("ClientError: Could not reach the server due to `{0}`")]
+    Request(String),
+    #[error("ClientError: The server answered {0} `{1}`")]
+    Server(u16, String),
+    #[error("ClientError: Unexpected answer from the server due to `{0}`")]
+    Decode(String),
+}
+
+/// Result of a synchronous call, `usage` as reported by the server
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ExecOutput {
+    pub result: String,
+    pub usage: Value,
+}
+
+/// Talks to a running Limes server over its HTTP api
+#[derive(Clone, Debug)]
+pub struct LimesClient {
+    base_url: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl LimesClient {
+    /// `base_url` is the address of the server, e.g. `http://127.0.0.1:8080`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Bearer token sent with every request
+    pub fn set_token(&mut self, token: Option<String>) -> &mut Self {
+        self.token = token;
+        self
+    }
+
+    pub async fn push_module(&self, bytes: Vec<u8>) -> Result<String, ClientError> {
+        self.push_module_with_dependencies(bytes, &[]).await
+    }
+
+    /// Registers a component importing interfaces exported by the `dependencies` modules
+    pub async fn push_module_with_dependencies(
+        &self,
+        bytes: Vec<u8>,
+        dependencies: &[String],
+    ) -> Result<String, ClientError> {
+        let mut request = self.post("/modules").body(bytes);
+        if !dependencies.is_empty() {
+            request = request.query(&[("dependencies", dependencies.join(","))]);
+        }
+        let answer: Value = self.json(request).await?;
+        Self::field(&answer, "module_id")
+    }
+
+    /// Runs the checks of a registration on the component without registering it
+    pub async fn validate_module(
+        &self,
+        bytes: Vec<u8>,
+        dependencies: &[String],
+    ) -> Result<ModuleReport, ClientError> {
+        let mut request = self.post("/modules/validate").body(bytes);
+        if !dependencies.is_empty() {
+            request = request.query(&[("dependencies", dependencies.join(","))]);
+        }
+        self.json(request).await
+    }
+
+    /// Imports and exports of a registered module
+    pub async fn inspect_module(&self, module_id: &str) -> Result<ModuleReport, ClientError> {
+        self.json(self.get(&format!("/modules/{}", module_id)))
+            .await
+    }
+
+    pub async fn list_modules(&self) -> Result<Vec<ModuleInfo>, ClientError> {
+        self.json(self.get("/modules")).await
+    }
+
+    pub async fn remove_module(&self, module_id: &str) -> Result<(), ClientError> {
+        self.send(self.delete(&format!("/modules/{}", module_id)))
+            .await
+            .map(drop)
+    }
+
+    /// Publishes a registered module as `name@version`, `name@latest` moves to it
+    pub async fn publish_module(
+        &self,
+        module_id: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<ModuleName, ClientError> {
+        let request = self
+            .put(&format!("/names/{}/versions/{}", name, version))
+            .json(&json!({ "module_id": module_id }));
+        self.json(request).await
+    }
+
+    /// Versions and aliases of a module name
+    pub async fn module_name(&self, name: &str) -> Result<ModuleName, ClientError> {
+        self.json(self.get(&format!("/names/{}", name))).await
+    }
+
+    pub async fn set_alias(
+        &self,
+        name: &str,
+        alias: &str,
+        version: &str,
+    ) -> Result<ModuleName, ClientError> {
+        let request = self
+            .put(&format!("/names/{}/aliases/{}", name, alias))
+            .json(&json!({ "version": version }));
+        self.json(request).await
+    }
+
+    pub async fn remove_alias(&self, name: &str, alias: &str) -> Result<(), ClientError> {
+        self.send(self.delete(&format!("/names/{}/aliases/{}", name, alias)))
+            .await
+            .map(drop)
+    }
+
+    pub async fn init_function(
+        &self,
+        module_id: &str,
+        tap_ip: Option<Ipv4Addr>,
+    ) -> Result<String, ClientError> {
+        let request = self
+            .post(&format!("/modules/{}/functions", module_id))
+            .json(&json!({ "tap_ip": tap_ip }));
+        let answer: Value = self.json(request).await?;
+        Self::field(&answer, "function_id")
+    }
+
+    pub async fn list_functions(&self) -> Result<Vec<FunctionInfo>, ClientError> {
+        self.json(self.get("/functions")).await
+    }
+
+    pub async fn function_info(&self, function_id: &str) -> Result<FunctionInfo, ClientError> {
+        self.json(self.get(&format!("/functions/{}", function_id)))
+            .await
+    }
+
+    pub async fn remove_function(&self, function_id: &str) -> Result<(), ClientError> {
+        self.send(self.delete(&format!("/functions/{}", function_id)))
+            .await
+            .map(drop)
+    }
+
+    pub async fn exec(&self, function_id: &str, args: &str) -> Result<ExecOutput, ClientError> {
+        let request = self
+            .post(&format!("/functions/{}/exec", function_id))
+            .body(args.to_string());
+        self.json(request).await
+    }
+
+    /// Moves the function to a module id or a `name@version` and `name@alias` reference
+    pub async fn update_function(
+        &self,
+        function_id: &str,
+        module: &str,
+    ) -> Result<FunctionInfo, ClientError> {
+        let request = self
+            .put(&format!("/functions/{}/module", function_id))
+            .json(&json!({ "module": module }));
+        self.json(request).await
+    }
+
+    pub async fn stop_function(&self, function_id: &str) -> Result<(), ClientError> {
+        self.send(self.post(&format!("/functions/{}/stop", function_id)))
+            .await
+            .map(drop)
+    }
+
+    /// Output of the function with a `seq` after `after`, the last `tail` lines when given
+    pub async fn logs(
+        &self,
+        function_id: &str,
+        after: Option<u64>,
+        tail: Option<usize>,
+    ) -> Result<Vec<LogLine>, ClientError> {
+        let mut query = Vec::new();
+        if let Some(after) = after {
+            query.push(("after", after.to_string()));
+        }
+        if let Some(tail) = tail {
+            query.push(("tail", tail.to_string()));
+        }
+        let request = self
+            .get(&format!("/functions/{}/logs", function_id))
+            .query(&query);
+        self.json(request).await
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.request(self.http.get(format!("{}{}", self.base_url, path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.request(self.http.post(format!("{}{}", self.base_url, path)))
+    }
+
+    fn put(&self, path: &str) -> RequestBuilder {
+        self.request(self.http.put(format!("{}{}", self.base_url, path)))
+    }
+
+    fn delete(&self, path: &str) -> RequestBuilder {
+        self.request(self.http.delete(format!("{}{}", self.base_url, path)))
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // Sends the request, an error status becomes ClientError::Server with the api message
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|body| body.get("error").and_then(Value::as_str).map(String::from))
+            .unwrap_or(match status {
+                StatusCode::NOT_FOUND => "not found".to_string(),
+                _ => body,
+            });
+        Err(ClientError::Server(status.as_u16(), message))
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        self.send(request)
+            .await?
+            .json()
+            .await
+            .map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    fn field(answer: &Value, name: &str) -> Result<String, ClientError> {
+        answer
+            .get(name)
+            .and_then(Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| ClientError::Decode(format!("missing `{}`", name)))
+    }
+}
@@ -0,0 +1,386 @@
+use crate::runtime::lambda::{InstanceMode, OutboundRule};
+use crate::runtime::manifest::WarmPoolSection;
+use crate::runtime::runtime::{Runtime, RuntimeBuilder};
+use crate::runtime::runtime_error::RuntimeError;
+use crate::runtime::webhook::WebhookConfig;
+use crate::server::api::DEFAULT_MAX_UPLOAD_SIZE;
+use crate::tools::units::{
+    deserialize_bytes, deserialize_duration, deserialize_duration_opt, InvalidUnit,
+};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use wasmtime::OptLevel;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("ConfigError: Could not read `{0}` due to `{1}`")]
+    Read(PathBuf, String),
+    #[error("ConfigError: Could not parse the configuration due to `{0}`")]
+    Parse(String),
+    #[error("ConfigError: Unsupported configuration format `{0}`, use toml, yaml or yml")]
+    UnsupportedFormat(String),
+    #[error("ConfigError: {0}")]
+    InvalidValue(#[from] InvalidUnit),
+    #[error("ConfigError: Invalid configuration, {0}")]
+    Invalid(String),
+    #[error("ConfigError: Could not preload `{0}` due to `{1}`")]
+    Preload(PathBuf, RuntimeError),
+}
+
+/// Everything the limes server can be configured with, read from a TOML or YAML file.
+/// Every section and field is optional, the defaults are the ones of the command line
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub server: ListenConfig,
+    pub runtime: RuntimeConfig,
+    pub engine: EngineConfig,
+    pub webhook: WebhookSection,
+    pub network: NetworkConfig,
+    pub auth: AuthConfig,
+    /// Modules registered, and functions initialized, before the server starts listening
+    #[serde(alias = "module")]
+    pub modules: Vec<PreloadModule>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    /// OTLP/HTTP collector receiving the traces
+    pub otlp_endpoint: Option<String>,
+    /// Lets manifests deployed through the api map host directories
+    pub allow_manifest_mounts: bool,
+    /// Largest component, or deployment, the api accepts, e.g. `64MiB`
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub max_upload_size: usize,
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            otlp_endpoint: None,
+            allow_manifest_mounts: false,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Number of engines, one per cpu
+    pub cpus: usize,
+    /// Memory shared by every function, e.g. `512MiB`, `1G` or a number of bytes
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub memory: usize,
+    pub max_functions: usize,
+    /// How long the result of a finished invocation is kept, e.g. `10m`
+    #[serde(deserialize_with = "deserialize_duration_opt")]
+    pub invocation_retention: Option<Duration>,
+    /// Distinct module and function label pairs the metrics keep
+    pub metrics_label_cap: Option<usize>,
+    /// Storage of the runtime: the directory keeping the key-value namespaces of the
+    /// functions, relative to the configuration file. They only live in memory without it
+    pub kv_dir: Option<PathBuf>,
+    /// Calls a function may nest through `limes:invoke/call`
+    pub max_call_depth: Option<usize>,
+    /// How long the init export of a pre-initialized module may run, e.g. `10s`
+    #[serde(deserialize_with = "deserialize_duration_opt")]
+    pub preinit_timeout: Option<Duration>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            cpus: 1,
+            memory: 1024 * 1024 * 100,
+            max_functions: 25,
+            invocation_retention: None,
+            metrics_label_cap: None,
+            kv_dir: None,
+            max_call_depth: None,
+            preinit_timeout: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineOptLevel {
+    None,
+    Speed,
+    #[default]
+    SpeedAndSize,
+}
+
+impl From<EngineOptLevel> for OptLevel {
+    fn from(level: EngineOptLevel) -> Self {
+        match level {
+            EngineOptLevel::None => OptLevel::None,
+            EngineOptLevel::Speed => OptLevel::Speed,
+            EngineOptLevel::SpeedAndSize => OptLevel::SpeedAndSize,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub opt_level: EngineOptLevel,
+    pub consume_fuel: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSection {
+    pub max_retries: u32,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub initial_backoff: Duration,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_backoff: Duration,
+    /// Key signing the callbacks
+    pub secret: Option<String>,
+}
+
+impl Default for WebhookSection {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            secret: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// The only address functions may bind when the init request does not pick one
+    pub default_tap_ip: Ipv4Addr,
+    /// `ip` or `ip:port` functions may connect to when their manifest has no outbound rules,
+    /// any address when missing
+    pub default_outbound: Option<Vec<String>>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            default_tap_ip: Ipv4Addr::LOCALHOST,
+            default_outbound: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn outbound_rules(&self) -> Result<Option<Vec<OutboundRule>>, ConfigError> {
+        self.default_outbound
+            .as_ref()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|rule| {
+                        rule.parse().map_err(|e| {
+                            ConfigError::Invalid(format!("network.default_outbound, {}", e))
+                        })
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Bearer tokens accepted by the api, when empty every request is accepted
+    pub tokens: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreloadModule {
+    /// Path of the component, relative to the configuration file
+    pub path: PathBuf,
+    /// Export run once at registration, its state is baked in every function
+    pub preinit: Option<String>,
+    /// Number of functions initialized from the module
+    #[serde(default = "default_functions")]
+    pub functions: usize,
+    /// Overrides `network.default_tap_ip` for these functions
+    pub tap_ip: Option<Ipv4Addr>,
+    /// Serves the calls from pre-instantiated stores instead of fresh ones
+    pub warm_pool: Option<WarmPoolSection>,
+}
+
+fn default_functions() -> usize {
+    1
+}
+
+/// A module registered from the configuration, with the functions initialized from it
+#[derive(Clone, Debug)]
+pub struct PreloadedModule {
+    pub path: PathBuf,
+    pub module_id: String,
+    pub function_ids: Vec<String>,
+}
+
+impl ServerConfig {
+    /// Reads the file as YAML when its extension is `yaml` or `yml`, as TOML otherwise.
+    /// The module paths are resolved relative to the directory of the file
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Read(path.to_path_buf(), e.to_string()))?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("toml");
+        let mut config = Self::from_str(&text, extension)?;
+        if let Some(dir) = path.parent() {
+            for module in config.modules.iter_mut() {
+                module.path = dir.join(&module.path);
+            }
+            if let Some(kv_dir) = config.runtime.kv_dir.as_mut() {
+                *kv_dir = dir.join(&*kv_dir);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Parses a configuration written in `format`: toml, yaml or yml
+    pub fn from_str(text: &str, format: &str) -> Result<Self, ConfigError> {
+        match format {
+            "toml" => toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string())),
+            "yaml" | "yml" => {
+                serde_yaml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+            }
+            other => Err(ConfigError::UnsupportedFormat(other.to_string())),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: &str| Err(ConfigError::Invalid(message.to_string()));
+        if self.runtime.cpus == 0 {
+            return invalid("runtime.cpus must be > 0");
+        }
+        if self.runtime.max_functions == 0 {
+            return invalid("runtime.max_functions must be > 0");
+        }
+        if self.runtime.memory < self.runtime.max_functions {
+            return invalid("runtime.memory is too small for runtime.max_functions");
+        }
+        if self.server.max_upload_size == 0 {
+            return invalid("server.max_upload_size must be > 0");
+        }
+        if self.runtime.metrics_label_cap == Some(0) {
+            return invalid("runtime.metrics_label_cap must be > 0");
+        }
+        if self.webhook.initial_backoff > self.webhook.max_backoff {
+            return invalid("webhook.initial_backoff must not exceed webhook.max_backoff");
+        }
+        if self.auth.tokens.iter().any(|token| token.trim().is_empty()) {
+            return invalid("auth.tokens must not contain empty tokens");
+        }
+        self.network.outbound_rules()?;
+        let preloaded: usize = self.modules.iter().map(|module| module.functions).sum();
+        if preloaded > self.runtime.max_functions {
+            return Err(ConfigError::Invalid(format!(
+                "{} preloaded functions exceed runtime.max_functions {}",
+                preloaded, self.runtime.max_functions
+            )));
+        }
+        for module in self.modules.iter() {
+            if !module.path.is_file() {
+                return Err(ConfigError::Invalid(format!(
+                    "module `{}` does not exist",
+                    module.path.display()
+                )));
+            }
+            if module.warm_pool.as_ref().is_some_and(|pool| pool.size == 0) {
+                return Err(ConfigError::Invalid(format!(
+                    "the warm pool of `{}` must have size > 0",
+                    module.path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn runtime_builder(&self) -> RuntimeBuilder {
+        let mut builder = Runtime::new();
+        builder
+            .set_cpus(self.runtime.cpus)
+            .set_total_memory_size(self.runtime.memory)
+            .set_max_functions_number(self.runtime.max_functions)
+            .set_opt_level(self.engine.opt_level.into())
+            .set_consume_fuel(self.engine.consume_fuel)
+            .set_webhook_config(WebhookConfig::new(
+                self.webhook.max_retries,
+                self.webhook.initial_backoff,
+                self.webhook.max_backoff,
+                self.webhook.secret.clone(),
+            ));
+        if let Some(retention) = self.runtime.invocation_retention {
+            builder.set_invocation_retention(retention);
+        }
+        if let Some(cap) = self.runtime.metrics_label_cap {
+            builder.set_metrics_label_cap(cap);
+        }
+        if let Some(dir) = &self.runtime.kv_dir {
+            builder.set_kv_dir(dir.clone());
+        }
+        if let Some(depth) = self.runtime.max_call_depth {
+            builder.set_max_call_depth(depth);
+        }
+        if let Some(timeout) = self.runtime.preinit_timeout {
+            builder.set_preinit_timeout(timeout);
+        }
+        // Invalid rules are reported by `validate`
+        if let Ok(Some(rules)) = self.network.outbound_rules() {
+            builder.set_default_outbound(rules);
+        }
+        builder
+    }
+
+    /// Registers every configured module and initializes its functions
+    pub async fn preload(&self, runtime: &Runtime) -> Result<Vec<PreloadedModule>, ConfigError> {
+        let mut preloaded = Vec::new();
+        for module in self.modules.iter() {
+            let preload_error = |e: RuntimeError| ConfigError::Preload(module.path.clone(), e);
+            let bytes = std::fs::read(&module.path)
+                .map_err(|e| ConfigError::Read(module.path.clone(), e.to_string()))?;
+            let module_id = match &module.preinit {
+                Some(export) => runtime.register_module_with_preinit(bytes, export).await,
+                None => runtime.register_module(bytes).await,
+            }
+            .map_err(preload_error)?;
+
+            let tap_ip = module.tap_ip.unwrap_or(self.network.default_tap_ip);
+            let mut function_ids = Vec::new();
+            for _ in 0..module.functions {
+                let mode = match &module.warm_pool {
+                    Some(pool) => InstanceMode::Warm(pool.into()),
+                    None => InstanceMode::Fresh,
+                };
+                let function_id = runtime
+                    .init_function_with_mode(module_id.clone(), tap_ip, mode)
+                    .await
+                    .map_err(preload_error)?;
+                function_ids.push(function_id);
+            }
+            preloaded.push(PreloadedModule {
+                path: module.path.clone(),
+                module_id,
+                function_ids,
+            });
+        }
+        Ok(preloaded)
+    }
+}
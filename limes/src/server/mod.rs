@@ -0,0 +1,5 @@
+pub mod api;
+pub mod api_error;
+pub mod client;
+pub mod config;
+pub mod telemetry;
@@ -0,0 +1,48 @@
+use anyhow::{bail, Context, Result};
+use wasi_preview1_component_adapter_provider::WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER;
+use wit_component::ComponentEncoder;
+
+/// Name of the core module wasip1 modules import the system calls from
+pub const WASIP1_MODULE: &str = "wasi_snapshot_preview1";
+
+/// Wraps a wasip1 core module into a component, like `wasm-tools component new --adapt`.
+///
+/// The imports of `wasi_snapshot_preview1` are served by the standard reactor adapter on top of
+/// WASI 0.2. The world of the component comes from the `component-type` custom section of the
+/// module, as embedded by `wit-bindgen` when building for `wasm32-wasip1`, so a module made
+/// from a `#[limes::function]` becomes a component exporting `component:run/run`. Modules
+/// without that section, such as those of older toolchains, have no world to export and are
+/// refused.
+pub fn adapt_wasip1(bytes: &[u8]) -> Result<Vec<u8>> {
+    if !wasmparser::Parser::is_core_wasm(bytes) {
+        bail!("Only a core module can be adapted, this is a component");
+    }
+    if !has_component_type(bytes)? {
+        bail!(
+            "The module has no `component-type` section, only wasip1 modules built with \
+             wit-bindgen can be adapted"
+        );
+    }
+    ComponentEncoder::default()
+        .validate(true)
+        .module(bytes)
+        .context("Could not read the core module")?
+        .adapter(WASIP1_MODULE, WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER)
+        .context("Could not read the wasi_snapshot_preview1 adapter")?
+        .encode()
+        .context("Could not encode the component")
+}
+
+// wit-bindgen names the section `component-type`, followed by the world and its version
+fn has_component_type(bytes: &[u8]) -> Result<bool> {
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        if let wasmparser::Payload::CustomSection(section) =
+            payload.context("Could not read the core module")?
+        {
+            if section.name().starts_with("component-type") {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,501 @@
+use crate::runtime::lambda::{Lambda, WasiFlags};
+use crate::runtime::runtime::Runtime;
+use crate::runtime::usage::Usage;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use wasmtime::component::Component;
+use wasmtime::{Config, Engine, OptLevel};
+use wasmtime_wasi::{DirPerms, FilePerms};
+
+/// Which API the benchmarks are driven through
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchApi {
+    /// Every iteration compiles the module and builds a brand new Lambda, a cold start
+    #[default]
+    Lambda,
+    /// The module is registered and its function initialized once, then only executed
+    Runtime,
+}
+
+impl std::fmt::Display for BenchApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BenchApi::Lambda => write!(f, "lambda"),
+            BenchApi::Runtime => write!(f, "runtime"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchOptLevel {
+    None,
+    Speed,
+    #[default]
+    SpeedAndSize,
+}
+
+impl From<BenchOptLevel> for OptLevel {
+    fn from(level: BenchOptLevel) -> Self {
+        match level {
+            BenchOptLevel::None => OptLevel::None,
+            BenchOptLevel::Speed => OptLevel::Speed,
+            BenchOptLevel::SpeedAndSize => OptLevel::SpeedAndSize,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineSettings {
+    pub opt_level: BenchOptLevel,
+    pub consume_fuel: bool,
+}
+
+impl EngineSettings {
+    pub fn engine(&self) -> Result<Engine> {
+        let mut config = Config::new();
+        config
+            .async_support(true)
+            .wasm_component_model(true)
+            .epoch_interruption(true)
+            .consume_fuel(self.consume_fuel)
+            .cranelift_opt_level(self.opt_level.into());
+        Engine::new(&config)
+    }
+}
+
+/// A host directory mapped into the guest
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preopen {
+    pub host: PathBuf,
+    pub guest: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BenchmarkSpec {
+    pub name: String,
+    /// Path of the component, relative to the scenario file
+    pub module: PathBuf,
+    #[serde(default)]
+    pub args: String,
+    /// Memory of the function in bytes
+    #[serde(default = "default_memory")]
+    pub memory: usize,
+    #[serde(default)]
+    pub preopens: Vec<Preopen>,
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+    /// Runs done before the measured ones and thrown away
+    #[serde(default)]
+    pub warmup: usize,
+    /// Iterations running at the same time
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_memory() -> usize {
+    1024 * 1024 * 500
+}
+
+fn default_iterations() -> usize {
+    1
+}
+
+fn default_concurrency() -> usize {
+    1
+}
+
+/// A list of benchmarks sharing the same engine settings and API
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub api: BenchApi,
+    #[serde(default)]
+    pub engine: EngineSettings,
+    #[serde(rename = "benchmark")]
+    pub benchmarks: Vec<BenchmarkSpec>,
+}
+
+impl Scenario {
+    /// Reads a TOML scenario, the module and preopen paths become relative to its directory
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the scenario {}", path.display()))?;
+        let mut scenario: Scenario = toml::from_str(&text)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        for spec in scenario.benchmarks.iter_mut() {
+            spec.module = base.join(&spec.module);
+            for preopen in spec.preopens.iter_mut() {
+                preopen.host = base.join(&preopen.host);
+            }
+        }
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for spec in self.benchmarks.iter() {
+            if spec.iterations == 0 || spec.concurrency == 0 {
+                bail!(
+                    "Benchmark {}: iterations and concurrency must be > 0",
+                    spec.name
+                );
+            }
+            if self.api == BenchApi::Runtime && !spec.preopens.is_empty() {
+                bail!("Benchmark {}: preopens need the lambda api", spec.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One measured iteration
+#[derive(Clone, Debug, Serialize)]
+pub struct Sample {
+    pub iteration: usize,
+    /// With the runtime api the module is compiled once, reported by the first iteration
+    #[serde(rename = "compile_us", serialize_with = "as_micros_opt")]
+    pub compile: Option<Duration>,
+    pub usage: Usage,
+    pub error: Option<String>,
+}
+
+fn as_micros_opt<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&(duration.as_micros() as u64)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Distribution of a phase over the samples, in milliseconds
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct Percentiles {
+    pub count: usize,
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles, all zero when there are no values
+    pub fn from_durations(values: &[Duration]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut millis: Vec<f64> = values.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        millis.sort_by(f64::total_cmp);
+        let rank = |p: f64| {
+            let index = ((p / 100.0) * millis.len() as f64).ceil() as usize;
+            millis[index.clamp(1, millis.len()) - 1]
+        };
+        Self {
+            count: millis.len(),
+            min: millis[0],
+            mean: millis.iter().sum::<f64>() / millis.len() as f64,
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+            max: millis[millis.len() - 1],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub errors: usize,
+    pub compile: Percentiles,
+    pub instantiate: Percentiles,
+    pub execute: Percentiles,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchReport {
+    pub name: String,
+    pub api: BenchApi,
+    pub samples: Vec<Sample>,
+    pub summary: Summary,
+}
+
+impl BenchReport {
+    fn new(spec: &BenchmarkSpec, api: BenchApi, samples: Vec<Sample>) -> Self {
+        let ok = samples.iter().filter(|s| s.error.is_none());
+        let summary = Summary {
+            errors: samples.iter().filter(|s| s.error.is_some()).count(),
+            compile: Percentiles::from_durations(
+                &samples.iter().filter_map(|s| s.compile).collect::<Vec<_>>(),
+            ),
+            instantiate: Percentiles::from_durations(
+                &ok.clone().map(|s| s.usage.instantiate).collect::<Vec<_>>(),
+            ),
+            execute: Percentiles::from_durations(&ok.map(|s| s.usage.execute).collect::<Vec<_>>()),
+        };
+        Self {
+            name: spec.name.clone(),
+            api,
+            samples,
+            summary,
+        }
+    }
+}
+
+/// Runs every benchmark of the scenario, one after the other
+pub async fn run_scenario(scenario: &Scenario) -> Result<Vec<BenchReport>> {
+    let mut reports = Vec::with_capacity(scenario.benchmarks.len());
+    for spec in scenario.benchmarks.iter() {
+        log::info!("Starting {} through the {} api", spec.name, scenario.api);
+        let report = match scenario.api {
+            BenchApi::Lambda => run_lambda_benchmark(&scenario.engine, spec).await?,
+            BenchApi::Runtime => run_runtime_benchmark(&scenario.engine, spec).await?,
+        };
+        log::info!(
+            "Finished {}: execute p50 {:.3}ms p99 {:.3}ms, {} errors",
+            spec.name,
+            report.summary.execute.p50,
+            report.summary.execute.p99,
+            report.summary.errors
+        );
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+async fn run_lambda_benchmark(
+    settings: &EngineSettings,
+    spec: &BenchmarkSpec,
+) -> Result<BenchReport> {
+    let engine = Arc::new(settings.engine()?);
+    let bytes = Arc::new(
+        std::fs::read(&spec.module)
+            .with_context(|| format!("Could not read the module {}", spec.module.display()))?,
+    );
+    let shared = Arc::new(spec.clone());
+
+    let samples = run_batches(spec, move |iteration| {
+        let engine = engine.clone();
+        let bytes = bytes.clone();
+        let spec = shared.clone();
+        async move {
+            let compile_start = Instant::now();
+            let component = match Component::from_binary(&engine, &bytes) {
+                Ok(component) => Arc::new(component),
+                Err(e) => return failed(iteration, e.to_string()),
+            };
+            let compile = compile_start.elapsed();
+            let lambda = match Lambda::new(
+                component,
+                spec.memory,
+                Ipv4Addr::new(127, 0, 0, 1),
+                wasi_flags(&spec),
+            )
+            .await
+            {
+                Ok(lambda) => lambda,
+                Err(e) => return failed(iteration, e.to_string()),
+            };
+            let (result, usage) = lambda.run_with_usage(&spec.args).await;
+            Sample {
+                iteration,
+                compile: Some(compile),
+                usage,
+                error: result.err().map(|e| e.to_string()),
+            }
+        }
+    })
+    .await;
+    Ok(BenchReport::new(spec, BenchApi::Lambda, samples))
+}
+
+async fn run_runtime_benchmark(
+    settings: &EngineSettings,
+    spec: &BenchmarkSpec,
+) -> Result<BenchReport> {
+    let runtime = Arc::new(
+        Runtime::new()
+            .set_total_memory_size(spec.memory)
+            .set_max_functions_number(1)
+            .set_opt_level(settings.opt_level.into())
+            .set_consume_fuel(settings.consume_fuel)
+            .build()?,
+    );
+    let bytes = std::fs::read(&spec.module)
+        .with_context(|| format!("Could not read the module {}", spec.module.display()))?;
+    let compile_start = Instant::now();
+    let module_id = runtime.register_module(bytes).await?;
+    let compile = compile_start.elapsed();
+    let func_id = runtime
+        .init_function(module_id.clone(), Ipv4Addr::new(127, 0, 0, 1))
+        .await?;
+
+    let args = spec.args.clone();
+    let exec_runtime = runtime.clone();
+    let exec_func_id = func_id.clone();
+    let mut samples = run_batches(spec, move |iteration| {
+        let runtime = exec_runtime.clone();
+        let func_id = exec_func_id.clone();
+        let args = args.clone();
+        async move {
+            match runtime.exec_function_with_usage(func_id, &args).await {
+                Ok((_, usage)) => Sample {
+                    iteration,
+                    compile: None,
+                    usage,
+                    error: None,
+                },
+                Err(e) => failed(iteration, e.to_string()),
+            }
+        }
+    })
+    .await;
+    if let Some(first) = samples.first_mut() {
+        first.compile = Some(compile);
+    }
+
+    runtime.remove_function(func_id).await;
+    runtime.remove_module(module_id).await?;
+    Ok(BenchReport::new(spec, BenchApi::Runtime, samples))
+}
+
+// Warmup runs first, then the iterations in batches of `concurrency` tasks
+async fn run_batches<F, Fut>(spec: &BenchmarkSpec, run: F) -> Vec<Sample>
+where
+    F: Fn(usize) -> Fut,
+    Fut: std::future::Future<Output = Sample> + Send + 'static,
+{
+    for iteration in 0..spec.warmup {
+        run(iteration).await;
+    }
+
+    let mut samples = Vec::with_capacity(spec.iterations);
+    let mut next = 0;
+    while next < spec.iterations {
+        let batch = spec.concurrency.min(spec.iterations - next);
+        let mut tasks = JoinSet::new();
+        for iteration in next..next + batch {
+            tasks.spawn(run(iteration));
+        }
+        while let Some(sample) = tasks.join_next().await {
+            match sample {
+                Ok(sample) => samples.push(sample),
+                Err(e) => log::warn!("Benchmark task failed: {}", e),
+            }
+        }
+        next += batch;
+    }
+    samples.sort_by_key(|sample| sample.iteration);
+    samples
+}
+
+fn failed(iteration: usize, error: String) -> Sample {
+    Sample {
+        iteration,
+        compile: None,
+        usage: Usage::default(),
+        error: Some(error),
+    }
+}
+
+fn wasi_flags(spec: &BenchmarkSpec) -> WasiFlags {
+    if spec.preopens.is_empty() {
+        return WasiFlags::new(Some(()), None);
+    }
+    let file_map = spec
+        .preopens
+        .iter()
+        .map(|preopen| {
+            let (dir_perms, file_perms) = match preopen.read_only {
+                true => (DirPerms::READ, FilePerms::READ),
+                false => (DirPerms::all(), FilePerms::all()),
+            };
+            (
+                preopen.host.to_string_lossy().to_string(),
+                (preopen.guest.clone(), dir_perms, file_perms),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    WasiFlags::new(Some(()), Some(file_map))
+}
+
+/// One row per sample, times in milliseconds
+pub fn samples_csv(reports: &[BenchReport]) -> String {
+    let mut csv = String::from(
+        "benchmark,api,iteration,compile_ms,instantiate_ms,execute_ms,peak_memory_bytes,fuel_used,preopen_bytes_read,preopen_bytes_written,error\n",
+    );
+    for report in reports.iter() {
+        for sample in report.samples.iter() {
+            let usage = &sample.usage;
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                report.name,
+                report.api,
+                sample.iteration,
+                sample
+                    .compile
+                    .map(|d| (d.as_secs_f64() * 1000.0).to_string())
+                    .unwrap_or_default(),
+                usage.instantiate.as_secs_f64() * 1000.0,
+                usage.execute.as_secs_f64() * 1000.0,
+                usage.peak_memory_bytes,
+                usage.fuel_used.map(|f| f.to_string()).unwrap_or_default(),
+                usage.preopen_bytes_read,
+                usage.preopen_bytes_written,
+                sample
+                    .error
+                    .as_deref()
+                    .unwrap_or("")
+                    .replace([',', '\n'], " ")
+            );
+        }
+    }
+    csv
+}
+
+/// One row per benchmark and phase, times in milliseconds
+pub fn summary_csv(reports: &[BenchReport]) -> String {
+    let mut csv = String::from("benchmark,api,phase,count,errors,min,mean,p50,p90,p99,max\n");
+    for report in reports.iter() {
+        let summary = &report.summary;
+        for (phase, p) in [
+            ("compile", &summary.compile),
+            ("instantiate", &summary.instantiate),
+            ("execute", &summary.execute),
+        ] {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                report.name,
+                report.api,
+                phase,
+                p.count,
+                summary.errors,
+                p.min,
+                p.mean,
+                p.p50,
+                p.p90,
+                p.p99,
+                p.max
+            );
+        }
+    }
+    csv
+}
@@ -0,0 +1,264 @@
+use crate::runtime::lambda::{Program, COMMAND_EXPORT};
+use crate::runtime::manifest::RUN_INTERFACE;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use wasmtime::component::types::{ComponentFunc, ComponentItem};
+use wasmtime::component::{Component, Type};
+use wasmtime::{Engine, ExternType, FuncType, Module};
+
+/// Signature the `run` function of `RUN_INTERFACE` must have
+pub const RUN_SIGNATURE: &str = "func(args: string) -> string";
+
+/// Kind of an import or an export of a component
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Instance,
+    Func,
+    CoreFunc,
+    Module,
+    Component,
+    Type,
+    Resource,
+    Memory,
+    Table,
+    Global,
+}
+
+/// An import or an export of a component
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ComponentEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    /// WIT signature of every function of an instance, or of the entry itself for a function
+    #[serde(default)]
+    pub functions: BTreeMap<String, String>,
+}
+
+/// What a component needs and provides, with the problems that prevent its registration
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ModuleReport {
+    /// Compile, linking and interface errors, empty when the component can be registered
+    pub errors: Vec<String>,
+    /// True when it exports `component:run/run`, or `_start` for a core module, false for a
+    /// library component
+    pub runnable: bool,
+    pub imports: Vec<ComponentEntry>,
+    pub exports: Vec<ComponentEntry>,
+}
+
+impl ModuleReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+pub fn inspect_program(program: &Program) -> ModuleReport {
+    match program {
+        Program::Component(component) => inspect(component),
+        Program::Command(module) => inspect_command(module),
+    }
+}
+
+/// Lists the imports and exports of a core module, named `module/name` for the imports, and
+/// checks it exports `_start` like a wasip1 command
+pub fn inspect_command(module: &Module) -> ModuleReport {
+    let imports: Vec<ComponentEntry> = module
+        .imports()
+        .map(|import| {
+            let name = format!("{}/{}", import.module(), import.name());
+            core_entry(name, &import.ty())
+        })
+        .collect();
+    let exports: Vec<ComponentEntry> = module
+        .exports()
+        .map(|export| core_entry(export.name().to_string(), &export.ty()))
+        .collect();
+
+    let mut errors = Vec::new();
+    let start = exports.iter().find(|export| export.name == COMMAND_EXPORT);
+    match start.and_then(|start| start.functions.get(COMMAND_EXPORT)) {
+        Some(signature) if signature == "func()" => {}
+        Some(signature) => errors.push(format!(
+            "`{}` is a `{}`, expected a `func()`",
+            COMMAND_EXPORT, signature
+        )),
+        None => errors.push(format!(
+            "the core module does not export `{}`",
+            COMMAND_EXPORT
+        )),
+    }
+    ModuleReport {
+        errors,
+        runnable: start.is_some(),
+        imports,
+        exports,
+    }
+}
+
+fn core_entry(name: String, ty: &ExternType) -> ComponentEntry {
+    let mut functions = BTreeMap::new();
+    let kind = match ty {
+        ExternType::Func(func) => {
+            let func_name = name.rsplit('/').next().unwrap_or(&name).to_string();
+            functions.insert(func_name, core_func(func));
+            EntryKind::CoreFunc
+        }
+        ExternType::Memory(_) => EntryKind::Memory,
+        ExternType::Table(_) => EntryKind::Table,
+        ExternType::Global(_) => EntryKind::Global,
+    };
+    ComponentEntry {
+        name,
+        kind,
+        functions,
+    }
+}
+
+/// Writes the signature of a core function, such as `func(i32, i32) -> i32`
+pub fn core_func(func: &FuncType) -> String {
+    let params: Vec<String> = func.params().map(|ty| ty.to_string()).collect();
+    let results: Vec<String> = func.results().map(|ty| ty.to_string()).collect();
+    match results.len() {
+        0 => format!("func({})", params.join(", ")),
+        1 => format!("func({}) -> {}", params.join(", "), results[0]),
+        _ => format!("func({}) -> ({})", params.join(", "), results.join(", ")),
+    }
+}
+
+/// Lists the imports and exports of `component` and checks it implements a supported world:
+/// `component:run/runnable`, or at least one exported interface for a library component
+pub fn inspect(component: &Component) -> ModuleReport {
+    let engine = component.engine();
+    let component_type = component.component_type();
+    let imports: Vec<ComponentEntry> = component_type
+        .imports(engine)
+        .map(|(name, item)| entry(engine, name, &item))
+        .collect();
+    let exports: Vec<ComponentEntry> = component_type
+        .exports(engine)
+        .map(|(name, item)| entry(engine, name, &item))
+        .collect();
+
+    let mut errors = Vec::new();
+    let run = exports.iter().find(|export| export.name == RUN_INTERFACE);
+    match run.map(|run| run.functions.get("run")) {
+        Some(Some(signature)) if signature == RUN_SIGNATURE => {}
+        Some(Some(signature)) => errors.push(format!(
+            "`{}` exports `run: {}`, expected `run: {}`",
+            RUN_INTERFACE, signature, RUN_SIGNATURE
+        )),
+        Some(None) => errors.push(format!("`{}` has no `run` function", RUN_INTERFACE)),
+        None if exports.iter().any(|e| e.kind == EntryKind::Instance) => {}
+        None => errors.push(format!(
+            "the component exports neither `{}` nor any interface",
+            RUN_INTERFACE
+        )),
+    }
+    ModuleReport {
+        errors,
+        runnable: run.is_some(),
+        imports,
+        exports,
+    }
+}
+
+fn entry(engine: &Engine, name: &str, item: &ComponentItem) -> ComponentEntry {
+    let mut functions = BTreeMap::new();
+    let kind = match item {
+        ComponentItem::ComponentInstance(instance) => {
+            for (func_name, func_item) in instance.exports(engine) {
+                if let ComponentItem::ComponentFunc(func) = func_item {
+                    functions.insert(func_name.to_string(), wit_func(&func));
+                }
+            }
+            EntryKind::Instance
+        }
+        ComponentItem::ComponentFunc(func) => {
+            functions.insert(name.to_string(), wit_func(func));
+            EntryKind::Func
+        }
+        ComponentItem::CoreFunc(_) => EntryKind::CoreFunc,
+        ComponentItem::Module(_) => EntryKind::Module,
+        ComponentItem::Component(_) => EntryKind::Component,
+        ComponentItem::Type(_) => EntryKind::Type,
+        ComponentItem::Resource(_) => EntryKind::Resource,
+    };
+    ComponentEntry {
+        name: name.to_string(),
+        kind,
+        functions,
+    }
+}
+
+/// Writes the signature of `func` in WIT, such as `func(args: string) -> string`
+pub fn wit_func(func: &ComponentFunc) -> String {
+    let params: Vec<String> = func
+        .params()
+        .map(|(name, ty)| format!("{}: {}", name, wit_type(&ty)))
+        .collect();
+    let results: Vec<String> = func.results().map(|ty| wit_type(&ty)).collect();
+    match results.len() {
+        0 => format!("func({})", params.join(", ")),
+        1 => format!("func({}) -> {}", params.join(", "), results[0]),
+        _ => format!("func({}) -> ({})", params.join(", "), results.join(", ")),
+    }
+}
+
+/// Writes `ty` in WIT, records, variants, enums and flags are expanded in place of their name
+pub fn wit_type(ty: &Type) -> String {
+    match ty {
+        Type::Bool => "bool".to_string(),
+        Type::S8 => "s8".to_string(),
+        Type::U8 => "u8".to_string(),
+        Type::S16 => "s16".to_string(),
+        Type::U16 => "u16".to_string(),
+        Type::S32 => "s32".to_string(),
+        Type::U32 => "u32".to_string(),
+        Type::S64 => "s64".to_string(),
+        Type::U64 => "u64".to_string(),
+        Type::Float32 => "f32".to_string(),
+        Type::Float64 => "f64".to_string(),
+        Type::Char => "char".to_string(),
+        Type::String => "string".to_string(),
+        Type::List(list) => format!("list<{}>", wit_type(&list.ty())),
+        Type::Record(record) => {
+            let fields: Vec<String> = record
+                .fields()
+                .map(|field| format!("{}: {}", field.name, wit_type(&field.ty)))
+                .collect();
+            format!("record {{ {} }}", fields.join(", "))
+        }
+        Type::Tuple(tuple) => {
+            let types: Vec<String> = tuple.types().map(|ty| wit_type(&ty)).collect();
+            format!("tuple<{}>", types.join(", "))
+        }
+        Type::Variant(variant) => {
+            let cases: Vec<String> = variant
+                .cases()
+                .map(|case| match case.ty {
+                    Some(ty) => format!("{}({})", case.name, wit_type(&ty)),
+                    None => case.name.to_string(),
+                })
+                .collect();
+            format!("variant {{ {} }}", cases.join(", "))
+        }
+        Type::Enum(enum_type) => format!(
+            "enum {{ {} }}",
+            enum_type.names().collect::<Vec<_>>().join(", ")
+        ),
+        Type::Option(option) => format!("option<{}>", wit_type(&option.ty())),
+        Type::Result(result) => match (result.ok(), result.err()) {
+            (Some(ok), Some(err)) => format!("result<{}, {}>", wit_type(&ok), wit_type(&err)),
+            (Some(ok), None) => format!("result<{}>", wit_type(&ok)),
+            (None, Some(err)) => format!("result<_, {}>", wit_type(&err)),
+            (None, None) => "result".to_string(),
+        },
+        Type::Flags(flags) => format!(
+            "flags {{ {} }}",
+            flags.names().collect::<Vec<_>>().join(", ")
+        ),
+        Type::Own(_) => "own<resource>".to_string(),
+        Type::Borrow(_) => "borrow<resource>".to_string(),
+    }
+}
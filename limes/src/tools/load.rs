@@ -0,0 +1,320 @@
+use crate::runtime::runtime::Runtime;
+use crate::tools::bench::Percentiles;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+/// How the requests are issued
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadModel {
+    /// Requests arrive at the target rate whatever the latency, the ones finding every
+    /// slot busy wait in a queue
+    Open,
+    /// Every worker sends its next request once the previous one returned, paced by the
+    /// target rate when there is one
+    Closed,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadConfig {
+    pub model: LoadModel,
+    /// Requests in flight at most, the number of workers for the closed model
+    pub concurrency: usize,
+    /// Requests per second, required by the open model
+    pub rate: Option<f64>,
+    pub duration: Duration,
+    /// Width of the windows of the report over time
+    pub interval: Duration,
+}
+
+/// Something able to serve one request of the load
+#[async_trait::async_trait]
+pub trait LoadTarget: Send + Sync {
+    async fn call(&self) -> Result<(), String>;
+}
+
+/// Calls `exec_function` on a runtime in the same process
+pub struct RuntimeTarget {
+    runtime: Arc<Runtime>,
+    func_id: String,
+    args: String,
+}
+
+impl RuntimeTarget {
+    pub fn new(runtime: Arc<Runtime>, func_id: String, args: String) -> Self {
+        Self {
+            runtime,
+            func_id,
+            args,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoadTarget for RuntimeTarget {
+    async fn call(&self) -> Result<(), String> {
+        self.runtime
+            .exec_function(self.func_id.clone(), &self.args)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// POSTs to the exec endpoint of a running server
+pub struct HttpTarget {
+    client: reqwest::Client,
+    url: String,
+    args: String,
+}
+
+impl HttpTarget {
+    /// `base_url` is the address of the server, e.g. http://127.0.0.1:8080
+    pub fn new(base_url: &str, func_id: &str, args: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!(
+                "{}/functions/{}/exec",
+                base_url.trim_end_matches('/'),
+                func_id
+            ),
+            args,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoadTarget for HttpTarget {
+    async fn call(&self) -> Result<(), String> {
+        let response = self
+            .client
+            .post(&self.url)
+            .body(self.args.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(response.status().to_string()),
+        }
+    }
+}
+
+// One finished request, offsets are from the start of the run
+#[derive(Clone, Debug)]
+struct Record {
+    finished_at: Duration,
+    latency: Duration,
+    queued: Duration,
+    failed: bool,
+}
+
+/// Requests finished during one interval of the run
+#[derive(Clone, Debug, Serialize)]
+pub struct Window {
+    pub start_secs: f64,
+    pub completed: usize,
+    pub errors: usize,
+    pub throughput: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p99_ms: f64,
+    pub queued_mean_ms: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LoadReport {
+    pub model: LoadModel,
+    pub concurrency: usize,
+    pub target_rate: Option<f64>,
+    pub elapsed_secs: f64,
+    pub completed: usize,
+    pub errors: usize,
+    pub error_rate: f64,
+    /// Finished requests per second
+    pub throughput: f64,
+    /// From the scheduled arrival to the answer, queueing included
+    pub latency: Percentiles,
+    /// Time spent waiting for a free slot, always zero for the closed model
+    pub queued: Percentiles,
+    pub windows: Vec<Window>,
+}
+
+impl LoadReport {
+    fn new(config: &LoadConfig, elapsed: Duration, mut records: Vec<Record>) -> Self {
+        records.sort_by_key(|record| record.finished_at);
+        let completed = records.len();
+        let errors = records.iter().filter(|record| record.failed).count();
+        let elapsed_secs = elapsed.as_secs_f64();
+        let latencies: Vec<Duration> = records.iter().map(|record| record.latency).collect();
+        let queued: Vec<Duration> = records.iter().map(|record| record.queued).collect();
+
+        let interval = config.interval.max(Duration::from_millis(1));
+        let mut windows = Vec::new();
+        let mut start = Duration::ZERO;
+        while start < elapsed {
+            let end = start + interval;
+            let window: Vec<&Record> = records
+                .iter()
+                .filter(|record| record.finished_at >= start && record.finished_at < end)
+                .collect();
+            let latency = Percentiles::from_durations(
+                &window
+                    .iter()
+                    .map(|record| record.latency)
+                    .collect::<Vec<_>>(),
+            );
+            let queued = Percentiles::from_durations(
+                &window
+                    .iter()
+                    .map(|record| record.queued)
+                    .collect::<Vec<_>>(),
+            );
+            let width = (elapsed.min(end) - start).as_secs_f64();
+            windows.push(Window {
+                start_secs: start.as_secs_f64(),
+                completed: window.len(),
+                errors: window.iter().filter(|record| record.failed).count(),
+                throughput: window.len() as f64 / width,
+                latency_p50_ms: latency.p50,
+                latency_p99_ms: latency.p99,
+                queued_mean_ms: queued.mean,
+            });
+            start = end;
+        }
+
+        Self {
+            model: config.model,
+            concurrency: config.concurrency,
+            target_rate: config.rate,
+            elapsed_secs,
+            completed,
+            errors,
+            error_rate: match completed {
+                0 => 0.0,
+                n => errors as f64 / n as f64,
+            },
+            throughput: completed as f64 / elapsed_secs,
+            latency: Percentiles::from_durations(&latencies),
+            queued: Percentiles::from_durations(&queued),
+            windows,
+        }
+    }
+}
+
+/// Drives the target for `config.duration`, then waits for the requests still in flight
+pub async fn run_load(
+    target: Arc<dyn LoadTarget>,
+    config: &LoadConfig,
+) -> anyhow::Result<LoadReport> {
+    if config.concurrency == 0 {
+        anyhow::bail!("The concurrency must be > 0");
+    }
+    if let Some(rate) = config.rate {
+        if rate.is_nan() || rate <= 0.0 {
+            anyhow::bail!("The rate must be > 0");
+        }
+    }
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+    match config.model {
+        LoadModel::Open => {
+            let rate = config
+                .rate
+                .ok_or_else(|| anyhow::anyhow!("The open model needs a target rate"))?;
+            run_open(target, config, rate, start, records.clone()).await
+        }
+        LoadModel::Closed => run_closed(target, config, start, records.clone()).await,
+    }
+    let elapsed = start.elapsed();
+    let records = std::mem::take(&mut *records.lock().unwrap_or_else(|e| e.into_inner()));
+    Ok(LoadReport::new(config, elapsed, records))
+}
+
+async fn run_open(
+    target: Arc<dyn LoadTarget>,
+    config: &LoadConfig,
+    rate: f64,
+    start: Instant,
+    records: Arc<Mutex<Vec<Record>>>,
+) {
+    let slots = Arc::new(Semaphore::new(config.concurrency));
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let mut tasks = JoinSet::new();
+    loop {
+        let scheduled = ticker.tick().await.into_std();
+        if scheduled.duration_since(start) >= config.duration {
+            break;
+        }
+        let target = target.clone();
+        let slots = slots.clone();
+        let records = records.clone();
+        tasks.spawn(async move {
+            let Ok(_slot) = slots.acquire_owned().await else {
+                return;
+            };
+            let queued = scheduled.elapsed();
+            let failed = target.call().await.is_err();
+            let record = Record {
+                finished_at: start.elapsed(),
+                latency: scheduled.elapsed(),
+                queued,
+                failed,
+            };
+            records
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(record);
+        });
+        // Reap the finished requests so the set does not grow with the run
+        while tasks.try_join_next().is_some() {}
+    }
+    while tasks.join_next().await.is_some() {}
+}
+
+async fn run_closed(
+    target: Arc<dyn LoadTarget>,
+    config: &LoadConfig,
+    start: Instant,
+    records: Arc<Mutex<Vec<Record>>>,
+) {
+    // Each worker takes its share of the rate
+    let pace = config
+        .rate
+        .map(|rate| Duration::from_secs_f64(config.concurrency as f64 / rate));
+    let mut workers = JoinSet::new();
+    for _ in 0..config.concurrency {
+        let target = target.clone();
+        let records = records.clone();
+        let duration = config.duration;
+        workers.spawn(async move {
+            let mut next = Instant::now();
+            loop {
+                if let Some(pace) = pace {
+                    tokio::time::sleep_until(next.into()).await;
+                    next += pace;
+                }
+                if start.elapsed() >= duration {
+                    break;
+                }
+                let sent = Instant::now();
+                let failed = target.call().await.is_err();
+                let record = Record {
+                    finished_at: start.elapsed(),
+                    latency: sent.elapsed(),
+                    queued: Duration::ZERO,
+                    failed,
+                };
+                records
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(record);
+            }
+        });
+    }
+    while workers.join_next().await.is_some() {}
+}
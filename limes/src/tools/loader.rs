@@ -1,6 +1,6 @@
 use crate::runtime::lambda::Lambda;
 use crate::runtime::lambda::WasiFlags;
-use crate::runtime::lambda_error::LambdaError;
+use crate::tools::snapshot;
 use anyhow::Result;
 use std::net::Ipv4Addr;
 use std::path::Path;
@@ -8,6 +8,7 @@ use std::sync::Arc;
 use wasmtime::component::Component;
 use wasmtime::Config;
 use wasmtime::Engine;
+use wasmtime::Module;
 use wasmtime::OptLevel;
 
 pub async fn load_module_from_file(engine: &Engine, file: &Path) -> Result<Arc<Component>> {
@@ -18,16 +19,37 @@ pub async fn load_module_from_bytes(engine: &Engine, image: &[u8]) -> Result<Arc
     Ok(Arc::new(Component::from_binary(engine, image)?))
 }
 
+// Load a component instrumented for ResetPolicy::Snapshot, text format is accepted too
+pub async fn load_snapshot_module_from_file(
+    engine: &Engine,
+    file: &Path,
+) -> Result<Arc<Component>> {
+    let bytes = wat::parse_file(file)?;
+    load_snapshot_module_from_bytes(engine, &bytes).await
+}
+
+pub async fn load_snapshot_module_from_bytes(
+    engine: &Engine,
+    image: &[u8],
+) -> Result<Arc<Component>> {
+    let bytes = wat::parse_bytes(image)?;
+    let instrumented = snapshot::instrument_component(&bytes)?;
+    Ok(Arc::new(Component::from_binary(engine, &instrumented)?))
+}
+
 pub async fn build_engine(async_support: bool, wasm_component_module: bool) -> Result<Engine> {
     let mut config = Config::new();
     config
         .async_support(async_support)
         .wasm_component_model(wasm_component_module)
+        // Lambda::stop and the lambda timeout interrupt the guest through the epoch
+        .epoch_interruption(true)
         .cranelift_opt_level(OptLevel::SpeedAndSize);
     let engine = Engine::new(&config)?;
     Ok(engine)
 }
 
+// A wasip1 core module runs as a command, anything else is loaded as a component
 pub async fn build_lambda_from_file(
     file: &Path,
     mem_size: usize,
@@ -35,6 +57,11 @@ pub async fn build_lambda_from_file(
     wasi_flags: WasiFlags,
 ) -> Result<Lambda> {
     let engine = build_engine(true, true).await?;
+    let bytes = std::fs::read(file)?;
+    if wasmparser::Parser::is_core_wasm(&bytes) {
+        let module = Arc::new(Module::from_binary(&engine, &bytes)?);
+        return Ok(Lambda::from_command(module, mem_size, tap_ip, wasi_flags).await?);
+    }
     let component = load_module_from_file(&engine, file).await?;
     let lambda = Lambda::new(component, mem_size, tap_ip, wasi_flags).await?;
     Ok(lambda)
@@ -1 +1,9 @@
+pub mod adapt;
+pub mod bench;
+pub mod inspect;
+pub mod load;
 pub mod loader;
+pub mod preinit;
+pub mod reconcile;
+pub mod snapshot;
+pub mod units;
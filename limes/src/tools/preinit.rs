@@ -0,0 +1,386 @@
+use super::snapshot::{self, read_layout, ModuleLayout, PAGE_SIZE_LOG2};
+use crate::runtime::deadline::DeadlineWatch;
+use crate::runtime::guest_log::{self, GuestLogView, GuestLogger};
+use crate::runtime::invoke::{self, InvokeHost, InvokeView};
+use crate::runtime::keyvalue::{self, KvHost, KvView};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use wasm_encoder::reencode::{Reencode, RoundtripReencoder};
+use wasm_encoder::{
+    ComponentSectionId, ConstExpr, DataCountSection, DataSection, GlobalSection, MemorySection,
+    Module, RawSection,
+};
+use wasmparser::{Chunk, DataKind, Parser, Payload};
+use wasmtime::component::{Component, Linker, ResourceTable, Val, WasmList};
+use wasmtime::{Engine, Store, UpdateDeadline};
+use wasmtime_wasi::{IoView, WasiCtx, WasiCtxBuilder, WasiView};
+
+// Zero runs shorter than this are kept inside a data segment instead of splitting it
+const MIN_ZERO_GAP: usize = 64;
+
+struct PreinitState {
+    wasi_ctx: WasiCtx,
+    resource_table: ResourceTable,
+    // Without a namespace, the store is not part of the baked state
+    keyvalue: KvHost,
+    guest_log: GuestLogger,
+    invoke: InvokeHost,
+}
+
+impl IoView for PreinitState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.resource_table
+    }
+}
+
+impl WasiView for PreinitState {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi_ctx
+    }
+}
+
+impl KvView for PreinitState {
+    fn keyvalue(&mut self) -> &mut KvHost {
+        &mut self.keyvalue
+    }
+}
+
+impl GuestLogView for PreinitState {
+    fn guest_log(&mut self) -> &mut GuestLogger {
+        &mut self.guest_log
+    }
+}
+
+impl InvokeView for PreinitState {
+    fn invoke(&mut self) -> &mut InvokeHost {
+        &mut self.invoke
+    }
+}
+
+// State of a core instance once the init export returned
+#[derive(Default)]
+struct ModuleState {
+    memories: Vec<Vec<u8>>,
+    globals: HashMap<u32, ConstExpr>,
+}
+
+/// Runs `init_export` once and bakes the resulting state into a new component, like Wizer.
+///
+/// The linear memories and mutable globals of each core instance are read back after the
+/// call and written into the data segments and global initializers of its module, the start
+/// function is dropped. Every instance of the returned component begins where `init_export`
+/// left. The init export must be a `func()` without side effects outside the wasm state:
+/// no file, socket or other host resource it acquires survives, and tables are not captured.
+/// The run is aborted once `timeout` elapsed.
+pub async fn preinitialize_component(
+    engine: &Engine,
+    bytes: &[u8],
+    init_export: &str,
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let instrumented = snapshot::instrument(bytes, true)?;
+    for (n, instance) in instrumented.instances.iter().enumerate() {
+        if instrumented.instances[..n]
+            .iter()
+            .any(|other| other.module == instance.module)
+        {
+            bail!(
+                "The core module {} is instantiated more than once, its state can not be baked",
+                instance.module
+            );
+        }
+    }
+
+    let component = Component::from_binary(engine, &instrumented.bytes)?;
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::add_to_linker_async(&mut linker)?;
+    keyvalue::add_to_linker(&mut linker)?;
+    guest_log::add_to_linker(&mut linker)?;
+    invoke::add_to_linker(&mut linker)?;
+    let mut store = Store::new(
+        engine,
+        PreinitState {
+            wasi_ctx: WasiCtxBuilder::new().build(),
+            resource_table: ResourceTable::new(),
+            keyvalue: KvHost::default(),
+            guest_log: GuestLogger::default(),
+            invoke: InvokeHost::default(),
+        },
+    );
+    // Like a call, a guest spinning on its own is stopped by the epoch, one awaiting the host
+    // by dropping the future
+    let deadline = Instant::now() + timeout;
+    let timed_out = move || anyhow!("The init export did not finish within {:?}", timeout);
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(move |_| match Instant::now() >= deadline {
+        true => Err(timed_out()),
+        false => Ok(UpdateDeadline::Yield(1)),
+    });
+    let _watch = DeadlineWatch::new(engine, deadline);
+    let run = run_init(&mut store, &component, &linker, &instrumented, init_export);
+    match tokio::time::timeout_at(deadline.into(), run).await {
+        Ok(Ok(states)) => bake_component(bytes, &states),
+        Ok(Err(e)) if Instant::now() < deadline => Err(e),
+        _ => Err(timed_out()),
+    }
+}
+
+// Instantiates the component, calls `init_export` and reads back the state of every instance
+async fn run_init(
+    mut store: &mut Store<PreinitState>,
+    component: &Component,
+    linker: &Linker<PreinitState>,
+    instrumented: &snapshot::Instrumented,
+    init_export: &str,
+) -> Result<HashMap<u32, ModuleState>> {
+    let instance = linker.instantiate_async(&mut store, component).await?;
+
+    let init = instance
+        .get_typed_func::<(), ()>(&mut store, init_export)
+        .map_err(|_| {
+            anyhow!(
+                "The component has no `{}` export of type func()",
+                init_export
+            )
+        })?;
+    init.call_async(&mut store, ()).await?;
+    init.post_return_async(&mut store).await?;
+
+    let mut states = HashMap::new();
+    for (n, instrumented) in instrumented.instances.iter().enumerate() {
+        let mut state = ModuleState::default();
+        for memory in 0..instrumented.memories {
+            let dump = instance.get_typed_func::<(), (WasmList<u8>,)>(
+                &mut store,
+                snapshot::dump_memory_export(n, memory),
+            )?;
+            let (content,) = dump.call_async(&mut store, ()).await?;
+            state.memories.push(content.as_le_slice(&store).to_vec());
+            dump.post_return_async(&mut store).await?;
+        }
+        for global in instrumented.globals.iter() {
+            let dump = instance
+                .get_func(&mut store, snapshot::dump_global_export(n, *global))
+                .ok_or(anyhow!("Missing dump export for global {}", global))?;
+            let mut result = [Val::Bool(false)];
+            dump.call_async(&mut store, &[], &mut result).await?;
+            dump.post_return_async(&mut store).await?;
+            let value = match result[0] {
+                Val::S32(value) => ConstExpr::i32_const(value),
+                Val::S64(value) => ConstExpr::i64_const(value),
+                Val::Float32(value) => ConstExpr::f32_const(value),
+                Val::Float64(value) => ConstExpr::f64_const(value),
+                _ => bail!("Unexpected value for global {}", global),
+            };
+            state.globals.insert(*global, value);
+        }
+        states.insert(instrumented.module, state);
+    }
+    Ok(states)
+}
+
+// Rewrites the core modules of the original component with their captured state
+fn bake_component(bytes: &[u8], states: &HashMap<u32, ModuleState>) -> Result<Vec<u8>> {
+    let mut component = wasm_encoder::Component::new();
+    let mut core_modules = 0;
+
+    let mut parser = Parser::new(0);
+    let mut offset = 0;
+    loop {
+        let (payload, consumed) = match parser.parse(&bytes[offset..], true)? {
+            Chunk::NeedMoreData(_) => bail!("Unexpected end of the component"),
+            Chunk::Parsed { consumed, payload } => (payload, consumed),
+        };
+        offset += consumed;
+
+        match &payload {
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => {
+                offset = unchecked_range.end;
+                let module = &bytes[unchecked_range.clone()];
+                let baked = match states.get(&core_modules) {
+                    Some(state) => Some(bake_module(module, state)?),
+                    None => None,
+                };
+                core_modules += 1;
+                component.section(&RawSection {
+                    id: ComponentSectionId::CoreModule as u8,
+                    data: baked.as_deref().unwrap_or(module),
+                });
+                continue;
+            }
+            Payload::ComponentSection {
+                unchecked_range, ..
+            } => {
+                offset = unchecked_range.end;
+                component.section(&RawSection {
+                    id: ComponentSectionId::Component as u8,
+                    data: &bytes[unchecked_range.clone()],
+                });
+                continue;
+            }
+            Payload::ComponentImportSection(reader) => {
+                for import in reader.clone() {
+                    if let wasmparser::ComponentTypeRef::Module(_) = import?.ty {
+                        core_modules += 1;
+                    }
+                }
+            }
+            Payload::ComponentAliasSection(reader) => {
+                for alias in reader.clone() {
+                    if let wasmparser::ComponentAlias::Outer {
+                        kind: wasmparser::ComponentOuterAliasKind::CoreModule,
+                        ..
+                    } = alias?
+                    {
+                        core_modules += 1;
+                    }
+                }
+            }
+            Payload::ComponentExportSection(reader) => {
+                for export in reader.clone() {
+                    if export?.kind == wasmparser::ComponentExternalKind::Module {
+                        core_modules += 1;
+                    }
+                }
+            }
+            Payload::End(_) => break,
+            _ => {}
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            component.section(&RawSection {
+                id,
+                data: &bytes[range],
+            });
+        }
+    }
+
+    let baked = component.finish();
+    wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+        .validate_all(&baked)?;
+    Ok(baked)
+}
+
+// Replaces the data segments with the memory content and the global initializers with their
+// values. The original segments become empty passive ones so `memory.init` indexes still hold
+fn bake_module(bytes: &[u8], state: &ModuleState) -> Result<Vec<u8>> {
+    let layout = read_layout(bytes)?;
+    let mut segments = Vec::new();
+    for (idx, memory) in state.memories.iter().enumerate() {
+        for (start, end) in non_zero_runs(memory) {
+            segments.push((idx, start, end));
+        }
+    }
+
+    let mut reencoder = RoundtripReencoder;
+    let mut module = Module::new();
+    let mut data_emitted = false;
+    for payload in Parser::new(0).parse_all(bytes) {
+        let payload = payload?;
+        let Some((id, range)) = payload.as_section() else {
+            continue;
+        };
+
+        match payload {
+            Payload::MemorySection(reader) => {
+                let mut section = MemorySection::new();
+                for (idx, memory) in reader.into_iter().enumerate() {
+                    let mut memory = reencoder.memory_type(memory?);
+                    let pages = (state.memories[idx].len() >> PAGE_SIZE_LOG2) as u64;
+                    memory.minimum = memory.minimum.max(pages);
+                    section.memory(memory);
+                }
+                module.section(&section);
+            }
+            Payload::GlobalSection(reader) => {
+                let mut section = GlobalSection::new();
+                for (idx, global) in reader.into_iter().enumerate() {
+                    let global = global?;
+                    let init = match state.globals.get(&(layout.imported_globals + idx as u32)) {
+                        Some(value) => value.clone(),
+                        None => reencoder.const_expr(global.init_expr)?,
+                    };
+                    section.global(reencoder.global_type(global.ty)?, &init);
+                }
+                module.section(&section);
+            }
+            // The start function already ran before the init export
+            Payload::StartSection { .. } => {}
+            Payload::DataCountSection { count, .. } => {
+                module.section(&DataCountSection {
+                    count: count + segments.len() as u32,
+                });
+            }
+            Payload::DataSection(reader) => {
+                data_emitted = true;
+                module.section(&new_data_section(&layout, state, &segments, Some(reader))?);
+            }
+            _ => {
+                module.section(&RawSection {
+                    id,
+                    data: &bytes[range],
+                });
+            }
+        }
+    }
+    if !data_emitted {
+        module.section(&new_data_section(&layout, state, &segments, None)?);
+    }
+
+    let baked = module.finish();
+    wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+        .validate_all(&baked)?;
+    Ok(baked)
+}
+
+// Data is the last ordered section, without one the new section goes at the end
+fn new_data_section(
+    layout: &ModuleLayout,
+    state: &ModuleState,
+    segments: &[(usize, usize, usize)],
+    original: Option<wasmparser::DataSectionReader>,
+) -> Result<DataSection> {
+    let mut section = DataSection::new();
+    if let Some(reader) = original {
+        for data in reader {
+            let data = data?;
+            match data.kind {
+                DataKind::Active { .. } => section.passive([]),
+                DataKind::Passive => section.passive(data.data.iter().copied()),
+            };
+        }
+    }
+    for (memory, start, end) in segments {
+        let content = &state.memories[*memory][*start..*end];
+        section.active(
+            layout.imported_memories + *memory as u32,
+            &ConstExpr::i32_const(*start as i32),
+            content.iter().copied(),
+        );
+    }
+    Ok(section)
+}
+
+// Ranges of the memory holding non zero bytes, close ranges are merged together
+fn non_zero_runs(memory: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut idx = 0;
+    while idx < memory.len() {
+        if memory[idx] == 0 {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < memory.len() && memory[idx] != 0 {
+            idx += 1;
+        }
+        match runs.last_mut() {
+            Some(last) if start - last.1 < MIN_ZERO_GAP => last.1 = idx,
+            _ => runs.push((start, idx)),
+        }
+    }
+    runs
+}
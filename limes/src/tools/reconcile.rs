@@ -0,0 +1,395 @@
+use crate::runtime::manifest::{Deployment, FunctionManifest, MANIFEST_FILE};
+use crate::runtime::runtime::Runtime;
+use crate::runtime::runtime_error::RuntimeError;
+use anyhow::{bail, Context, Result};
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A function as the directory describes it
+#[derive(Clone, Debug)]
+pub struct DesiredFunction {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    manifest: FunctionManifest,
+    bytes: Arc<Vec<u8>>,
+    // Changes when the component, its adaptation, preinit export, dependencies or version change
+    module_hash: String,
+    // Changes when anything in the manifest changes
+    manifest_hash: String,
+}
+
+/// One step bringing the runtime to the desired state
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    Create {
+        name: String,
+    },
+    /// The function takes the new settings, on a new module when the component changed. It
+    /// keeps its id and key-value namespace
+    Update {
+        name: String,
+        module_changed: bool,
+    },
+    Remove {
+        name: String,
+    },
+}
+
+impl Change {
+    pub fn name(&self) -> &str {
+        match self {
+            Change::Create { name } | Change::Update { name, .. } | Change::Remove { name } => name,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Create { name } => write!(f, "+ {}", name),
+            Change::Update {
+                name,
+                module_changed: true,
+            } => write!(f, "~ {} (module and manifest)", name),
+            Change::Update { name, .. } => write!(f, "~ {} (manifest)", name),
+            Change::Remove { name } => write!(f, "- {}", name),
+        }
+    }
+}
+
+/// The difference between the directory and what the reconciler deployed
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+    pub unchanged: Vec<String>,
+    desired: BTreeMap<String, DesiredFunction>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn count(&self, filter: impl Fn(&Change) -> bool) -> usize {
+        self.changes.iter().filter(|change| filter(change)).count()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        write!(
+            f,
+            "{} to create, {} to update, {} to remove, {} unchanged",
+            self.count(|c| matches!(c, Change::Create { .. })),
+            self.count(|c| matches!(c, Change::Update { .. })),
+            self.count(|c| matches!(c, Change::Remove { .. })),
+            self.unchanged.len()
+        )
+    }
+}
+
+/// What `Reconciler::apply` did, a failed change is planned again by the next reconcile
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ApplyReport {
+    pub applied: Vec<Change>,
+    pub failed: Vec<(Change, String)>,
+}
+
+#[derive(Clone, Debug)]
+struct Deployed {
+    deployment: Deployment,
+    module_hash: String,
+    manifest_hash: String,
+}
+
+/// Keeps the functions of a runtime in line with a directory of manifests. Every manifest
+/// is either `<dir>/<name>/limes.toml` or `<dir>/<name>.toml`, named after its `name` field
+/// when set. Only the functions deployed by the reconciler are touched
+pub struct Reconciler {
+    runtime: Arc<Runtime>,
+    default_tap_ip: Ipv4Addr,
+    deployed: BTreeMap<String, Deployed>,
+    // Modules replaced by updates whose removal failed, tried again by every apply
+    stale_modules: Vec<String>,
+}
+
+impl Reconciler {
+    pub fn new(runtime: Arc<Runtime>, default_tap_ip: Ipv4Addr) -> Self {
+        Self {
+            runtime,
+            default_tap_ip,
+            deployed: BTreeMap::new(),
+            stale_modules: Vec::new(),
+        }
+    }
+
+    /// Ids of the functions deployed so far, by name
+    pub fn deployments(&self) -> BTreeMap<String, Deployment> {
+        self.deployed
+            .iter()
+            .map(|(name, deployed)| (name.clone(), deployed.deployment.clone()))
+            .collect()
+    }
+
+    /// Reads the directory and compares it with the deployed functions, nothing is applied
+    pub fn plan(&self, dir: &Path) -> Result<Plan> {
+        let desired = load_dir(dir)?;
+        let mut plan = Plan::default();
+        for (name, function) in desired.iter() {
+            match self.deployed.get(name) {
+                None => plan.changes.push(Change::Create { name: name.clone() }),
+                Some(deployed)
+                    if deployed.module_hash != function.module_hash
+                        || deployed.manifest_hash != function.manifest_hash =>
+                {
+                    plan.changes.push(Change::Update {
+                        name: name.clone(),
+                        module_changed: deployed.module_hash != function.module_hash,
+                    })
+                }
+                Some(_) => plan.unchanged.push(name.clone()),
+            }
+        }
+        for name in self.deployed.keys() {
+            if !desired.contains_key(name) {
+                plan.changes.push(Change::Remove { name: name.clone() });
+            }
+        }
+        plan.desired = desired;
+        Ok(plan)
+    }
+
+    pub async fn apply(&mut self, plan: Plan) -> ApplyReport {
+        let mut report = ApplyReport::default();
+        self.remove_stale_modules().await;
+        for change in plan.changes {
+            let result = match &change {
+                Change::Create { name } => self.create(&plan.desired[name]).await,
+                Change::Update {
+                    name,
+                    module_changed,
+                } => self.update(&plan.desired[name], *module_changed).await,
+                Change::Remove { name } => self.remove(name).await,
+            };
+            match result {
+                Ok(()) => report.applied.push(change),
+                Err(e) => report.failed.push((change, format!("{:#}", e))),
+            }
+        }
+        report
+    }
+
+    /// Plans and applies in one step
+    pub async fn reconcile(&mut self, dir: &Path) -> Result<ApplyReport> {
+        let plan = self.plan(dir)?;
+        Ok(self.apply(plan).await)
+    }
+
+    /// Reconciles every time the directory changes, once writes settled for `debounce`.
+    /// With `dry_run` the plans are only logged. Runs until the watcher fails
+    pub async fn watch(&mut self, dir: &Path, debounce: Duration, dry_run: bool) -> Result<()> {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if event.is_ok() {
+                    let _ = sender.send(());
+                }
+            })?;
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+
+        while receiver.recv().await.is_some() {
+            // Let a burst of writes settle before reading the directory
+            while let Ok(Some(())) = tokio::time::timeout(debounce, receiver.recv()).await {}
+            let plan = match self.plan(dir) {
+                Ok(plan) if plan.is_empty() => continue,
+                Ok(plan) => plan,
+                Err(e) => {
+                    log::warn!("Could not read {}: {:#}", dir.display(), e);
+                    continue;
+                }
+            };
+            log::info!("Changes in {}:\n{}", dir.display(), plan);
+            if dry_run {
+                continue;
+            }
+            let report = self.apply(plan).await;
+            for (change, error) in report.failed.iter() {
+                log::warn!("Could not apply `{}`: {}", change, error);
+            }
+        }
+        Ok(())
+    }
+
+    async fn create(&mut self, function: &DesiredFunction) -> Result<()> {
+        let deployment = self
+            .runtime
+            .deploy_with_tap_ip(
+                &function.manifest,
+                function.bytes.to_vec(),
+                self.default_tap_ip,
+            )
+            .await?;
+        self.deployed.insert(
+            function.name.clone(),
+            Deployed {
+                deployment,
+                module_hash: function.module_hash.clone(),
+                manifest_hash: function.manifest_hash.clone(),
+            },
+        );
+        Ok(())
+    }
+
+    // The function moves to the new module and settings in place, calls keep being served
+    async fn update(&mut self, function: &DesiredFunction, module_changed: bool) -> Result<()> {
+        let old = self.deployed[&function.name].clone();
+        let module_id = match module_changed {
+            true => {
+                self.runtime
+                    .register_manifest_module(&function.manifest, function.bytes.to_vec())
+                    .await?
+            }
+            false => old.deployment.module_id.clone(),
+        };
+
+        let mut manifest = function.manifest.clone();
+        manifest.network.tap_ip.get_or_insert(self.default_tap_ip);
+        let updated = self
+            .runtime
+            .update_function_with_manifest(
+                old.deployment.function_id.clone(),
+                &module_id,
+                &manifest,
+            )
+            .await;
+        if let Err(e) = updated {
+            if module_changed {
+                let _ = self.runtime.remove_module(module_id).await;
+            }
+            return Err(e.into());
+        }
+        self.deployed.insert(
+            function.name.clone(),
+            Deployed {
+                deployment: Deployment {
+                    module_id: module_id.clone(),
+                    function_id: old.deployment.function_id,
+                },
+                module_hash: function.module_hash.clone(),
+                manifest_hash: function.manifest_hash.clone(),
+            },
+        );
+        // The function already runs the new module, a failed cleanup does not fail the update
+        if module_changed {
+            self.stale_modules.push(old.deployment.module_id);
+            self.remove_stale_modules().await;
+        }
+        Ok(())
+    }
+
+    // The entry is kept until the module is gone too, so a failed removal is planned again
+    async fn remove(&mut self, name: &str) -> Result<()> {
+        if let Some(deployed) = self.deployed.get(name) {
+            let deployment = deployed.deployment.clone();
+            self.runtime.remove_function(deployment.function_id).await;
+            match self.runtime.remove_module(deployment.module_id).await {
+                Ok(()) | Err(RuntimeError::ModuleNotRegistered) => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.deployed.remove(name);
+        }
+        Ok(())
+    }
+
+    async fn remove_stale_modules(&mut self) {
+        let mut stale = Vec::new();
+        for module_id in std::mem::take(&mut self.stale_modules) {
+            match self.runtime.remove_module(module_id.clone()).await {
+                Ok(()) | Err(RuntimeError::ModuleNotRegistered) => {}
+                Err(e) => {
+                    log::warn!("Could not remove the replaced module {}: {}", module_id, e);
+                    stale.push(module_id);
+                }
+            }
+        }
+        self.stale_modules = stale;
+    }
+}
+
+/// Reads every manifest of the directory with its component
+pub fn load_dir(dir: &Path) -> Result<BTreeMap<String, DesiredFunction>> {
+    let mut manifests = Vec::new();
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("could not read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() && path.join(MANIFEST_FILE).is_file() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            manifests.push((name.to_string(), path.join(MANIFEST_FILE)));
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            manifests.push((name.to_string(), path));
+        }
+    }
+
+    let mut desired = BTreeMap::new();
+    for (name, path) in manifests {
+        let text = std::fs::read(&path)?;
+        let manifest = FunctionManifest::from_file(&path)
+            .with_context(|| format!("invalid manifest {}", path.display()))?;
+        let bytes = manifest
+            .read_module()
+            .with_context(|| format!("invalid manifest {}", path.display()))?;
+        let name = manifest.name.clone().unwrap_or(name);
+
+        // Every field is followed by a separator, so moving bytes between fields changes the hash
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes);
+        hasher.update([manifest.adapt_wasip1 as u8]);
+        let fields = [manifest.preinit.as_deref(), manifest.version.as_deref()];
+        for field in fields
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .chain(manifest.dependencies.iter().map(String::as_str))
+        {
+            hasher.update([0]);
+            hasher.update(field);
+        }
+        let module_hash = hex::encode(hasher.finalize());
+        let manifest_hash = hex::encode(Sha256::digest(&text));
+
+        let function = DesiredFunction {
+            name: name.clone(),
+            manifest_path: path.clone(),
+            manifest,
+            bytes: Arc::new(bytes),
+            module_hash,
+            manifest_hash,
+        };
+        if let Some(other) = desired.insert(name.clone(), function) {
+            bail!(
+                "`{}` is declared by both {} and {}",
+                name,
+                other.manifest_path.display(),
+                path.display()
+            );
+        }
+    }
+    Ok(desired)
+}
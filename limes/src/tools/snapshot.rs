@@ -0,0 +1,815 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use wasm_encoder::reencode::{Reencode, RoundtripReencoder};
+use wasm_encoder::{
+    Alias, CanonicalFunctionSection, CanonicalOption, CodeSection, ComponentAliasSection,
+    ComponentExportKind, ComponentExportSection, ComponentSectionId, ComponentTypeSection,
+    ComponentValType, ConstExpr, ExportKind, ExportSection, Function, FunctionSection,
+    GlobalSection, GlobalType, Instruction, MemArg, MemorySection, MemoryType, Module,
+    PrimitiveValType, RawSection, SectionId, TypeSection, ValType,
+};
+use wasmparser::{
+    CanonicalFunction, Chunk, ComponentAlias, ComponentExternalKind, ComponentOuterAliasKind,
+    ComponentTypeRef, ExternalKind, Instance, Parser, Payload, TypeRef,
+};
+
+// Names of the core exports added to every instrumented module
+const CORE_SNAPSHOT_EXPORT: &str = "__limes_snapshot";
+const CORE_RESTORE_EXPORT: &str = "__limes_restore";
+const CORE_SHADOW_MEMORY_EXPORT: &str = "__limes_shadow_memory";
+const CORE_DUMP_MEMORY_EXPORT: &str = "__limes_dump_memory";
+const CORE_DUMP_GLOBAL_EXPORT: &str = "__limes_dump_global";
+
+/// Prefix of the component exports that capture the state of a core instance
+pub const SNAPSHOT_EXPORT_PREFIX: &str = "limes-snapshot-instance";
+/// Prefix of the component exports that restore the state of a core instance
+pub const RESTORE_EXPORT_PREFIX: &str = "limes-restore-instance";
+
+// The linear memory page size, custom page sizes are refused by the instrumentation
+pub(crate) const PAGE_SIZE_LOG2: i32 = 16;
+
+/// Rewrites a component so that the state of each core instance can be captured and restored.
+///
+/// Every core module defining a linear memory or a mutable global gets a shadow memory and a
+/// shadow global for each of them, plus two exported functions copying the state into and out
+/// of the shadows. The component then lifts and exports those functions as
+/// `limes-snapshot-instanceN` and `limes-restore-instanceN`, one pair per core instance.
+/// Tables and host resources are not part of the snapshot.
+pub fn instrument_component(bytes: &[u8]) -> Result<Vec<u8>> {
+    Ok(instrument(bytes, false)?.bytes)
+}
+
+/// A component rewritten by `instrument`, with the core instances that received the exports
+pub(crate) struct Instrumented {
+    pub bytes: Vec<u8>,
+    pub instances: Vec<InstrumentedInstance>,
+}
+
+pub(crate) struct InstrumentedInstance {
+    /// Index of the instantiated core module in the component
+    pub module: u32,
+    /// Number of memories defined by the module
+    pub memories: u32,
+    /// Index of the mutable globals defined by the module
+    pub globals: Vec<u32>,
+}
+
+/// Export returning the content of a memory, only added with `dump`
+pub(crate) fn dump_memory_export(instance: usize, memory: u32) -> String {
+    format!("limes-dump-instance{}-memory{}", instance, memory)
+}
+
+/// Export returning the value of a mutable global, only added with `dump`
+pub(crate) fn dump_global_export(instance: usize, global: u32) -> String {
+    format!("limes-dump-instance{}-global{}", instance, global)
+}
+
+/// Instruments the component for snapshots, with `dump` the memories and mutable globals of
+/// every core instance can also be read back by the host
+pub(crate) fn instrument(bytes: &[u8], dump: bool) -> Result<Instrumented> {
+    let mut component = wasm_encoder::Component::new();
+    let mut counter = IndexCounter::default();
+    let mut instrumented_modules: HashMap<u32, ModuleAdditions> = HashMap::new();
+    let mut instances: Vec<(u32, InstrumentedInstance)> = Vec::new();
+
+    let mut parser = Parser::new(0);
+    let mut offset = 0;
+    loop {
+        let (payload, consumed) = match parser.parse(&bytes[offset..], true)? {
+            Chunk::NeedMoreData(_) => bail!("Unexpected end of the component"),
+            Chunk::Parsed { consumed, payload } => (payload, consumed),
+        };
+        offset += consumed;
+
+        match &payload {
+            Payload::Version { encoding, .. } => {
+                if *encoding != wasmparser::Encoding::Component {
+                    bail!("The binary is a core module, not a component");
+                }
+            }
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => {
+                // Nested modules are not visited by the outer parser
+                offset = unchecked_range.end;
+                let module = &bytes[unchecked_range.clone()];
+                let instrumented = match ModuleAdditions::new(module, dump)? {
+                    Some(additions) => {
+                        let instrumented = additions.apply(module)?;
+                        instrumented_modules.insert(counter.core_modules, additions);
+                        Some(instrumented)
+                    }
+                    None => None,
+                };
+                counter.core_modules += 1;
+                component.section(&RawSection {
+                    id: ComponentSectionId::CoreModule as u8,
+                    data: instrumented.as_deref().unwrap_or(module),
+                });
+                continue;
+            }
+            Payload::ComponentSection {
+                unchecked_range, ..
+            } => {
+                offset = unchecked_range.end;
+                component.section(&RawSection {
+                    id: ComponentSectionId::Component as u8,
+                    data: &bytes[unchecked_range.clone()],
+                });
+                continue;
+            }
+            Payload::InstanceSection(reader) => {
+                for instance in reader.clone() {
+                    if let Instance::Instantiate { module_index, .. } = instance? {
+                        if let Some(additions) = instrumented_modules.get(&module_index) {
+                            instances.push((
+                                counter.core_instances,
+                                InstrumentedInstance {
+                                    module: module_index,
+                                    memories: additions.memories.len() as u32,
+                                    globals: additions.globals.iter().map(|g| g.0).collect(),
+                                },
+                            ));
+                        }
+                    }
+                    counter.core_instances += 1;
+                }
+            }
+            Payload::End(_) => break,
+            _ => counter.count(&payload)?,
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            component.section(&RawSection {
+                id,
+                data: &bytes[range],
+            });
+        }
+    }
+
+    if instances.is_empty() {
+        return Ok(Instrumented {
+            bytes: component.finish(),
+            instances: Vec::new(),
+        });
+    }
+
+    // Component types used to lift the new functions
+    let mut types = ComponentTypeSection::new();
+    let unit_func_type = counter.types;
+    types
+        .function()
+        .params::<[(&str, ComponentValType); 0], _>([])
+        .results::<[(&str, ComponentValType); 0], _>([]);
+    let bytes_type = counter.types + 1;
+    types.defined_type().list(PrimitiveValType::U8);
+    let bytes_func_type = counter.types + 2;
+    types
+        .function()
+        .params::<[(&str, ComponentValType); 0], _>([])
+        .result(ComponentValType::Type(bytes_type));
+    let primitives = [
+        (ValType::I32, PrimitiveValType::S32),
+        (ValType::I64, PrimitiveValType::S64),
+        (ValType::F32, PrimitiveValType::F32),
+        (ValType::F64, PrimitiveValType::F64),
+    ];
+    let mut global_func_types = HashMap::new();
+    for (n, (val_type, primitive)) in primitives.into_iter().enumerate() {
+        types
+            .function()
+            .params::<[(&str, ComponentValType); 0], _>([])
+            .result(ComponentValType::Primitive(primitive));
+        global_func_types.insert(val_type, counter.types + 3 + n as u32);
+    }
+    component.section(&types);
+
+    let mut aliases = ComponentAliasSection::new();
+    let mut canonicals = CanonicalFunctionSection::new();
+    let mut exports = ComponentExportSection::new();
+    let mut lift = |instance: u32, core_name: &str, export: &str, ty: u32, memory: Option<&str>| {
+        let mut options = Vec::new();
+        if let Some(memory) = memory {
+            aliases.alias(Alias::CoreInstanceExport {
+                instance,
+                kind: ExportKind::Memory,
+                name: memory,
+            });
+            options.push(CanonicalOption::Memory(counter.core_memories));
+            counter.core_memories += 1;
+        }
+        aliases.alias(Alias::CoreInstanceExport {
+            instance,
+            kind: ExportKind::Func,
+            name: core_name,
+        });
+        canonicals.lift(counter.core_funcs, ty, options);
+        exports.export(export, ComponentExportKind::Func, counter.funcs, None);
+        counter.core_funcs += 1;
+        counter.funcs += 1;
+    };
+
+    for (n, (instance, instrumented)) in instances.iter().enumerate() {
+        let snapshot_export = format!("{}{}", SNAPSHOT_EXPORT_PREFIX, n);
+        let restore_export = format!("{}{}", RESTORE_EXPORT_PREFIX, n);
+        lift(
+            *instance,
+            CORE_SNAPSHOT_EXPORT,
+            &snapshot_export,
+            unit_func_type,
+            None,
+        );
+        lift(
+            *instance,
+            CORE_RESTORE_EXPORT,
+            &restore_export,
+            unit_func_type,
+            None,
+        );
+        if !dump {
+            continue;
+        }
+        for memory in 0..instrumented.memories {
+            lift(
+                *instance,
+                &format!("{}{}", CORE_DUMP_MEMORY_EXPORT, memory),
+                &dump_memory_export(n, memory),
+                bytes_func_type,
+                Some(&format!("{}{}", CORE_SHADOW_MEMORY_EXPORT, memory)),
+            );
+        }
+        let additions = &instrumented_modules[&instrumented.module];
+        for (global, _, val_type) in additions.globals.iter() {
+            lift(
+                *instance,
+                &format!("{}{}", CORE_DUMP_GLOBAL_EXPORT, global),
+                &dump_global_export(n, *global),
+                global_func_types[val_type],
+                None,
+            );
+        }
+    }
+    component.section(&aliases);
+    component.section(&canonicals);
+    component.section(&exports);
+
+    Ok(Instrumented {
+        bytes: component.finish(),
+        instances: instances
+            .into_iter()
+            .map(|(_, instance)| instance)
+            .collect(),
+    })
+}
+
+// Tracks the index spaces of the top level component needed to append new items
+#[derive(Default)]
+struct IndexCounter {
+    core_modules: u32,
+    core_instances: u32,
+    core_funcs: u32,
+    core_memories: u32,
+    funcs: u32,
+    types: u32,
+}
+
+impl IndexCounter {
+    fn count(&mut self, payload: &Payload) -> Result<()> {
+        match payload {
+            Payload::ComponentTypeSection(reader) => self.types += reader.count(),
+            Payload::ComponentImportSection(reader) => {
+                for import in reader.clone() {
+                    match import?.ty {
+                        ComponentTypeRef::Func(_) => self.funcs += 1,
+                        ComponentTypeRef::Type(_) => self.types += 1,
+                        ComponentTypeRef::Module(_) => self.core_modules += 1,
+                        _ => {}
+                    }
+                }
+            }
+            Payload::ComponentExportSection(reader) => {
+                for export in reader.clone() {
+                    self.count_component_kind(export?.kind);
+                }
+            }
+            Payload::ComponentAliasSection(reader) => {
+                for alias in reader.clone() {
+                    match alias? {
+                        ComponentAlias::InstanceExport { kind, .. } => {
+                            self.count_component_kind(kind)
+                        }
+                        ComponentAlias::CoreInstanceExport { kind, .. } => match kind {
+                            ExternalKind::Func => self.core_funcs += 1,
+                            ExternalKind::Memory => self.core_memories += 1,
+                            _ => {}
+                        },
+                        ComponentAlias::Outer { kind, .. } => match kind {
+                            ComponentOuterAliasKind::CoreModule => self.core_modules += 1,
+                            ComponentOuterAliasKind::Type => self.types += 1,
+                            _ => {}
+                        },
+                    }
+                }
+            }
+            Payload::ComponentCanonicalSection(reader) => {
+                for canonical in reader.clone() {
+                    match canonical? {
+                        CanonicalFunction::Lift { .. } => self.funcs += 1,
+                        _ => self.core_funcs += 1,
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn count_component_kind(&mut self, kind: ComponentExternalKind) {
+        match kind {
+            ComponentExternalKind::Func => self.funcs += 1,
+            ComponentExternalKind::Type => self.types += 1,
+            ComponentExternalKind::Module => self.core_modules += 1,
+            _ => {}
+        }
+    }
+}
+
+// What a core module defines, collected before rewriting it
+#[derive(Default)]
+pub(crate) struct ModuleLayout {
+    pub types: u32,
+    pub imported_funcs: u32,
+    pub imported_memories: u32,
+    pub imported_globals: u32,
+    pub defined_funcs: u32,
+    pub defined_memories: u32,
+    pub defined_globals: Vec<wasmparser::GlobalType>,
+}
+
+pub(crate) fn read_layout(bytes: &[u8]) -> Result<ModuleLayout> {
+    let mut layout = ModuleLayout::default();
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::TypeSection(reader) => {
+                for group in reader {
+                    layout.types += group?.types().len() as u32;
+                }
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    match import?.ty {
+                        TypeRef::Func(_) => layout.imported_funcs += 1,
+                        TypeRef::Memory(_) => layout.imported_memories += 1,
+                        TypeRef::Global(_) => layout.imported_globals += 1,
+                        _ => {}
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => layout.defined_funcs = reader.count(),
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    let memory = memory?;
+                    if memory.memory64 || memory.shared || memory.page_size_log2.is_some() {
+                        bail!(
+                            "Only unshared 32 bit memories with default pages can be snapshotted"
+                        );
+                    }
+                    layout.defined_memories += 1;
+                }
+            }
+            Payload::GlobalSection(reader) => {
+                for global in reader {
+                    layout.defined_globals.push(global?.ty);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(layout)
+}
+
+// Sections must follow the order of the spec, the tag and data count ones sit between others
+pub(crate) fn section_order(id: u8) -> u8 {
+    match id {
+        id if id == SectionId::Tag as u8 => SectionId::Memory as u8 * 2 + 1,
+        id if id == SectionId::DataCount as u8 => SectionId::Element as u8 * 2 + 1,
+        id => id * 2,
+    }
+}
+
+// Items appended to a core module to snapshot, restore and optionally dump its state
+struct ModuleAdditions {
+    dump: bool,
+    types_base: u32,
+    funcs_base: u32,
+    // Pairs of (memory, shadow memory)
+    memories: Vec<(u32, u32)>,
+    // Globals holding the number of pages copied in each shadow memory
+    pages_globals: Vec<u32>,
+    // Triples of (global, shadow global, type)
+    globals: Vec<(u32, u32, ValType)>,
+}
+
+impl ModuleAdditions {
+    // `None` if the module has no memory nor mutable global of its own
+    fn new(bytes: &[u8], dump: bool) -> Result<Option<Self>> {
+        let layout = read_layout(bytes)?;
+        let mutable_globals = layout
+            .defined_globals
+            .iter()
+            .enumerate()
+            .filter(|(_, ty)| ty.mutable)
+            .map(|(idx, ty)| (layout.imported_globals + idx as u32, ty))
+            .collect::<Vec<_>>();
+        if layout.defined_memories == 0 && mutable_globals.is_empty() {
+            return Ok(None);
+        }
+
+        let globals_base = layout.imported_globals + layout.defined_globals.len() as u32;
+        let shadows_base = globals_base + layout.defined_memories;
+        let mut globals = Vec::new();
+        for (idx, (global, ty)) in mutable_globals.into_iter().enumerate() {
+            let val_type = RoundtripReencoder.val_type(ty.content_type)?;
+            if dump
+                && !matches!(
+                    val_type,
+                    ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64
+                )
+            {
+                bail!("Only numeric globals can be dumped");
+            }
+            globals.push((global, shadows_base + idx as u32, val_type));
+        }
+
+        Ok(Some(Self {
+            dump,
+            types_base: layout.types,
+            funcs_base: layout.imported_funcs + layout.defined_funcs,
+            memories: (0..layout.defined_memories)
+                .map(|idx| {
+                    (
+                        layout.imported_memories + idx,
+                        layout.imported_memories + layout.defined_memories + idx,
+                    )
+                })
+                .collect(),
+            pages_globals: (0..layout.defined_memories)
+                .map(|idx| globals_base + idx)
+                .collect(),
+            globals,
+        }))
+    }
+
+    fn snapshot_func(&self) -> u32 {
+        self.funcs_base
+    }
+
+    fn restore_func(&self) -> u32 {
+        self.funcs_base + 1
+    }
+
+    fn dump_memory_func(&self, memory: usize) -> u32 {
+        self.funcs_base + 2 + memory as u32
+    }
+
+    fn dump_global_func(&self, global: usize) -> u32 {
+        self.funcs_base + 2 + self.memories.len() as u32 + global as u32
+    }
+
+    fn apply(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut reencoder = RoundtripReencoder;
+        let mut module = Module::new();
+        let mut emitted: Vec<u8> = Vec::new();
+
+        for payload in Parser::new(0).parse_all(bytes) {
+            let payload = payload?;
+            let Some((id, range)) = payload.as_section() else {
+                continue;
+            };
+            if id != SectionId::Custom as u8 {
+                self.emit_missing_sections(&mut module, &mut emitted, section_order(id));
+                emitted.push(id);
+            }
+
+            match payload {
+                Payload::TypeSection(reader) => {
+                    let mut section = TypeSection::new();
+                    reencoder.parse_type_section(&mut section, reader)?;
+                    self.append_types(&mut section);
+                    module.section(&section);
+                }
+                Payload::FunctionSection(reader) => {
+                    let mut section = FunctionSection::new();
+                    reencoder.parse_function_section(&mut section, reader)?;
+                    self.append_functions(&mut section);
+                    module.section(&section);
+                }
+                Payload::MemorySection(reader) => {
+                    let mut section = MemorySection::new();
+                    reencoder.parse_memory_section(&mut section, reader)?;
+                    self.append_memories(&mut section);
+                    module.section(&section);
+                }
+                Payload::GlobalSection(reader) => {
+                    let mut section = GlobalSection::new();
+                    reencoder.parse_global_section(&mut section, reader)?;
+                    self.append_globals(&mut section);
+                    module.section(&section);
+                }
+                Payload::ExportSection(reader) => {
+                    let mut section = ExportSection::new();
+                    reencoder.parse_export_section(&mut section, reader)?;
+                    self.append_exports(&mut section);
+                    module.section(&section);
+                }
+                Payload::CodeSectionStart { .. } => {
+                    let mut section = CodeSection::new();
+                    for body in Parser::new(0).parse_all(bytes) {
+                        if let Payload::CodeSectionEntry(body) = body? {
+                            section.raw(&bytes[body.range()]);
+                        }
+                    }
+                    self.append_code(&mut section);
+                    module.section(&section);
+                }
+                _ => {
+                    module.section(&RawSection {
+                        id,
+                        data: &bytes[range],
+                    });
+                }
+            }
+        }
+        self.emit_missing_sections(&mut module, &mut emitted, u8::MAX);
+
+        let instrumented = module.finish();
+        wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+            .validate_all(&instrumented)?;
+        Ok(instrumented)
+    }
+
+    // Adds the sections the module lacks, keeping the order required by the spec
+    fn emit_missing_sections(&self, module: &mut Module, emitted: &mut Vec<u8>, before: u8) {
+        let required = [
+            SectionId::Type,
+            SectionId::Function,
+            SectionId::Memory,
+            SectionId::Global,
+            SectionId::Export,
+            SectionId::Code,
+        ];
+        for id in required {
+            if section_order(id as u8) >= before || emitted.contains(&(id as u8)) {
+                continue;
+            }
+            emitted.push(id as u8);
+            match id {
+                SectionId::Type => {
+                    let mut section = TypeSection::new();
+                    self.append_types(&mut section);
+                    module.section(&section);
+                }
+                SectionId::Function => {
+                    let mut section = FunctionSection::new();
+                    self.append_functions(&mut section);
+                    module.section(&section);
+                }
+                SectionId::Memory => {
+                    let mut section = MemorySection::new();
+                    self.append_memories(&mut section);
+                    if !section.is_empty() {
+                        module.section(&section);
+                    }
+                }
+                SectionId::Global => {
+                    let mut section = GlobalSection::new();
+                    self.append_globals(&mut section);
+                    module.section(&section);
+                }
+                SectionId::Export => {
+                    let mut section = ExportSection::new();
+                    self.append_exports(&mut section);
+                    module.section(&section);
+                }
+                _ => {
+                    let mut section = CodeSection::new();
+                    self.append_code(&mut section);
+                    module.section(&section);
+                }
+            }
+        }
+    }
+
+    fn append_types(&self, section: &mut TypeSection) {
+        section.ty().function([], []);
+        if self.dump {
+            section.ty().function([], [ValType::I32]);
+            for (_, _, val_type) in self.globals.iter() {
+                section.ty().function([], [*val_type]);
+            }
+        }
+    }
+
+    fn append_functions(&self, section: &mut FunctionSection) {
+        section.function(self.types_base);
+        section.function(self.types_base);
+        if self.dump {
+            for _ in self.memories.iter() {
+                section.function(self.types_base + 1);
+            }
+            for idx in 0..self.globals.len() {
+                section.function(self.types_base + 2 + idx as u32);
+            }
+        }
+    }
+
+    fn append_memories(&self, section: &mut MemorySection) {
+        for _ in self.memories.iter() {
+            section.memory(MemoryType {
+                minimum: 0,
+                maximum: None,
+                memory64: false,
+                shared: false,
+                page_size_log2: None,
+            });
+        }
+    }
+
+    fn append_globals(&self, section: &mut GlobalSection) {
+        for _ in self.pages_globals.iter() {
+            section.global(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: true,
+                    shared: false,
+                },
+                &ConstExpr::i32_const(0),
+            );
+        }
+        for (_, _, val_type) in self.globals.iter() {
+            section.global(
+                GlobalType {
+                    val_type: *val_type,
+                    mutable: true,
+                    shared: false,
+                },
+                &zero_const(*val_type),
+            );
+        }
+    }
+
+    fn append_exports(&self, section: &mut ExportSection) {
+        section.export(CORE_SNAPSHOT_EXPORT, ExportKind::Func, self.snapshot_func());
+        section.export(CORE_RESTORE_EXPORT, ExportKind::Func, self.restore_func());
+        if self.dump {
+            for (idx, (_, shadow)) in self.memories.iter().enumerate() {
+                let memory = format!("{}{}", CORE_SHADOW_MEMORY_EXPORT, idx);
+                section.export(&memory, ExportKind::Memory, *shadow);
+                let func = format!("{}{}", CORE_DUMP_MEMORY_EXPORT, idx);
+                section.export(&func, ExportKind::Func, self.dump_memory_func(idx));
+            }
+            for (idx, (global, _, _)) in self.globals.iter().enumerate() {
+                let func = format!("{}{}", CORE_DUMP_GLOBAL_EXPORT, global);
+                section.export(&func, ExportKind::Func, self.dump_global_func(idx));
+            }
+        }
+    }
+
+    fn append_code(&self, section: &mut CodeSection) {
+        section.function(&self.snapshot_body());
+        section.function(&self.restore_body());
+        if self.dump {
+            for idx in 0..self.memories.len() {
+                section.function(&self.dump_memory_body(idx));
+            }
+            for (global, _, _) in self.globals.iter() {
+                let mut func = Function::new([]);
+                func.instruction(&Instruction::GlobalGet(*global))
+                    .instruction(&Instruction::End);
+                section.function(&func);
+            }
+        }
+    }
+
+    // Copies every memory into its shadow, growing the shadow to the same size, and every global
+    fn snapshot_body(&self) -> Function {
+        let mut func = Function::new([(1, ValType::I32)]);
+        for ((memory, shadow), pages) in self.memories.iter().zip(&self.pages_globals) {
+            func.instruction(&Instruction::MemorySize(*memory))
+                .instruction(&Instruction::GlobalSet(*pages))
+                .instruction(&Instruction::MemorySize(*memory))
+                .instruction(&Instruction::MemorySize(*shadow))
+                .instruction(&Instruction::I32Sub)
+                .instruction(&Instruction::LocalTee(0))
+                .instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::I32GtS)
+                .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
+                .instruction(&Instruction::LocalGet(0))
+                .instruction(&Instruction::MemoryGrow(*shadow))
+                .instruction(&Instruction::I32Const(-1))
+                .instruction(&Instruction::I32Eq)
+                .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
+                .instruction(&Instruction::Unreachable)
+                .instruction(&Instruction::End)
+                .instruction(&Instruction::End)
+                .instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::GlobalGet(*pages))
+                .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+                .instruction(&Instruction::I32Shl)
+                .instruction(&Instruction::MemoryCopy {
+                    src_mem: *memory,
+                    dst_mem: *shadow,
+                });
+        }
+        for (global, shadow, _) in self.globals.iter() {
+            func.instruction(&Instruction::GlobalGet(*global))
+                .instruction(&Instruction::GlobalSet(*shadow));
+        }
+        func.instruction(&Instruction::End);
+        func
+    }
+
+    // Copies the shadows back and zeroes the pages the guest grew after the snapshot
+    fn restore_body(&self) -> Function {
+        let mut func = Function::new([]);
+        for ((memory, shadow), pages) in self.memories.iter().zip(&self.pages_globals) {
+            func.instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::GlobalGet(*pages))
+                .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+                .instruction(&Instruction::I32Shl)
+                .instruction(&Instruction::MemoryCopy {
+                    src_mem: *shadow,
+                    dst_mem: *memory,
+                })
+                .instruction(&Instruction::GlobalGet(*pages))
+                .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+                .instruction(&Instruction::I32Shl)
+                .instruction(&Instruction::I32Const(0))
+                .instruction(&Instruction::MemorySize(*memory))
+                .instruction(&Instruction::GlobalGet(*pages))
+                .instruction(&Instruction::I32Sub)
+                .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+                .instruction(&Instruction::I32Shl)
+                .instruction(&Instruction::MemoryFill(*memory));
+        }
+        for (global, shadow, _) in self.globals.iter() {
+            func.instruction(&Instruction::GlobalGet(*shadow))
+                .instruction(&Instruction::GlobalSet(*global));
+        }
+        func.instruction(&Instruction::End);
+        func
+    }
+
+    // Snapshots the state, then returns a pointer to the (pointer, length) pair describing the
+    // copied memory. The pair lives in an extra page of the shadow so the copy is left untouched
+    fn dump_memory_body(&self, memory: usize) -> Function {
+        let (_, shadow) = self.memories[memory];
+        let pages = self.pages_globals[memory];
+        let store = |offset| {
+            Instruction::I32Store(MemArg {
+                offset,
+                align: 2,
+                memory_index: shadow,
+            })
+        };
+        let mut func = Function::new([]);
+        func.instruction(&Instruction::Call(self.snapshot_func()))
+            .instruction(&Instruction::I32Const(1))
+            .instruction(&Instruction::MemoryGrow(shadow))
+            .instruction(&Instruction::I32Const(-1))
+            .instruction(&Instruction::I32Eq)
+            .instruction(&Instruction::If(wasm_encoder::BlockType::Empty))
+            .instruction(&Instruction::Unreachable)
+            .instruction(&Instruction::End)
+            .instruction(&Instruction::GlobalGet(pages))
+            .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+            .instruction(&Instruction::I32Shl)
+            .instruction(&Instruction::I32Const(0))
+            .instruction(&store(0))
+            .instruction(&Instruction::GlobalGet(pages))
+            .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+            .instruction(&Instruction::I32Shl)
+            .instruction(&Instruction::GlobalGet(pages))
+            .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+            .instruction(&Instruction::I32Shl)
+            .instruction(&store(4))
+            .instruction(&Instruction::GlobalGet(pages))
+            .instruction(&Instruction::I32Const(PAGE_SIZE_LOG2))
+            .instruction(&Instruction::I32Shl)
+            .instruction(&Instruction::End);
+        func
+    }
+}
+
+fn zero_const(val_type: ValType) -> ConstExpr {
+    match val_type {
+        ValType::I32 => ConstExpr::i32_const(0),
+        ValType::I64 => ConstExpr::i64_const(0),
+        ValType::F32 => ConstExpr::f32_const(0.0),
+        ValType::F64 => ConstExpr::f64_const(0.0),
+        ValType::V128 => ConstExpr::v128_const(0),
+        ValType::Ref(ref_type) => ConstExpr::ref_null(ref_type.heap_type),
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid value `{0}`")]
+pub struct InvalidUnit(pub String);
+
+/// Parses a size such as `64M`, `512MiB`, `1.5GB` or `1048576`. Decimal and binary units
+/// are both read as powers of 1024, like the memory of the command line always was
+pub fn parse_bytes(value: &str) -> Result<usize, InvalidUnit> {
+    let invalid = || InvalidUnit(value.to_string());
+    let (number, unit) = split_unit(value).ok_or_else(invalid)?;
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let bytes = number * multiplier as f64;
+    if !bytes.is_finite() || bytes < 0.0 || bytes > usize::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as usize)
+}
+
+/// Parses a duration such as `500ms`, `30s`, `5m`, `1h` or `1.5s`, a bare number is in seconds
+pub fn parse_duration(value: &str) -> Result<Duration, InvalidUnit> {
+    let invalid = || InvalidUnit(value.to_string());
+    let (number, unit) = split_unit(value).ok_or_else(invalid)?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Formats a size with the largest binary unit keeping it at least 1, e.g. `1.5KiB` or `64MiB`
+pub fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match value.fract() == 0.0 {
+        true => format!("{}{}", value, units[unit]),
+        false => format!("{:.1}{}", value, units[unit]),
+    }
+}
+
+// The leading number and the unit after it
+fn split_unit(value: &str) -> Option<(f64, &str)> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    Some((number.parse().ok()?, unit.trim()))
+}
+
+// Sizes and durations are accepted as strings with units or as plain numbers
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u64),
+    Float(f64),
+    String(String),
+}
+
+pub(crate) fn deserialize_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(bytes) => Ok(bytes as usize),
+        NumberOrString::Float(bytes) => parse_bytes(&bytes.to_string()),
+        NumberOrString::String(bytes) => parse_bytes(&bytes),
+    }
+    .map_err(serde::de::Error::custom)
+}
+
+pub(crate) fn deserialize_bytes_opt<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    deserialize_bytes(deserializer).map(Some)
+}
+
+pub(crate) fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(secs) => Ok(Duration::from_secs(secs)),
+        NumberOrString::Float(secs) => parse_duration(&secs.to_string()),
+        NumberOrString::String(duration) => parse_duration(&duration),
+    }
+    .map_err(serde::de::Error::custom)
+}
+
+pub(crate) fn deserialize_duration_opt<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
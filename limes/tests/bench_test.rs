@@ -0,0 +1,113 @@
+use limes::tools::bench::{self, BenchApi, Percentiles, Scenario};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+fn write_scenario(name: &str, api: &str) -> PathBuf {
+    let module = get_crate_path().join("exec_rust_lambda_function.wasm");
+    let scenario = format!(
+        r#"api = "{api}"
+
+[engine]
+opt_level = "speed"
+consume_fuel = true
+
+[[benchmark]]
+name = "exec"
+module = "{}"
+memory = 4194304
+iterations = 5
+warmup = 1
+concurrency = 2
+"#,
+        module.display()
+    );
+    let path = std::env::temp_dir().join(format!("limes_{}_{}.toml", name, std::process::id()));
+    std::fs::write(&path, scenario).unwrap();
+    path
+}
+
+#[test]
+fn bench_percentiles() {
+    let values: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+    let percentiles = Percentiles::from_durations(&values);
+    assert_eq!(percentiles.count, 100);
+    assert_eq!(percentiles.min, 1.0);
+    assert_eq!(percentiles.p50, 50.0);
+    assert_eq!(percentiles.p90, 90.0);
+    assert_eq!(percentiles.p99, 99.0);
+    assert_eq!(percentiles.max, 100.0);
+    assert_eq!(percentiles.mean, 50.5);
+    assert_eq!(Percentiles::from_durations(&[]), Percentiles::default());
+}
+
+#[test]
+fn bench_scenario_validation() {
+    let path = std::env::temp_dir().join(format!("limes_invalid_{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"api = "runtime"
+[[benchmark]]
+name = "io"
+module = "io.wasm"
+preopens = [{ host = "images", guest = "./" }]
+"#,
+    )
+    .unwrap();
+    assert!(Scenario::from_file(&path).is_err());
+
+    std::fs::write(
+        &path,
+        "[[benchmark]]\nname = \"typo\"\nmodul = \"x.wasm\"\n",
+    )
+    .unwrap();
+    assert!(Scenario::from_file(&path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn bench_lambda_scenario() {
+    let path = write_scenario("lambda", "lambda");
+    let scenario = Scenario::from_file(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(scenario.api, BenchApi::Lambda);
+
+    let reports = bench::run_scenario(&scenario).await.unwrap();
+    let report = &reports[0];
+    assert_eq!(report.samples.len(), 5);
+    assert_eq!(report.summary.errors, 0);
+    assert!(report.samples.iter().all(|sample| sample.compile.is_some()));
+    assert!(report.samples.iter().all(|s| s.usage.fuel_used.is_some()));
+    assert_eq!(report.summary.compile.count, 5);
+    assert_eq!(report.summary.execute.count, 5);
+
+    let csv = bench::samples_csv(&reports);
+    assert_eq!(csv.lines().count(), 6);
+    assert!(csv.lines().nth(1).unwrap().starts_with("exec,lambda,0,"));
+    assert_eq!(bench::summary_csv(&reports).lines().count(), 4);
+}
+
+#[tokio::test]
+async fn bench_runtime_scenario() {
+    let path = write_scenario("runtime", "runtime");
+    let scenario = Scenario::from_file(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let reports = bench::run_scenario(&scenario).await.unwrap();
+    let report = &reports[0];
+    assert_eq!(report.api, BenchApi::Runtime);
+    assert_eq!(report.samples.len(), 5);
+    assert_eq!(report.summary.errors, 0);
+    // The module is registered once
+    assert_eq!(report.summary.compile.count, 1);
+    assert_eq!(report.summary.execute.count, 5);
+
+    let json = serde_json::to_value(&reports).unwrap();
+    assert!(json[0]["summary"]["execute"]["p99"].is_f64());
+    assert!(json[0]["samples"][0]["compile_us"].is_u64());
+    assert!(json[0]["samples"][1]["compile_us"].is_null());
+}
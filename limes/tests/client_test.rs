@@ -0,0 +1,171 @@
+use limes::runtime::logs::LogStream;
+use limes::runtime::runtime::{FunctionHandlerStatus, Runtime};
+use limes::server::api::{self, ApiOptions};
+use limes::server::client::{ClientError, LimesClient};
+use std::future::IntoFuture;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+// Serves a fresh runtime on a random port, returns its url
+async fn start_server(options: ApiOptions) -> String {
+    let runtime = Arc::new(Runtime::default());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = api::router_with_options(runtime, options);
+    tokio::spawn(axum::serve(listener, router).into_future());
+    url
+}
+
+fn read_module(name: &str) -> Vec<u8> {
+    std::fs::read(get_crate_path().join(name)).unwrap()
+}
+
+#[tokio::test]
+async fn client_module_and_function_lifecycle() {
+    let client = LimesClient::new(&start_server(ApiOptions::default()).await);
+    let bytes = read_module("exec_rust_lambda_function.wasm");
+    let module_id = client.push_module(bytes.clone()).await.unwrap();
+
+    let modules = client.list_modules().await.unwrap();
+    assert_eq!(modules.len(), 1);
+    assert_eq!(modules[0].module_id, module_id);
+    assert_eq!(modules[0].size_bytes, bytes.len());
+    assert_eq!(modules[0].functions, 0);
+
+    let function_id = client.init_function(&module_id, None).await.unwrap();
+    let functions = client.list_functions().await.unwrap();
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0].function_id, function_id);
+    assert_eq!(functions[0].status, FunctionHandlerStatus::Ready);
+    assert_eq!(client.list_modules().await.unwrap()[0].functions, 1);
+
+    let output = client.exec(&function_id, "Hello").await.unwrap();
+    assert_eq!(output.result, "Hello### TEST ###");
+    assert!(output.usage.get("execute_us").is_some());
+
+    client.stop_function(&function_id).await.unwrap();
+    let info = client.function_info(&function_id).await.unwrap();
+    assert_eq!(info.status, FunctionHandlerStatus::Stopped);
+    assert_eq!(info.running, 0);
+
+    client.remove_function(&function_id).await.unwrap();
+    assert!(client.list_functions().await.unwrap().is_empty());
+    assert!(matches!(
+        client.function_info(&function_id).await,
+        Err(ClientError::Server(404, _))
+    ));
+    client.remove_module(&module_id).await.unwrap();
+    assert!(client.list_modules().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn client_reads_function_logs() {
+    let client = LimesClient::new(&start_server(ApiOptions::default()).await);
+    let module_id = client
+        .push_module(read_module("tcp_udp_bind_to_not_allowed_ip.wasm"))
+        .await
+        .unwrap();
+    let function_id = client.init_function(&module_id, None).await.unwrap();
+    assert!(client
+        .logs(&function_id, None, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The guest fails on a refused bind, its error and panic message go to stderr
+    for _ in 0..2 {
+        let result = client.exec(&function_id, "TCP,10.0.0.1:8080").await;
+        assert!(matches!(result, Err(ClientError::Server(422, _))));
+    }
+    let logs = client.logs(&function_id, None, None).await.unwrap();
+    assert!(logs
+        .iter()
+        .any(|line| line.stream == LogStream::Stderr && line.line.contains("panicked")));
+    assert!(logs.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+
+    let tail = client.logs(&function_id, None, Some(1)).await.unwrap();
+    assert_eq!(tail.len(), 1);
+    assert_eq!(tail[0].seq, logs.last().unwrap().seq);
+    let after = client
+        .logs(&function_id, Some(logs.last().unwrap().seq), None)
+        .await
+        .unwrap();
+    assert!(after.is_empty());
+}
+
+#[tokio::test]
+async fn client_sends_the_token() {
+    let url = start_server(ApiOptions {
+        tokens: vec!["secret".to_string()],
+        ..ApiOptions::default()
+    })
+    .await;
+    let mut client = LimesClient::new(&url);
+    assert!(matches!(
+        client.list_modules().await,
+        Err(ClientError::Server(401, _))
+    ));
+    client.set_token(Some("secret".to_string()));
+    assert!(client.list_modules().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn cli_pipes_stdin_and_writes_the_result() {
+    let url = start_server(ApiOptions::default()).await;
+    let run = |args: &[&str]| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_limes"));
+        command
+            .args(args)
+            .env("LIMES_SERVER", &url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    };
+    let module_path = get_crate_path().join("exec_rust_lambda_function.wasm");
+    let output = run(&["module", "push", module_path.to_str().unwrap()])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success());
+    let module_id = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+    let output = run(&["fn", "init", &module_id, "--format", "json"])
+        .output()
+        .await
+        .unwrap();
+    let answer: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let function_id = answer["function_id"].as_str().unwrap().to_string();
+
+    let out = std::env::temp_dir().join(format!("limes_cli_{}.txt", function_id));
+    let mut child = run(&["fn", "exec", &function_id, "-o", out.to_str().unwrap()])
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"from stdin").await.unwrap();
+    drop(stdin);
+    assert!(child.wait_with_output().await.unwrap().status.success());
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "from stdin### TEST ###"
+    );
+    let _ = std::fs::remove_file(&out);
+
+    let output = run(&["fn", "ls", "--format", "json"])
+        .output()
+        .await
+        .unwrap();
+    let functions: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(functions[0]["function_id"], function_id.as_str());
+    assert_eq!(functions[0]["status"], "ready");
+
+    let output = run(&["fn", "status", "missing"]).output().await.unwrap();
+    assert!(!output.status.success());
+}
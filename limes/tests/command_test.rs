@@ -0,0 +1,227 @@
+use limes::runtime::lambda::{self, InstanceMode, Lambda, WarmPoolConfig};
+use limes::runtime::lambda_error::LambdaError;
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::{RegisterOptions, Runtime};
+use limes::runtime::runtime_error::RuntimeError;
+use limes::tools::inspect::EntryKind;
+use limes::tools::loader;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime::Module;
+
+fn get_echo_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/command/echo.wat")
+}
+
+fn echo_bytes() -> Vec<u8> {
+    wat::parse_file(get_echo_path()).unwrap()
+}
+
+async fn echo_lambda(wasi_flags: lambda::WasiFlags) -> Lambda {
+    let engine = loader::build_engine(true, true).await.unwrap();
+    let module = Arc::new(Module::from_binary(&engine, &echo_bytes()).unwrap());
+    Lambda::from_command(module, 1024 * 1024 * 2, Ipv4Addr::LOCALHOST, wasi_flags)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn command_runs_core_modules() {
+    let runtime = Runtime::default();
+    let report = runtime.validate_module(&echo_bytes(), &[]);
+    assert!(report.is_valid(), "{:?}", report.errors);
+    assert!(report.runnable);
+    let fd_read = report
+        .imports
+        .iter()
+        .find(|import| import.name == "wasi_snapshot_preview1/fd_read")
+        .unwrap();
+    assert_eq!(fd_read.kind, EntryKind::CoreFunc);
+    assert_eq!(
+        fd_read.functions["fd_read"],
+        "func(i32, i32, i32, i32) -> i32"
+    );
+
+    // The args are read on stdin, the result is what the command wrote on stdout
+    let module_id = runtime.register_module(echo_bytes()).await.unwrap();
+    let function_id = runtime
+        .init_function(module_id.clone(), Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    let result = runtime
+        .exec_function(function_id.clone(), "world")
+        .await
+        .unwrap();
+    assert_eq!(result, "hello world");
+    assert_eq!(
+        runtime.exec_function(function_id, "exit").await,
+        Err(RuntimeError::FunctionExecError(
+            LambdaError::CommandExit(3).to_string()
+        ))
+    );
+
+    // The command line comes from the manifest
+    let manifest =
+        FunctionManifest::from_toml("module = \"echo.wasm\"\nargs = [\"echo\", \"-v\"]").unwrap();
+    let function_id = runtime
+        .init_function_with_manifest(module_id, &manifest)
+        .await
+        .unwrap();
+    assert_eq!(
+        runtime.exec_function(function_id, "argc").await.unwrap(),
+        "2"
+    );
+}
+
+#[tokio::test]
+async fn command_refusals() {
+    let runtime = Runtime::default();
+    let library = wat::parse_str("(module (func (export \"run\")))").unwrap();
+    let report = runtime.validate_module(&library, &[]);
+    assert_eq!(
+        report.errors,
+        vec!["the core module does not export `_start`"]
+    );
+    assert!(!report.runnable);
+
+    let unknown = wat::parse_str(
+        "(module (import \"env\" \"host\" (func)) (func (export \"_start\") call 0))",
+    )
+    .unwrap();
+    let registered = runtime.register_module(unknown).await;
+    assert!(matches!(registered, Err(RuntimeError::ModuleValidationError(e)) if e.contains("env")));
+
+    // Core modules neither import nor provide libraries, nor run on warm instances
+    let module_id = runtime.register_module(echo_bytes()).await.unwrap();
+    let dependent = runtime
+        .register_module_with_dependencies(echo_bytes(), vec![module_id.clone()])
+        .await;
+    assert!(matches!(dependent, Err(RuntimeError::DependencyError(_))));
+    let warm = runtime
+        .init_function_with_mode(
+            module_id,
+            Ipv4Addr::LOCALHOST,
+            InstanceMode::Warm(WarmPoolConfig::default()),
+        )
+        .await;
+    assert!(matches!(warm, Err(RuntimeError::FunctionInitError(_))));
+
+    // Neither can a manifest deploying one ask for them
+    for setting in ["preinit = \"init\"", "[warm_pool]\nsize = 1"] {
+        let manifest =
+            FunctionManifest::from_toml(&format!("module = \"echo.wasm\"\n{}", setting)).unwrap();
+        assert!(matches!(
+            runtime.deploy(&manifest, echo_bytes()).await,
+            Err(RuntimeError::ManifestError(e)) if e.ends_with("not supported by a core module")
+        ));
+    }
+}
+
+fn compiled_bytes(name: &str) -> Vec<u8> {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let path = Path::new(&crate_dir)
+        .join("resources/wasm_wasi_module_test_files/wasm_compiled")
+        .join(name);
+    std::fs::read(path).unwrap()
+}
+
+// `#[limes_guest::function]` built for wasm32-wasip1, see wasip1_function
+fn wasip1_bytes() -> Vec<u8> {
+    compiled_bytes("wasip1_function.wasm")
+}
+
+#[tokio::test]
+async fn command_adapts_wasip1_modules() {
+    let runtime = Runtime::default();
+    let adapt = RegisterOptions {
+        adapt_wasip1: true,
+        ..RegisterOptions::default()
+    };
+
+    // As is, the module is a core module without `_start`
+    let registered = runtime.register_module(wasip1_bytes()).await;
+    assert_eq!(
+        registered,
+        Err(RuntimeError::ModuleValidationError(
+            "the core module does not export `_start`".to_string()
+        ))
+    );
+
+    // Adapted, it is a component exporting `component:run/run` and runs on warm instances
+    let module_id = runtime
+        .register_module_with_options(wasip1_bytes(), adapt.clone())
+        .await
+        .unwrap();
+    let report = runtime.inspect_module(&module_id).unwrap();
+    assert!(report.runnable);
+    assert!(report
+        .imports
+        .iter()
+        .all(|import| !import.name.starts_with("wasi_snapshot_preview1")));
+    let function_id = runtime
+        .init_function_with_mode(
+            module_id,
+            Ipv4Addr::LOCALHOST,
+            InstanceMode::Warm(WarmPoolConfig::default()),
+        )
+        .await
+        .unwrap();
+    let result = runtime.exec_function(function_id.clone(), "module").await;
+    assert_eq!(result.unwrap(), "wasip1 module");
+    let logs = runtime
+        .function_logs(function_id, None, None)
+        .await
+        .unwrap();
+    assert_eq!(logs[0].line, "adapted");
+
+    // Through a manifest, which may then ask for a warm pool
+    let manifest = FunctionManifest::from_toml(
+        "module = \"fn.wasm\"\nadapt_wasip1 = true\n[warm_pool]\nsize = 1",
+    )
+    .unwrap();
+    let deployment = runtime.deploy(&manifest, wasip1_bytes()).await.unwrap();
+    let result = runtime
+        .exec_function(deployment.function_id, "manifest")
+        .await;
+    assert_eq!(result.unwrap(), "wasip1 manifest");
+
+    // Only core modules are adapted
+    let component = compiled_bytes("exec_rust_lambda_function.wasm");
+    assert!(matches!(
+        runtime.register_module_with_options(component, adapt.clone()).await,
+        Err(RuntimeError::ModuleAdaptError(e)) if e.contains("this is a component")
+    ));
+
+    // and only those carrying the world wit-bindgen embeds
+    let legacy = wat::parse_str(
+        r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (memory (export "memory") 1)
+            (func (export "_start")))"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        runtime.register_module_with_options(legacy, adapt).await,
+        Err(RuntimeError::ModuleAdaptError(e)) if e.contains("no `component-type` section")
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn command_limits() {
+    let mut lambda = echo_lambda(lambda::WasiFlags::default()).await;
+    assert_eq!(lambda.run("grow").await, Err(LambdaError::CommandExit(4)));
+    assert_eq!(lambda.run("argc").await.unwrap(), "0");
+
+    lambda.set_timeout(Some(Duration::from_millis(200)));
+    assert_eq!(lambda.run("spin").await, Err(LambdaError::Timeout));
+
+    let lambda = Arc::new(echo_lambda(lambda::WasiFlags::default()).await);
+    let running = lambda.clone();
+    let handle = tokio::spawn(async move { running.run("spin").await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    lambda.stop().await.unwrap();
+    assert_eq!(handle.await.unwrap(), Err(LambdaError::ForceStop));
+}
@@ -0,0 +1,141 @@
+use limes::runtime::lambda::{InstanceMode, WarmPoolConfig};
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+fn read_wat(name: &str) -> Vec<u8> {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let path = Path::new(&crate_dir)
+        .join("resources/wasm_wasi_module_test_files/composition")
+        .join(name);
+    wat::parse_file(path).unwrap()
+}
+
+// `app` imports `shout`, which imports `codec`
+async fn register_libraries(runtime: &Runtime) -> (String, String) {
+    let codec = runtime
+        .register_module(read_wat("codec.wat"))
+        .await
+        .unwrap();
+    let shout = runtime
+        .register_module_with_dependencies(read_wat("shout.wat"), vec![codec.clone()])
+        .await
+        .unwrap();
+    (codec, shout)
+}
+
+#[tokio::test]
+async fn composition_links_the_dependencies() {
+    let runtime = Runtime::default();
+    let (codec, shout) = register_libraries(&runtime).await;
+
+    // The dependencies of `shout` come along
+    let app = runtime
+        .register_module_with_dependencies(read_wat("app.wat"), vec![shout.clone()])
+        .await
+        .unwrap();
+    let function_id = runtime
+        .init_function(app.clone(), Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    assert_eq!(
+        runtime.exec_function(function_id, "limes").await.unwrap(),
+        "LIMES!"
+    );
+
+    let warm = runtime
+        .init_function_with_mode(
+            app.clone(),
+            Ipv4Addr::LOCALHOST,
+            InstanceMode::Warm(WarmPoolConfig::default()),
+        )
+        .await
+        .unwrap();
+    for args in ["one", "two"] {
+        assert_eq!(
+            runtime.exec_function(warm.clone(), args).await.unwrap(),
+            format!("{}!", args.to_uppercase())
+        );
+    }
+
+    let modules = runtime.list_modules().await;
+    let app_info = modules.iter().find(|m| m.module_id == app).unwrap();
+    assert_eq!(app_info.dependencies, vec![shout.clone()]);
+
+    // A module is only removed once nothing depends on it
+    assert_eq!(
+        runtime.remove_module(codec.clone()).await,
+        Err(RuntimeError::ModuleInUse(shout.clone()))
+    );
+    runtime.remove_module(app).await.unwrap();
+    runtime.remove_module(shout).await.unwrap();
+    runtime.remove_module(codec).await.unwrap();
+}
+
+#[tokio::test]
+async fn composition_refuses_unresolved_imports() {
+    let runtime = Runtime::default();
+    let (codec, _) = register_libraries(&runtime).await;
+
+    let missing = runtime
+        .register_module_with_dependencies(read_wat("app.wat"), vec![codec.clone()])
+        .await;
+    assert_eq!(
+        missing,
+        Err(RuntimeError::DependencyError(
+            "no dependency exports `test:codec/shout`".to_string()
+        ))
+    );
+
+    let other_codec = runtime
+        .register_module(read_wat("codec.wat"))
+        .await
+        .unwrap();
+    let ambiguous = runtime
+        .register_module_with_dependencies(read_wat("shout.wat"), vec![codec, other_codec])
+        .await;
+    assert_eq!(
+        ambiguous,
+        Err(RuntimeError::DependencyError(
+            "`test:codec/codec` is exported by more than one dependency".to_string()
+        ))
+    );
+
+    let unknown = runtime
+        .register_module_with_dependencies(read_wat("shout.wat"), vec!["unknown".to_string()])
+        .await;
+    assert_eq!(unknown, Err(RuntimeError::ModuleNotRegistered));
+
+    let manifest = "module = \"m.wasm\"\npreinit = \"init\"\ndependencies = [\"codec\"]";
+    assert!(FunctionManifest::from_toml(manifest).is_err());
+}
+
+#[tokio::test]
+async fn composition_across_engines() {
+    let runtime = Runtime::new().set_cpus(2).build().unwrap();
+    let (_, shout) = register_libraries(&runtime).await;
+    // Registered by the other engine, it exports nothing the others import
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let unused =
+        Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/guest_log/guest_log.wat");
+    let unused = runtime
+        .register_module(wat::parse_file(unused).unwrap())
+        .await
+        .unwrap();
+
+    // Built by the engine of `unused`, `shout` and `codec` are compiled again for it
+    let app = runtime
+        .register_module_with_dependencies(read_wat("app.wat"), vec![unused, shout])
+        .await
+        .unwrap();
+    let function_id = runtime
+        .init_function(app, Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    assert_eq!(
+        runtime.exec_function(function_id, "abc").await.unwrap(),
+        "ABC!"
+    );
+}
@@ -0,0 +1,221 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use limes::runtime::lambda::OutboundRule;
+use limes::server::api::{self, ApiOptions};
+use limes::server::config::{ConfigError, ServerConfig};
+use limes::tools::units;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).to_path_buf()
+}
+
+fn write_config(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("limes_config_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn config_units() {
+    assert_eq!(units::parse_bytes("1048576").unwrap(), 1048576);
+    assert_eq!(units::parse_bytes("64M").unwrap(), 64 * 1024 * 1024);
+    assert_eq!(units::parse_bytes("512MiB").unwrap(), 512 * 1024 * 1024);
+    assert_eq!(units::parse_bytes("1.5 GB").unwrap(), 3 * 512 * 1024 * 1024);
+    assert_eq!(units::parse_bytes("4kb").unwrap(), 4096);
+    assert!(units::parse_bytes("12 parsecs").is_err());
+    assert!(units::parse_bytes("MiB").is_err());
+
+    assert_eq!(
+        units::parse_duration("500ms").unwrap(),
+        Duration::from_millis(500)
+    );
+    assert_eq!(units::parse_duration("5s").unwrap(), Duration::from_secs(5));
+    assert_eq!(units::parse_duration("2").unwrap(), Duration::from_secs(2));
+    assert_eq!(
+        units::parse_duration("1.5m").unwrap(),
+        Duration::from_secs(90)
+    );
+    assert_eq!(
+        units::parse_duration("1h").unwrap(),
+        Duration::from_secs(3600)
+    );
+    assert!(units::parse_duration("3 fortnights").is_err());
+}
+
+#[test]
+fn config_toml_and_yaml() {
+    let config = ServerConfig::from_str(
+        r#"
+[server]
+address = "0.0.0.0"
+port = 9000
+max_upload_size = "8MiB"
+
+[runtime]
+cpus = 2
+memory = "1GiB"
+max_functions = 10
+invocation_retention = "10m"
+preinit_timeout = "2s"
+
+[engine]
+opt_level = "speed"
+consume_fuel = true
+
+[webhook]
+initial_backoff = "1s"
+max_backoff = 60
+
+[auth]
+tokens = ["secret"]
+"#,
+        "toml",
+    )
+    .unwrap();
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.max_upload_size, 8 << 20);
+    assert_eq!(config.runtime.cpus, 2);
+    assert_eq!(config.runtime.memory, 1 << 30);
+    assert_eq!(
+        config.runtime.invocation_retention,
+        Some(Duration::from_secs(600))
+    );
+    assert_eq!(config.runtime.preinit_timeout, Some(Duration::from_secs(2)));
+    assert!(config.engine.consume_fuel);
+    assert_eq!(config.webhook.max_backoff, Duration::from_secs(60));
+    assert_eq!(config.webhook.max_retries, 5);
+    assert_eq!(config.network.default_tap_ip, Ipv4Addr::LOCALHOST);
+    assert!(config.validate().is_ok());
+
+    let config = ServerConfig::from_str(
+        "runtime:\n  memory: 64M\n  max_functions: 4\nnetwork:\n  default_tap_ip: 10.0.0.2\n",
+        "yaml",
+    )
+    .unwrap();
+    assert_eq!(config.runtime.memory, 64 << 20);
+    assert_eq!(config.runtime.cpus, 1);
+    assert_eq!(config.network.default_tap_ip, Ipv4Addr::new(10, 0, 0, 2));
+
+    let config = ServerConfig::from_str(
+        "[network]\ndefault_outbound = [\"10.0.0.1:5432\", \"192.168.1.1\"]\n",
+        "toml",
+    )
+    .unwrap();
+    let rules = config.network.outbound_rules().unwrap().unwrap();
+    assert_eq!(rules[0], "10.0.0.1:5432".parse::<OutboundRule>().unwrap());
+    assert_eq!(rules[1].port, None);
+    assert!(config.runtime_builder().build().is_ok());
+
+    assert!(matches!(
+        ServerConfig::from_str("[runtime]\nmemroy = 1\n", "toml"),
+        Err(ConfigError::Parse(_))
+    ));
+    assert!(matches!(
+        ServerConfig::from_str("[runtime]\nmemory = \"lots\"\n", "toml"),
+        Err(ConfigError::Parse(_))
+    ));
+    assert_eq!(
+        ServerConfig::from_str("", "ini").unwrap_err(),
+        ConfigError::UnsupportedFormat("ini".to_string())
+    );
+}
+
+#[test]
+fn config_validation() {
+    let invalid = |text: &str| {
+        matches!(
+            ServerConfig::from_str(text, "toml").unwrap().validate(),
+            Err(ConfigError::Invalid(_))
+        )
+    };
+    assert!(invalid("[runtime]\ncpus = 0\n"));
+    assert!(invalid("[runtime]\nmax_functions = 0\n"));
+    assert!(invalid(
+        "[webhook]\ninitial_backoff = \"1m\"\nmax_backoff = \"1s\"\n"
+    ));
+    assert!(invalid("[auth]\ntokens = [\"\"]\n"));
+    assert!(invalid("[network]\ndefault_outbound = [\"nowhere\"]\n"));
+    assert!(invalid("[[module]]\npath = \"missing.wasm\"\n"));
+
+    let module = get_crate_path()
+        .join("resources/wasm_wasi_module_test_files/wasm_compiled/exec_rust_lambda_function.wasm");
+    let text = format!(
+        "[runtime]\nmax_functions = 2\n\n[[module]]\npath = \"{}\"\nfunctions = 3\n",
+        module.display()
+    );
+    assert!(invalid(&text));
+
+    // The example shipped with the repository is valid
+    let example = ServerConfig::from_file(&get_crate_path().join("resources/config/limes.toml"));
+    assert!(example.unwrap().validate().is_ok());
+}
+
+#[tokio::test]
+async fn config_preload_relative_modules() {
+    let module = get_crate_path()
+        .join("resources/wasm_wasi_module_test_files/wasm_compiled/exec_rust_lambda_function.wasm");
+    let path = write_config(
+        "preload.yaml",
+        "runtime:\n  max_functions: 3\nmodules:\n  - path: echo.wasm\n    functions: 2\n    warm_pool:\n      size: 2\n",
+    );
+    std::fs::copy(&module, path.parent().unwrap().join("echo.wasm")).unwrap();
+
+    let config = ServerConfig::from_file(&path).unwrap();
+    assert!(config.validate().is_ok());
+    let runtime = config.runtime_builder().build().unwrap();
+    let preloaded = config.preload(&runtime).await.unwrap();
+    assert_eq!(preloaded.len(), 1);
+    assert_eq!(preloaded[0].function_ids.len(), 2);
+    for func_id in preloaded[0].function_ids.iter() {
+        let result = runtime.exec_function(func_id.clone(), "").await.unwrap();
+        assert_eq!(result, "### TEST ###");
+    }
+
+    // A preinit export the module does not have fails the preload
+    let path = write_config(
+        "preinit.toml",
+        "[[module]]\npath = \"echo.wasm\"\npreinit = \"missing\"\n",
+    );
+    let config = ServerConfig::from_file(&path).unwrap();
+    let runtime = config.runtime_builder().build().unwrap();
+    let result = config.preload(&runtime).await;
+    assert!(matches!(result, Err(ConfigError::Preload(_, _))));
+}
+
+#[tokio::test]
+async fn config_api_tokens() {
+    let runtime = Arc::new(ServerConfig::default().runtime_builder().build().unwrap());
+    let options = ApiOptions {
+        default_tap_ip: Ipv4Addr::LOCALHOST,
+        tokens: vec!["secret".to_string()],
+        allow_mounts: false,
+        max_upload_size: api::DEFAULT_MAX_UPLOAD_SIZE,
+    };
+    let router = api::router_with_options(runtime, options);
+    let request = |token: Option<&str>| {
+        let mut builder = Request::builder().uri("/metrics");
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    };
+
+    let response = router.clone().oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = router
+        .clone()
+        .oneshot(request(Some("wrong")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = router.oneshot(request(Some("secret"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
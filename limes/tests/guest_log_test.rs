@@ -0,0 +1,211 @@
+use limes::runtime::guest_log::GUEST_LOG_TARGET;
+use limes::runtime::lambda::{self, Lambda};
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::tools::loader;
+use log::{Level, Log, Metadata, Record};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+static LINES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+// Keeps the guest lines, the tests pick theirs by function id
+struct Collector;
+
+impl Log for Collector {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == GUEST_LOG_TARGET
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = record.args().to_string();
+            LINES.lock().unwrap().push((record.level(), line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn install_collector() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_boxed_logger(Box::new(Collector)).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+}
+
+fn lines_of(tag: &str) -> Vec<(Level, String)> {
+    LINES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, line)| line.contains(tag))
+        .cloned()
+        .collect()
+}
+
+fn get_guest_log_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/guest_log/guest_log.wat")
+}
+
+async fn deploy(runtime: &Runtime, log: &str) -> String {
+    let manifest =
+        FunctionManifest::from_toml(&format!("module = \"guest_log.wasm\"\n[log]\n{}", log))
+            .unwrap();
+    let module_id = runtime
+        .register_module(wat::parse_file(get_guest_log_path()).unwrap())
+        .await
+        .unwrap();
+    runtime
+        .init_function_with_manifest(module_id, &manifest)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn guest_log_lines_are_tagged() {
+    install_collector();
+    let runtime = Runtime::default();
+    let function_id = deploy(&runtime, "level = \"debug\"").await;
+    let invocation_id = runtime
+        .submit_function(function_id.clone(), "tagged".to_string(), None)
+        .await
+        .unwrap();
+    for _ in 0..100 {
+        if runtime
+            .invocation_result(invocation_id.clone())
+            .await
+            .is_ok()
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        runtime
+            .invocation_result(invocation_id.clone())
+            .await
+            .unwrap(),
+        "ok"
+    );
+
+    let tags = format!("function={} invocation={}", function_id, invocation_id);
+    let lines = lines_of(&format!("function={} ", function_id));
+    assert_eq!(lines.len(), 5);
+    assert!(lines.iter().all(|(_, line)| line.starts_with(&tags)));
+    assert_eq!(
+        lines[0],
+        (Level::Info, format!("{} call args=\"tagged\"", tags))
+    );
+    assert_eq!(lines[3], (Level::Debug, format!("{} detail", tags)));
+    assert_eq!(lines[4], (Level::Warn, format!("{} done", tags)));
+}
+
+#[tokio::test]
+async fn guest_log_lines_are_limited_per_invocation() {
+    install_collector();
+    let runtime = Runtime::default();
+    let function_id = deploy(&runtime, "max_lines = 2").await;
+    let tag = format!("function={} ", function_id);
+
+    // The debug line is filtered, the third info and the warn are dropped
+    assert_eq!(
+        runtime
+            .exec_function(function_id.clone(), "a")
+            .await
+            .unwrap(),
+        "ok"
+    );
+    let lines = lines_of(&tag);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[..2].iter().all(|(level, _)| *level == Level::Info));
+    assert_eq!(lines[2].0, Level::Warn);
+    assert!(lines[2]
+        .1
+        .ends_with("reached 2 lines, the next ones are dropped"));
+
+    // The limit starts over on the next invocation
+    runtime.exec_function(function_id, "b").await.unwrap();
+    assert_eq!(lines_of(&tag).len(), 6);
+
+    assert!(FunctionManifest::from_toml("module = \"m.wasm\"\n[log]\nmax_lines = 0").is_err());
+}
+
+#[tokio::test]
+async fn guest_log_level_filter() {
+    install_collector();
+    let runtime = Runtime::default();
+    let warn = deploy(&runtime, "level = \"warn\"").await;
+    let off = deploy(&runtime, "level = \"off\"").await;
+    runtime.exec_function(warn.clone(), "c").await.unwrap();
+    runtime.exec_function(off.clone(), "c").await.unwrap();
+
+    let lines = lines_of(&format!("function={} ", warn));
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].0, Level::Warn);
+    assert!(lines_of(&format!("function={} ", off)).is_empty());
+}
+
+// limes_guest::log goes through the same interface, see guest_sdk
+#[tokio::test]
+async fn guest_log_through_the_sdk() {
+    install_collector();
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let path = Path::new(&crate_dir)
+        .join("resources/wasm_wasi_module_test_files/wasm_compiled/guest_sdk.wasm");
+    let runtime = Runtime::default();
+    let module_id = runtime
+        .register_module(std::fs::read(path).unwrap())
+        .await
+        .unwrap();
+    let manifest =
+        FunctionManifest::from_toml("module = \"guest_sdk.wasm\"\n[log]\nlevel = \"debug\"")
+            .unwrap();
+    let function_id = runtime
+        .init_function_with_manifest(module_id, &manifest)
+        .await
+        .unwrap();
+    let result = runtime.exec_function(function_id.clone(), "log,hi").await;
+    assert_eq!(result.unwrap(), "logged");
+
+    let lines = lines_of(&format!("function={} ", function_id));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].0, Level::Info);
+    assert!(
+        lines[0].1.ends_with("sdk text=\"hi\" len=\"2\""),
+        "{}",
+        lines[0].1
+    );
+    assert_eq!(lines[1].0, Level::Debug);
+    assert!(lines[1].1.ends_with("sdk detail"));
+    let logs = runtime
+        .function_logs(function_id, None, None)
+        .await
+        .unwrap();
+    assert!(logs.is_empty());
+}
+
+#[tokio::test]
+async fn guest_log_outside_a_runtime() {
+    install_collector();
+    let engine = loader::build_engine(true, true).await.unwrap();
+    let component = loader::load_module_from_file(&engine, &get_guest_log_path())
+        .await
+        .unwrap();
+    let lambda = Lambda::new(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::LOCALHOST,
+        lambda::WasiFlags::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(lambda.run("standalone").await.unwrap(), "ok");
+    let lines = lines_of("args=\"standalone\"");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].1.starts_with("function=- invocation=- call"));
+}
@@ -29,5 +29,4 @@ async fn process_image() {
     .await
     .unwrap();
     lambda.run("").await.unwrap();
-    assert!(true)
 }
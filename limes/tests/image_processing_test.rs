@@ -29,7 +29,6 @@ async fn process_image() {
     .await
     .unwrap();
     lambda.run("").await.unwrap();
-    assert!(true)
 }
 
 #[tokio::test]
@@ -56,5 +55,4 @@ async fn process_image_no_io() {
     .await
     .unwrap();
     lambda.run("").await.unwrap();
-    assert!(true)
 }
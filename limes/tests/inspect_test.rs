@@ -0,0 +1,168 @@
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::tools::inspect::{self, EntryKind, RUN_SIGNATURE};
+use limes::tools::loader;
+use std::path::{Path, PathBuf};
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files")
+}
+
+fn read_wat(name: &str) -> Vec<u8> {
+    wat::parse_file(get_crate_path().join("composition").join(name)).unwrap()
+}
+
+// Exports `component:run/run` with a `run` taking and returning a number
+const WRONG_RUN: &str = r#"
+(component
+  (core module $m (func (export "f") (param i32) (result i32) local.get 0))
+  (core instance $i (instantiate $m))
+  (func $f (param "args" u32) (result u32) (canon lift (core func $i "f")))
+  (instance $run (export "run" (func $f)))
+  (export "component:run/run" (instance $run))
+)"#;
+
+#[tokio::test]
+async fn inspect_lists_imports_and_exports() {
+    let runtime = Runtime::default();
+    let bytes =
+        std::fs::read(get_crate_path().join("wasm_compiled/exec_rust_lambda_function.wasm"))
+            .unwrap();
+    let report = runtime.validate_module(&bytes, &[]);
+    assert!(report.is_valid(), "{:?}", report.errors);
+    assert!(report.runnable);
+    let run = report
+        .exports
+        .iter()
+        .find(|export| export.name == "component:run/run")
+        .unwrap();
+    assert_eq!(run.kind, EntryKind::Instance);
+    assert_eq!(run.functions["run"], RUN_SIGNATURE);
+    assert!(report
+        .imports
+        .iter()
+        .any(|import| import.name.starts_with("wasi:cli/environment")));
+
+    // The registered module reports the same
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    assert_eq!(runtime.inspect_module(&module_id).unwrap(), report);
+    assert_eq!(
+        runtime.inspect_module(&"missing".to_string()),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+}
+
+#[tokio::test]
+async fn inspect_refuses_unlinkable_components() {
+    let runtime = Runtime::default();
+
+    // `app` imports `test:codec/shout`, only a dependency can provide it
+    let report = runtime.validate_module(&read_wat("app.wat"), &[]);
+    assert!(report.runnable);
+    assert_eq!(report.errors.len(), 1);
+    assert!(
+        report.errors[0].contains("test:codec/shout"),
+        "{}",
+        report.errors[0]
+    );
+    let shout = report
+        .imports
+        .iter()
+        .find(|import| import.name == "test:codec/shout")
+        .unwrap();
+    assert_eq!(shout.functions["shout"], "func(s: string) -> string");
+    let registered = runtime.register_module(read_wat("app.wat")).await;
+    assert!(matches!(
+        registered,
+        Err(RuntimeError::ModuleValidationError(_))
+    ));
+
+    // A library component is valid without `component:run/run`
+    let codec = runtime
+        .register_module(read_wat("codec.wat"))
+        .await
+        .unwrap();
+    let report = runtime.validate_module(&read_wat("shout.wat"), std::slice::from_ref(&codec));
+    assert!(report.is_valid(), "{:?}", report.errors);
+    assert!(!report.runnable);
+    let shout = runtime
+        .register_module_with_dependencies(read_wat("shout.wat"), vec![codec])
+        .await
+        .unwrap();
+    assert!(runtime
+        .validate_module(&read_wat("app.wat"), &[shout])
+        .is_valid());
+}
+
+#[tokio::test]
+async fn inspect_reports_invalid_components() {
+    let runtime = Runtime::default();
+    let report = runtime.validate_module(b"not a component", &[]);
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.imports.is_empty() && report.exports.is_empty());
+    match runtime.register_module(b"not a component".to_vec()).await {
+        Err(RuntimeError::ComponentBuildError(error)) => assert!(!error.is_empty()),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let empty = wat::parse_str("(component)").unwrap();
+    let report = runtime.validate_module(&empty, &[]);
+    assert!(
+        report.errors[0].contains("exports neither"),
+        "{:?}",
+        report.errors
+    );
+
+    let wrong = wat::parse_str(WRONG_RUN).unwrap();
+    let report = runtime.validate_module(&wrong, &[]);
+    assert!(report.runnable);
+    assert_eq!(
+        report.errors,
+        vec![format!(
+            "`component:run/run` exports `run: func(args: u32) -> u32`, expected `run: {}`",
+            RUN_SIGNATURE
+        )]
+    );
+    assert!(matches!(
+        runtime.register_module(wrong).await,
+        Err(RuntimeError::ModuleValidationError(_))
+    ));
+}
+
+#[tokio::test]
+async fn inspect_writes_wit_types() {
+    let engine = loader::build_engine(true, true).await.unwrap();
+    let bytes = wat::parse_str(
+        r#"(component
+          (import "test:types/types" (instance
+            (type $pair (record (field "key" string) (field "value" (list u8))))
+            (export "pair" (type $p (eq $pair)))
+            (type $shape (variant (case "point") (case "circle" f64)))
+            (export "shape" (type $s (eq $shape)))
+            (type $modes (flags "fill" "stroke"))
+            (export "modes" (type $m (eq $modes)))
+            (type $status (enum "ok" "skipped"))
+            (export "status" (type $st (eq $status)))
+            (export "get" (func (param "key" (option string)) (result (result $p (error u32)))))
+            (export "draw" (func (param "shape" $s) (param "modes" $m)
+              (result (tuple bool $st))))
+            (export "ping" (func))
+          ))
+        )"#,
+    )
+    .unwrap();
+    let component = wasmtime::component::Component::from_binary(&engine, &bytes).unwrap();
+    let report = inspect::inspect(&component);
+    let functions = &report.imports[0].functions;
+    assert_eq!(
+        functions["get"],
+        "func(key: option<string>) -> result<record { key: string, value: list<u8> }, u32>"
+    );
+    assert_eq!(
+        functions["draw"],
+        "func(shape: variant { point, circle(f64) }, modes: flags { fill, stroke }) \
+         -> tuple<bool, enum { ok, skipped }>"
+    );
+    assert_eq!(functions["ping"], "func()");
+}
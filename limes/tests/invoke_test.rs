@@ -0,0 +1,201 @@
+use limes::runtime::invocation::InvocationStatus;
+use limes::runtime::lambda::{self, Lambda};
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::tools::loader;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+fn get_test_files_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files")
+}
+
+// The caller runs the function whose id it is given, `E:` prefixes the failed calls
+async fn deploy(runtime: &Runtime, wat: &str, manifest: &str) -> String {
+    let manifest =
+        FunctionManifest::from_toml(&format!("module = \"f.wasm\"\n{}", manifest)).unwrap();
+    let bytes = wat::parse_file(get_test_files_path().join(wat)).unwrap();
+    runtime.deploy(&manifest, bytes).await.unwrap().function_id
+}
+
+async fn deploy_caller(runtime: &Runtime, manifest: &str) -> String {
+    deploy(runtime, "invoke/caller.wat", manifest).await
+}
+
+#[tokio::test]
+async fn invoke_allowed_functions() {
+    let runtime = Runtime::default();
+    let callee = deploy(&runtime, "guest_log/guest_log.wat", "").await;
+    let caller = deploy_caller(&runtime, &format!("[invoke]\nallow = [\"{}\"]", callee)).await;
+
+    assert_eq!(
+        runtime
+            .exec_function(caller.clone(), &callee)
+            .await
+            .unwrap(),
+        "ok"
+    );
+    assert_eq!(
+        runtime
+            .exec_function(caller.clone(), &caller)
+            .await
+            .unwrap(),
+        format!("E:InvokeError: The function may not call `{}`", caller)
+    );
+
+    let any = deploy_caller(&runtime, "[invoke]\nallow = [\"*\"]").await;
+    assert_eq!(
+        runtime.exec_function(any.clone(), &callee).await.unwrap(),
+        "ok"
+    );
+    assert_eq!(
+        runtime.exec_function(any, "missing").await.unwrap(),
+        "E:InvokeError: The function `missing` is not registered"
+    );
+
+    assert!(FunctionManifest::from_toml("module = \"m.wasm\"\n[invoke]\nallow = [\"\"]").is_err());
+}
+
+#[tokio::test]
+async fn invoke_depth_is_limited() {
+    let runtime = Runtime::new().set_max_call_depth(3).build().unwrap();
+    let recursive = deploy_caller(&runtime, "[invoke]\nallow = [\"*\"]").await;
+
+    // Every level calls itself, the refusal of the deepest goes back up the chain
+    assert_eq!(
+        runtime
+            .exec_function(recursive.clone(), &recursive)
+            .await
+            .unwrap(),
+        "E:InvokeError: Calls may not be nested deeper than 3"
+    );
+}
+
+#[tokio::test]
+async fn invoke_busy_functions_are_refused() {
+    let runtime = Runtime::default();
+    let recursive = deploy_caller(&runtime, "concurrency = 1\n[invoke]\nallow = [\"*\"]").await;
+
+    // The call holds the only permit, waiting for it would never end
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        runtime.exec_function(recursive.clone(), &recursive),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        result,
+        format!(
+            "E:InvokeError: The function `{}` already runs as many calls as its concurrency",
+            recursive
+        )
+    );
+}
+
+#[tokio::test]
+async fn invoke_inherits_the_deadline() {
+    let runtime = Runtime::default();
+    let spin = deploy(&runtime, "invoke/spin.wat", "").await;
+    let caller = deploy_caller(
+        &runtime,
+        &format!("timeout = \"500ms\"\n[invoke]\nallow = [\"{}\"]", spin),
+    )
+    .await;
+
+    // The callee has no timeout of its own, it is bound by the one of the caller
+    let start = Instant::now();
+    let result = runtime.exec_function(caller, &spin).await;
+    assert!(start.elapsed() < Duration::from_secs(5));
+    match result {
+        Ok(result) => assert!(result.starts_with("E:"), "{}", result),
+        Err(e) => assert!(e.to_string().contains("timeout"), "{}", e),
+    }
+}
+
+// The callee spins without yielding, it needs a thread of its own
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn invoke_stopped_with_the_caller() {
+    let runtime = Runtime::default();
+    let spin = deploy(&runtime, "invoke/spin.wat", "").await;
+    let caller = deploy_caller(&runtime, &format!("[invoke]\nallow = [\"{}\"]", spin)).await;
+    let invocation_id = runtime
+        .submit_function(caller.clone(), spin.clone(), None)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    runtime.stop_function(caller).await.unwrap();
+
+    let mut status = InvocationStatus::Running;
+    for _ in 0..100 {
+        status = runtime
+            .get_invocation(invocation_id.clone())
+            .await
+            .unwrap()
+            .status;
+        if status.is_finished() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(status, InvocationStatus::Failed);
+}
+
+#[tokio::test]
+async fn invoke_outside_a_runtime() {
+    let engine = loader::build_engine(true, true).await.unwrap();
+    let path = get_test_files_path().join("invoke/caller.wat");
+    let component = loader::load_module_from_file(&engine, &path).await.unwrap();
+    let lambda = Lambda::new(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::LOCALHOST,
+        lambda::WasiFlags::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        lambda.run("any").await.unwrap(),
+        "E:InvokeError: Function calls are not available to this function"
+    );
+}
+
+// `call,<function id>,<args>` calls through limes_guest::invoke
+#[tokio::test]
+async fn invoke_through_the_guest_sdk() {
+    let runtime = Runtime::default();
+    let bytes = std::fs::read(get_test_files_path().join("wasm_compiled/guest_sdk.wasm")).unwrap();
+    let deploy_sdk = |manifest: String| {
+        let manifest =
+            FunctionManifest::from_toml(&format!("module = \"f.wasm\"\n{}", manifest)).unwrap();
+        let bytes = bytes.clone();
+        let runtime = &runtime;
+        async move { runtime.deploy(&manifest, bytes).await.unwrap().function_id }
+    };
+    let callee = deploy_sdk(String::new()).await;
+    let caller = deploy_sdk(format!("[invoke]\nallow = [\"{}\"]", callee)).await;
+
+    assert_eq!(
+        runtime
+            .exec_function(caller.clone(), &format!("call,{},count,calls", callee))
+            .await
+            .unwrap(),
+        "1"
+    );
+
+    // A refused call is an error of the SDK
+    match runtime
+        .exec_function(caller.clone(), &format!("call,{},count,calls", caller))
+        .await
+    {
+        Err(RuntimeError::GuestError(error)) => assert_eq!(error.code, "invoke"),
+        result => panic!("expected the guest error, got {:?}", result),
+    }
+    let logs = runtime.function_logs(caller, None, None).await.unwrap();
+    assert!(logs
+        .iter()
+        .any(|line| line.line.starts_with(r#"{"error":{"code":"invoke""#)));
+}
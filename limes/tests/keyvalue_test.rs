@@ -0,0 +1,268 @@
+use limes::db::kv::{KvQuota, KvStore, LIST_PAGE_SIZE};
+use limes::db::kv_error::KvError;
+use limes::runtime::lambda::{self, Lambda};
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::tools::loader;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn get_counter_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/kv_counter/kv_counter.wat")
+}
+
+fn counter_bytes() -> Vec<u8> {
+    wat::parse_file(get_counter_path()).unwrap()
+}
+
+// The counter module is not read from the manifest, only its settings are used
+fn manifest(kv: &str) -> FunctionManifest {
+    FunctionManifest::from_toml(&format!("module = \"kv_counter.wasm\"\n[kv]\n{}", kv)).unwrap()
+}
+
+async fn exec(runtime: &Runtime, function_id: &str, args: &str) -> String {
+    runtime
+        .exec_function(function_id.to_string(), args)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn kv_function_scope_keeps_state_between_calls() {
+    let runtime = Runtime::default();
+    let module_id = runtime.register_module(counter_bytes()).await.unwrap();
+    let first = runtime
+        .init_function(module_id.clone(), Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    let second = runtime
+        .init_function(module_id, Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+
+    // Every call runs on a fresh instance, the count lives in the store
+    assert_eq!(exec(&runtime, &first, "calls").await, "1");
+    assert_eq!(exec(&runtime, &first, "calls").await, "2");
+    assert_eq!(exec(&runtime, &first, "other").await, "1");
+    assert_eq!(exec(&runtime, &second, "calls").await, "1");
+
+    let namespace = format!("function/{}", first);
+    assert!(runtime.kv_store().namespaces().contains(&namespace));
+    runtime.remove_function(first).await;
+    assert!(!runtime.kv_store().namespaces().contains(&namespace));
+}
+
+#[tokio::test]
+async fn kv_module_and_named_scopes() {
+    let runtime = Runtime::default();
+    let module_id = runtime.register_module(counter_bytes()).await.unwrap();
+    let module_scope = manifest("scope = \"module\"");
+    let first = runtime
+        .init_function_with_manifest(module_id.clone(), &module_scope)
+        .await
+        .unwrap();
+    let second = runtime
+        .init_function_with_manifest(module_id.clone(), &module_scope)
+        .await
+        .unwrap();
+    assert_eq!(exec(&runtime, &first, "calls").await, "1");
+    assert_eq!(exec(&runtime, &second, "calls").await, "2");
+
+    // A named namespace is shared across modules
+    let named = manifest("namespace = \"counters\"");
+    let other_module = runtime.register_module(counter_bytes()).await.unwrap();
+    let third = runtime
+        .init_function_with_manifest(module_id.clone(), &named)
+        .await
+        .unwrap();
+    let fourth = runtime
+        .init_function_with_manifest(other_module, &named)
+        .await
+        .unwrap();
+    assert_eq!(exec(&runtime, &third, "calls").await, "1");
+    assert_eq!(exec(&runtime, &fourth, "calls").await, "2");
+
+    // The module namespace goes with the module, once no function uses it
+    assert!(matches!(
+        runtime.remove_module(module_id.clone()).await,
+        Err(RuntimeError::ModuleNamespaceInUse(_))
+    ));
+    runtime.remove_function(first).await;
+    runtime.remove_function(second).await;
+    runtime.remove_module(module_id.clone()).await.unwrap();
+    assert_eq!(runtime.kv_store().namespaces(), vec!["named/counters"]);
+}
+
+#[tokio::test]
+async fn kv_quota_limits_the_namespace() {
+    let runtime = Runtime::default();
+    let module_id = runtime.register_module(counter_bytes()).await.unwrap();
+    let function_id = runtime
+        .init_function_with_manifest(module_id, &manifest("max_keys = 1\nquota = 8"))
+        .await
+        .unwrap();
+
+    // `E2` is quota-exceeded, `E3` other, an empty key is refused
+    assert_eq!(exec(&runtime, &function_id, "a").await, "1");
+    assert_eq!(exec(&runtime, &function_id, "a").await, "2");
+    assert_eq!(exec(&runtime, &function_id, "b").await, "E2");
+    assert_eq!(exec(&runtime, &function_id, "").await, "E3");
+
+    assert!(FunctionManifest::from_toml("module = \"m.wasm\"\n[kv]\nmax_keys = 0").is_err());
+    assert!(FunctionManifest::from_toml("module = \"m.wasm\"\n[kv]\nnamespace = \"\"").is_err());
+}
+
+#[tokio::test]
+async fn kv_store_persisted_in_a_directory() {
+    let dir = std::env::temp_dir().join(format!("limes_kv_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let named = manifest("namespace = \"persisted\"");
+    for expected in ["1", "2"] {
+        let runtime = Runtime::new().set_kv_dir(dir.clone()).build().unwrap();
+        let module_id = runtime.register_module(counter_bytes()).await.unwrap();
+        let function_id = runtime
+            .init_function_with_manifest(module_id, &named)
+            .await
+            .unwrap();
+        assert_eq!(exec(&runtime, &function_id, "calls").await, expected);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn kv_without_namespace() {
+    let engine = loader::build_engine(true, true).await.unwrap();
+    let component = loader::load_module_from_file(&engine, &get_counter_path())
+        .await
+        .unwrap();
+    let lambda = Lambda::new(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::LOCALHOST,
+        lambda::WasiFlags::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(lambda.run("calls").await.unwrap(), "E0");
+}
+
+#[tokio::test]
+async fn kv_namespace_operations() {
+    let store = Arc::new(KvStore::in_memory());
+    let quota = KvQuota {
+        max_bytes: 1024 * 1024,
+        max_keys: 1000,
+    };
+    let namespace = store.namespace("test", quota);
+    assert_eq!(namespace.get("missing"), None);
+    assert!(store.namespaces().is_empty());
+
+    for n in 0..150 {
+        namespace
+            .set(&format!("key{:03}", n), vec![n as u8])
+            .await
+            .unwrap();
+    }
+    assert!(namespace.exists("key007"));
+    assert_eq!(namespace.get("key007"), Some(vec![7]));
+    assert_eq!(namespace.usage(), (150, 150 * 7));
+
+    let (page, cursor) = namespace.list_keys(None);
+    assert_eq!(page.len(), LIST_PAGE_SIZE);
+    let (rest, cursor) = namespace.list_keys(cursor.as_deref());
+    assert_eq!(rest.len(), 150 - LIST_PAGE_SIZE);
+    assert_eq!(rest[0], format!("key{:03}", LIST_PAGE_SIZE));
+    assert_eq!(cursor, None);
+
+    namespace.delete("key007").await.unwrap();
+    assert!(!namespace.exists("key007"));
+    assert_eq!(namespace.usage(), (149, 149 * 7));
+
+    let small = store.namespace(
+        "small",
+        KvQuota {
+            max_bytes: 4,
+            max_keys: 10,
+        },
+    );
+    small.set("ab", vec![1, 2]).await.unwrap();
+    assert_eq!(
+        small.set("ab", vec![1, 2, 3]).await,
+        Err(KvError::QuotaExceeded("small".to_string()))
+    );
+    assert_eq!(small.get("ab"), Some(vec![1, 2]));
+}
+
+#[tokio::test]
+async fn kv_failed_writes_are_rolled_back() {
+    let dir = std::env::temp_dir().join(format!("limes_kv_rollback_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = Arc::new(KvStore::open(&dir).unwrap());
+    let namespace = store.namespace("test", KvQuota::default());
+    namespace.set("kept", vec![1]).await.unwrap();
+    namespace.set("other", vec![2]).await.unwrap();
+
+    // Without its directory the store can not write the namespace file anymore
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(
+        namespace.set("kept", vec![1, 2, 3]).await,
+        Err(KvError::Io(_))
+    ));
+    assert!(matches!(
+        namespace.set("new", vec![3]).await,
+        Err(KvError::Io(_))
+    ));
+    assert!(matches!(
+        namespace.delete("other").await,
+        Err(KvError::Io(_))
+    ));
+    assert_eq!(namespace.get("kept"), Some(vec![1]));
+    assert!(!namespace.exists("new"));
+    assert_eq!(namespace.get("other"), Some(vec![2]));
+    assert_eq!(namespace.usage(), (2, "kept".len() + "other".len() + 2));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    namespace.set("new", vec![3]).await.unwrap();
+    let reopened = Arc::new(KvStore::open(&dir).unwrap());
+    let namespace = reopened.namespace("test", KvQuota::default());
+    assert_eq!(
+        namespace.usage(),
+        (3, "kept".len() + "other".len() + "new".len() + 3)
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// `count,<key>` increments a counter through limes_guest::kv
+#[tokio::test]
+async fn kv_through_the_guest_sdk() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let bytes = std::fs::read(
+        Path::new(&crate_dir)
+            .join("resources/wasm_wasi_module_test_files/wasm_compiled/guest_sdk.wasm"),
+    )
+    .unwrap();
+    let runtime = Runtime::default();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let function_id = runtime
+        .init_function_with_manifest(module_id, &manifest("max_keys = 1"))
+        .await
+        .unwrap();
+    assert_eq!(exec(&runtime, &function_id, "count,a").await, "1");
+    assert_eq!(exec(&runtime, &function_id, "count,a").await, "2");
+
+    // The store errors come back as errors of the SDK
+    match runtime.exec_function(function_id.clone(), "count,b").await {
+        Err(RuntimeError::GuestError(error)) => assert_eq!(error.code, "quota_exceeded"),
+        result => panic!("expected the guest error, got {:?}", result),
+    }
+    let logs = runtime
+        .function_logs(function_id, None, None)
+        .await
+        .unwrap();
+    assert!(logs.iter().any(|line| line
+        .line
+        .starts_with(r#"{"error":{"code":"quota_exceeded""#)));
+}
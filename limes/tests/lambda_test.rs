@@ -1,9 +1,10 @@
-use limes::runtime::lambda::{self, Lambda};
+use limes::runtime::lambda::{self, InstanceMode, Lambda, ResetPolicy, WarmPoolConfig};
 use limes::runtime::lambda_error::LambdaError;
+use limes::tools::loader;
 use std::net::Ipv4Addr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio;
+use std::time::Duration;
 use wasmtime::component::Component;
 use wasmtime::*;
 
@@ -25,8 +26,7 @@ fn gen_engine(e_async: bool, e_epoch: bool, cranelift_opt_leve: OptLevel) -> Eng
 }
 
 fn load_component(engine: &Engine, path: PathBuf) -> wasmtime::component::Component {
-    let component = Component::from_file(&engine, path).expect("Wasm module not found");
-    component
+    Component::from_file(engine, path).expect("Wasm module not found")
 }
 
 async fn get_lambda(component_name: &str, mem_size: usize, tap_ip: Ipv4Addr) -> Lambda {
@@ -132,12 +132,230 @@ async fn tcp_udp_bind_to_not_allowed_ip() {
     );
 
     // Not allowed ip for tcp/udp
-    assert_eq!(
-        Err(LambdaError::FunctionExecError),
-        lambda.run("TCP,192.168.2.2.3:50300").await
-    );
-    assert_eq!(
-        Err(LambdaError::FunctionExecError),
-        lambda.run("UDP,192.168.2.2.3:50300").await
-    );
+    for args in ["TCP,192.168.2.2.3:50300", "UDP,192.168.2.2.3:50300"] {
+        match lambda.run(args).await {
+            Err(LambdaError::GuestError(error)) => assert_eq!(error.code, "io"),
+            result => panic!("expected the guest error, got {:?}", result),
+        }
+    }
+}
+
+async fn get_warm_lambda(component_name: &str, mem_size: usize, config: WarmPoolConfig) -> Lambda {
+    let engine = Arc::new(gen_engine(true, true, OptLevel::Speed));
+    let file = get_crate_path().join(component_name);
+    let component = Arc::new(load_component(&engine, file));
+    let wasi_flags = lambda::WasiFlags::default();
+    Lambda::with_instance_mode(
+        component.clone(),
+        mem_size,
+        Ipv4Addr::new(127, 0, 0, 1),
+        wasi_flags,
+        InstanceMode::Warm(config),
+    )
+    .await
+    .unwrap()
+}
+
+// Polls the pool until it holds `expected` idle instances, refills run in the background
+async fn wait_for_warm_instances(lambda: &Lambda, expected: usize) {
+    let polling = async {
+        while lambda.warm_instances() != expected {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), polling)
+        .await
+        .unwrap_or_else(|_| panic!("the pool did not reach {} instances", expected));
+}
+
+#[tokio::test]
+async fn warm_pool_reuse_instances() {
+    let config = WarmPoolConfig::new(2, Duration::from_secs(60), 100, ResetPolicy::PostReturn);
+    let lambda = get_warm_lambda("multiple_function_exec.wasm", 1024 * 1024 * 5, config).await;
+    assert_eq!(lambda.warm_instances(), 2);
+
+    assert_eq!(lambda.run("c,b,a").await.unwrap(), "[a,b,c]");
+    assert_eq!(lambda.run("z,y").await.unwrap(), "[y,z]");
+    assert_eq!(lambda.warm_instances(), 2);
+}
+
+#[tokio::test]
+async fn warm_pool_discard_and_max_reuse() {
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, ResetPolicy::Discard);
+    let lambda = get_warm_lambda("exec_rust_lambda_function.wasm", 1024 * 1024 * 2, config).await;
+    assert_eq!(lambda.run("").await.unwrap(), "### TEST ###");
+    assert_eq!(lambda.warm_instances(), 0);
+    assert_eq!(lambda.run("").await.unwrap(), "### TEST ###");
+
+    // Dropped instances are replaced in the background
+    wait_for_warm_instances(&lambda, 1).await;
+
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 2, ResetPolicy::PostReturn);
+    let lambda = get_warm_lambda("exec_rust_lambda_function.wasm", 1024 * 1024 * 2, config).await;
+    lambda.run("").await.unwrap();
+    assert_eq!(lambda.warm_instances(), 1);
+    lambda.run("").await.unwrap();
+    assert_eq!(lambda.warm_instances(), 0);
+    wait_for_warm_instances(&lambda, 1).await;
+}
+
+#[tokio::test]
+async fn warm_pool_idle_ttl() {
+    let config = WarmPoolConfig::new(2, Duration::from_millis(100), 100, ResetPolicy::PostReturn);
+    let lambda = get_warm_lambda("exec_rust_lambda_function.wasm", 1024 * 1024 * 2, config).await;
+    wait_for_warm_instances(&lambda, 0).await;
+    assert_eq!(lambda.run("").await.unwrap(), "### TEST ###");
+    assert_eq!(lambda.warm_instances(), 1);
+}
+
+async fn get_stateful_lambda(instance_mode: InstanceMode) -> Lambda {
+    let engine = gen_engine(true, true, OptLevel::Speed);
+    let file = get_crate_path().join("../stateful_counter/stateful_counter.wat");
+    let component = loader::load_snapshot_module_from_file(&engine, &file)
+        .await
+        .unwrap();
+    Lambda::with_instance_mode(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::new(127, 0, 0, 1),
+        lambda::WasiFlags::default(),
+        instance_mode,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn warm_pool_snapshot_no_state_leak() {
+    // Plain reuse keeps the counters growing
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, ResetPolicy::PostReturn);
+    let lambda = get_stateful_lambda(InstanceMode::Warm(config)).await;
+    assert_eq!(lambda.run("").await.unwrap(), "11");
+    assert_eq!(lambda.run("").await.unwrap(), "22");
+
+    // Snapshot restores memory and globals before every call
+    let policy = ResetPolicy::Snapshot { init_export: None };
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, policy);
+    let lambda = get_stateful_lambda(InstanceMode::Warm(config)).await;
+    for _ in 0..3 {
+        assert_eq!(lambda.run("").await.unwrap(), "11");
+    }
+    assert_eq!(lambda.warm_instances(), 1);
+}
+
+#[tokio::test]
+async fn warm_pool_snapshot_after_init() {
+    let policy = ResetPolicy::Snapshot {
+        init_export: Some("init".to_string()),
+    };
+    let config = WarmPoolConfig::new(2, Duration::from_secs(60), 100, policy);
+    let lambda = get_stateful_lambda(InstanceMode::Warm(config)).await;
+    for _ in 0..3 {
+        assert_eq!(lambda.run("").await.unwrap(), "63");
+    }
+}
+
+#[tokio::test]
+async fn warm_pool_snapshot_needs_instrumentation() {
+    let engine = Arc::new(gen_engine(true, true, OptLevel::Speed));
+    let file = get_crate_path().join("exec_rust_lambda_function.wasm");
+    let component = Arc::new(load_component(&engine, file));
+    let policy = ResetPolicy::Snapshot { init_export: None };
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, policy);
+    let result = Lambda::with_instance_mode(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::new(127, 0, 0, 1),
+        lambda::WasiFlags::default(),
+        InstanceMode::Warm(config),
+    )
+    .await;
+    assert!(matches!(result, Err(LambdaError::SnapshotError(_))));
+
+    // Real guests built with wit-bindgen keep working once instrumented
+    let file = get_crate_path().join("multiple_function_exec.wasm");
+    let component = loader::load_snapshot_module_from_file(&engine, &file)
+        .await
+        .unwrap();
+    let policy = ResetPolicy::Snapshot { init_export: None };
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, policy);
+    let lambda = Lambda::with_instance_mode(
+        component,
+        1024 * 1024 * 5,
+        Ipv4Addr::new(127, 0, 0, 1),
+        lambda::WasiFlags::default(),
+        InstanceMode::Warm(config),
+    )
+    .await
+    .unwrap();
+    assert_eq!(lambda.run("c,b,a").await.unwrap(), "[a,b,c]");
+    assert_eq!(lambda.run("e,d").await.unwrap(), "[d,e]");
+}
+
+// Grows the memory by 2 pages and the table by 3 elements, then returns "ok"
+const GROWING_COMPONENT: &str = r#"(component
+    (core module $m
+        (memory (export "memory") 1)
+        (table 1 funcref)
+        (data (i32.const 8) "\10\00\00\00\02\00\00\00ok")
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 32)
+        (func (export "run") (param i32 i32) (result i32)
+            (drop (memory.grow (i32.const 2)))
+            (drop (table.grow (ref.null func) (i32.const 3)))
+            i32.const 8))
+    (core instance $i (instantiate $m))
+    (func $run (param "args" string) (result string)
+        (canon lift (core func $i "run") (memory (core memory $i "memory"))
+            (realloc (core func $i "realloc"))))
+    (instance $run-instance (export "run" (func $run)))
+    (export "component:run/run" (instance $run-instance)))"#;
+
+async fn get_growing_lambda(engine: &Engine, instance_mode: InstanceMode) -> Lambda {
+    let bytes = wat::parse_str(GROWING_COMPONENT).unwrap();
+    let component = Arc::new(Component::from_binary(engine, &bytes).unwrap());
+    Lambda::with_instance_mode(
+        component,
+        1024 * 1024 * 2,
+        Ipv4Addr::new(127, 0, 0, 1),
+        lambda::WasiFlags::default(),
+        instance_mode,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn usage_memory_and_table_growth() {
+    let engine = gen_engine(true, true, OptLevel::Speed);
+    let lambda = get_growing_lambda(&engine, InstanceMode::Fresh).await;
+    let (result, usage) = lambda.run_with_usage("").await;
+    assert_eq!(result.unwrap(), "ok");
+    assert_eq!(usage.peak_memory_bytes, 3 * 65536);
+    assert_eq!(usage.table_growth, 3);
+    assert_eq!(usage.fuel_used, None);
+    assert_eq!(usage.preopen_bytes_written, 0);
+    assert!(usage.trap.is_none());
+
+    // A reused instance starts from the memory left by the previous call
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, ResetPolicy::PostReturn);
+    let lambda = get_growing_lambda(&engine, InstanceMode::Warm(config)).await;
+    let (_, usage) = lambda.run_with_usage("").await;
+    assert_eq!(usage.peak_memory_bytes, 3 * 65536);
+    let (_, usage) = lambda.run_with_usage("").await;
+    assert_eq!(usage.peak_memory_bytes, 5 * 65536);
+    assert_eq!(usage.table_growth, 3);
+}
+
+#[tokio::test]
+async fn usage_fuel_when_enabled() {
+    let mut config = Config::new();
+    config
+        .async_support(true)
+        .epoch_interruption(true)
+        .consume_fuel(true);
+    let engine = Engine::new(&config).unwrap();
+    let lambda = get_growing_lambda(&engine, InstanceMode::Fresh).await;
+    let (result, usage) = lambda.run_with_usage("").await;
+    assert_eq!(result.unwrap(), "ok");
+    assert!(usage.fuel_used.is_some_and(|fuel| fuel > 0));
 }
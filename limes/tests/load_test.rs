@@ -0,0 +1,146 @@
+use limes::runtime::runtime::Runtime;
+use limes::server::api;
+use limes::tools::load::{self, HttpTarget, LoadConfig, LoadModel, LoadTarget, RuntimeTarget};
+use std::future::IntoFuture;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+// Sleeps a fixed time and fails every other call
+struct SlowTarget {
+    delay: Duration,
+    calls: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl LoadTarget for SlowTarget {
+    async fn call(&self) -> Result<(), String> {
+        tokio::time::sleep(self.delay).await;
+        match self.calls.fetch_add(1, Ordering::SeqCst) % 2 {
+            0 => Ok(()),
+            _ => Err("failed".to_string()),
+        }
+    }
+}
+
+fn config(model: LoadModel, concurrency: usize, rate: Option<f64>) -> LoadConfig {
+    LoadConfig {
+        model,
+        concurrency,
+        rate,
+        duration: Duration::from_millis(500),
+        interval: Duration::from_millis(250),
+    }
+}
+
+#[tokio::test]
+async fn load_validation() {
+    let target = Arc::new(SlowTarget {
+        delay: Duration::ZERO,
+        calls: AtomicUsize::new(0),
+    });
+    assert!(
+        load::run_load(target.clone(), &config(LoadModel::Closed, 0, None))
+            .await
+            .is_err()
+    );
+    assert!(
+        load::run_load(target.clone(), &config(LoadModel::Open, 1, None))
+            .await
+            .is_err()
+    );
+    assert!(
+        load::run_load(target, &config(LoadModel::Closed, 1, Some(0.0)))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn load_open_model_queues() {
+    // 100 req/s against one slot of 20ms: arrivals outpace the service and wait
+    let target = Arc::new(SlowTarget {
+        delay: Duration::from_millis(20),
+        calls: AtomicUsize::new(0),
+    });
+    let report = load::run_load(target, &config(LoadModel::Open, 1, Some(100.0)))
+        .await
+        .unwrap();
+    assert!(report.completed >= 40 && report.completed <= 51);
+    assert!(report.errors > 0);
+    assert!(report.error_rate > 0.4 && report.error_rate < 0.6);
+    assert!(report.queued.max > 100.0);
+    assert!(report.latency.p99 >= report.queued.p99);
+    assert!(report.windows.len() >= 2);
+    assert_eq!(
+        report.windows.iter().map(|w| w.completed).sum::<usize>(),
+        report.completed
+    );
+}
+
+#[tokio::test]
+async fn load_closed_model_paced() {
+    let target = Arc::new(SlowTarget {
+        delay: Duration::from_millis(1),
+        calls: AtomicUsize::new(0),
+    });
+    let report = load::run_load(target, &config(LoadModel::Closed, 2, Some(40.0)))
+        .await
+        .unwrap();
+    assert!(report.completed >= 16 && report.completed <= 24);
+    assert_eq!(report.queued.max, 0.0);
+}
+
+#[tokio::test]
+async fn load_runtime_target() {
+    let runtime = Arc::new(Runtime::default());
+    let module = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let module_id = runtime.register_module(module).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    let target = Arc::new(RuntimeTarget::new(runtime, func_id, String::new()));
+    let report = load::run_load(target, &config(LoadModel::Closed, 2, None))
+        .await
+        .unwrap();
+    assert!(report.completed > 0);
+    assert_eq!(report.errors, 0);
+    assert!(report.throughput > 0.0);
+}
+
+#[tokio::test]
+async fn load_http_target() {
+    let runtime = Arc::new(Runtime::default());
+    let module = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let module_id = runtime.register_module(module).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(listener, api::router(runtime)).into_future());
+
+    let target = Arc::new(HttpTarget::new(&url, &func_id, String::new()));
+    let report = load::run_load(target, &config(LoadModel::Open, 4, Some(50.0)))
+        .await
+        .unwrap();
+    assert!(report.completed > 0);
+    assert_eq!(report.errors, 0);
+
+    let missing = Arc::new(HttpTarget::new(&url, "missing", String::new()));
+    let report = load::run_load(missing, &config(LoadModel::Closed, 1, Some(10.0)))
+        .await
+        .unwrap();
+    assert_eq!(report.errors, report.completed);
+}
@@ -28,8 +28,10 @@ async fn mandelbrotset() {
     )
     .await
     .unwrap();
-    lambda.run("").await.unwrap();
-    assert!(true)
+    let (result, usage) = lambda.run_with_usage("").await;
+    result.unwrap();
+    assert!(usage.preopen_bytes_written > 0);
+    assert!(usage.peak_memory_bytes > 0);
 }
 
 #[tokio::test]
@@ -47,6 +49,7 @@ async fn mandelbrotset_no_io() {
     )
     .await
     .unwrap();
-    lambda.run("").await.unwrap();
-    assert!(true)
+    let (result, usage) = lambda.run_with_usage("").await;
+    result.unwrap();
+    assert_eq!(usage.preopen_bytes_written, 0);
 }
@@ -0,0 +1,279 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use http_body_util::BodyExt;
+use limes::runtime::lambda::OutboundRule;
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::server::api;
+use serde_json::Value;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+// Spins forever without calling the host
+const SPINNING_COMPONENT: &str = r#"(component
+    (core module $m
+        (memory (export "memory") 1)
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 32)
+        (func (export "run") (param i32 i32) (result i32)
+            (loop $spin (br $spin))
+            i32.const 8))
+    (core instance $i (instantiate $m))
+    (func $run (param "args" string) (result string)
+        (canon lift (core func $i "run") (memory (core memory $i "memory"))
+            (realloc (core func $i "realloc"))))
+    (instance $run-instance (export "run" (func $run)))
+    (export "component:run/run" (instance $run-instance)))"#;
+
+fn build_manifest(module: &str, extra: &str) -> FunctionManifest {
+    FunctionManifest::from_toml(&format!("module = \"{}\"\n{}", module, extra)).unwrap()
+}
+
+#[test]
+fn manifest_parse() {
+    let manifest = FunctionManifest::from_toml(
+        r#"
+name = "echo"
+module = "echo.wasm"
+memory = "64MiB"
+timeout = "5s"
+concurrency = 4
+
+[env]
+GREETING = "hello"
+
+[[mount]]
+host = "data"
+guest = "/data"
+read_only = true
+
+[network]
+tap_ip = "10.0.0.2"
+outbound = ["10.0.0.1:5432", "192.168.1.1"]
+
+[warm_pool]
+size = 2
+"#,
+    )
+    .unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("echo"));
+    assert_eq!(manifest.world, "component:run/runnable");
+    assert_eq!(manifest.memory, Some(64 << 20));
+    assert_eq!(manifest.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(manifest.concurrency, Some(4));
+    assert_eq!(manifest.env["GREETING"], "hello");
+    assert!(manifest.mounts[0].read_only);
+    assert!(manifest.network.enabled);
+    assert_eq!(manifest.network.tap_ip, Some(Ipv4Addr::new(10, 0, 0, 2)));
+    assert_eq!(manifest.warm_pool.unwrap().size, 2);
+
+    let invalid = |text: &str| {
+        matches!(
+            FunctionManifest::from_toml(text),
+            Err(RuntimeError::ManifestError(_))
+        )
+    };
+    assert!(invalid("name = \"no module\"\n"));
+    assert!(invalid(
+        "module = \"m.wasm\"\nworld = \"wasi:http/proxy\"\n"
+    ));
+    assert!(invalid("module = \"m.wasm\"\nmemory = \"1MiB\"\n"));
+    assert!(invalid("module = \"m.wasm\"\ntimeout = \"0s\"\n"));
+    assert!(invalid("module = \"m.wasm\"\nconcurrency = 0\n"));
+    assert!(invalid(
+        "module = \"m.wasm\"\n[network]\noutbound = [\"nowhere\"]\n"
+    ));
+    assert!(invalid(
+        "module = \"m.wasm\"\n[[mount]]\nhost = \".\"\nguest = \"data\"\n"
+    ));
+    assert!(invalid("module = \"m.wasm\"\nmemroy = \"64MiB\"\n"));
+
+    // The example shipped next to the test function is valid
+    let example = get_crate_path().join("../exec_rust_lambda_function");
+    let manifest = FunctionManifest::from_file(&example).unwrap();
+    assert!(manifest.module.is_file());
+}
+
+#[test]
+fn manifest_outbound_rules() {
+    let any_port: OutboundRule = "10.0.0.1".parse().unwrap();
+    let one_port: OutboundRule = "10.0.0.1:5432".parse().unwrap();
+    let v6: OutboundRule = "[::1]".parse().unwrap();
+    let socket = |addr: &str| addr.parse::<SocketAddr>().unwrap();
+    assert!(any_port.allows(&socket("10.0.0.1:80")));
+    assert!(one_port.allows(&socket("10.0.0.1:5432")));
+    assert!(!one_port.allows(&socket("10.0.0.1:80")));
+    assert!(!any_port.allows(&socket("10.0.0.2:80")));
+    assert!(v6.allows(&socket("[::1]:443")));
+    assert!("10.0.0.1:port".parse::<OutboundRule>().is_err());
+}
+
+#[tokio::test]
+async fn manifest_deploy_from_file() {
+    let dir = std::env::temp_dir().join(format!("limes_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("data")).unwrap();
+    std::fs::copy(
+        get_crate_path().join("exec_rust_lambda_function.wasm"),
+        dir.join("echo.wasm"),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("limes.toml"),
+        "module = \"echo.wasm\"\nmemory = \"8MiB\"\ntimeout = \"5s\"\n\n[env]\nKEY = \"value\"\n\n[[mount]]\nhost = \"data\"\nguest = \"/data\"\n",
+    )
+    .unwrap();
+
+    let runtime = Runtime::default();
+    let deployment = runtime.deploy_from_file(&dir).await.unwrap();
+    let result = runtime
+        .exec_function(deployment.function_id.clone(), "")
+        .await
+        .unwrap();
+    assert_eq!(result, "### TEST ###");
+    assert!(runtime.remove_function(deployment.function_id).await);
+    runtime.remove_module(deployment.module_id).await.unwrap();
+
+    // The module is removed again when its function can not be initialized
+    std::fs::remove_dir(dir.join("data")).unwrap();
+    let result = runtime.deploy_from_file(&dir.join("limes.toml")).await;
+    assert!(matches!(result, Err(RuntimeError::ManifestError(_))));
+
+    let manifest = build_manifest("echo.wasm", "memory = \"1TiB\"\n");
+    let bytes = std::fs::read(dir.join("echo.wasm")).unwrap();
+    let result = runtime.deploy(&manifest, bytes).await;
+    assert!(matches!(result, Err(RuntimeError::FunctionInitError(_))));
+
+    // A component without `component:run/run` is refused at registration
+    let manifest = build_manifest("empty.wasm", "");
+    let result = runtime
+        .deploy(&manifest, wat::parse_str("(component)").unwrap())
+        .await;
+    assert!(matches!(
+        result,
+        Err(RuntimeError::ModuleValidationError(_))
+    ));
+}
+
+#[tokio::test]
+async fn manifest_timeout() {
+    let runtime = Runtime::default();
+
+    // A guest spinning on its own is stopped by the epoch deadline
+    let manifest = build_manifest("spin.wasm", "timeout = \"200ms\"\n");
+    let bytes = wat::parse_str(SPINNING_COMPONENT).unwrap();
+    let deployment = runtime.deploy(&manifest, bytes).await.unwrap();
+    let start = Instant::now();
+    let result = runtime.exec_function(deployment.function_id, "").await;
+    assert_eq!(
+        result,
+        Err(RuntimeError::FunctionExecError(
+            "Wasm function exceeded its timeout".to_string()
+        ))
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // A guest waiting on the host is dropped by the timer
+    let manifest = build_manifest("loop.wasm", "timeout = \"200ms\"\n");
+    let bytes = std::fs::read(get_crate_path().join("stop_infinite_loop.wasm")).unwrap();
+    let deployment = runtime.deploy(&manifest, bytes).await.unwrap();
+    let result = runtime.exec_function(deployment.function_id, "").await;
+    assert!(result.is_err());
+
+    let metrics = runtime.metrics().encode();
+    let timeouts: f64 = metrics
+        .lines()
+        .filter(|line| line.starts_with("limes_timeouts_total{"))
+        .filter_map(|line| line.rsplit(' ').next()?.parse::<f64>().ok())
+        .sum();
+    assert_eq!(timeouts, 2.0);
+}
+
+#[tokio::test]
+async fn manifest_concurrency() {
+    let runtime = Arc::new(Runtime::default());
+    let manifest = build_manifest("loop.wasm", "timeout = \"300ms\"\nconcurrency = 1\n");
+    let bytes = std::fs::read(get_crate_path().join("stop_infinite_loop.wasm")).unwrap();
+    let deployment = runtime.deploy(&manifest, bytes).await.unwrap();
+
+    // The second call waits for the first one to time out
+    let start = Instant::now();
+    let calls: Vec<_> = (0..2)
+        .map(|_| {
+            let runtime = runtime.clone();
+            let function_id = deployment.function_id.clone();
+            tokio::spawn(async move { runtime.exec_function(function_id, "").await })
+        })
+        .collect();
+    for call in calls {
+        assert!(call.await.unwrap().is_err());
+    }
+    assert!(start.elapsed() >= Duration::from_millis(600));
+}
+
+fn multipart(manifest: &str, module: &[u8]) -> Request<Body> {
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--limes\r\nContent-Disposition: form-data; name=\"manifest\"\r\n\r\n{}\r\n",
+            manifest
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(
+        b"--limes\r\nContent-Disposition: form-data; name=\"module\"; filename=\"m.wasm\"\r\nContent-Type: application/wasm\r\n\r\n",
+    );
+    body.extend_from_slice(module);
+    body.extend_from_slice(b"\r\n--limes--\r\n");
+    Request::builder()
+        .method("POST")
+        .uri("/deployments")
+        .header(header::CONTENT_TYPE, "multipart/form-data; boundary=limes")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn manifest_http_deployment() {
+    let runtime = Arc::new(Runtime::default());
+    let router = api::router(runtime.clone());
+    let module = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+
+    let request = multipart("module = \"echo.wasm\"\ntimeout = \"5s\"\n", &module);
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(body["module_id"].is_string());
+    let function_id = body["function_id"].as_str().unwrap().to_string();
+    let result = runtime.exec_function(function_id, "").await.unwrap();
+    assert_eq!(result, "### TEST ###");
+
+    let request = multipart("module = \"echo.wasm\"\nworld = \"other\"\n", &module);
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Host directories are refused unless the server allows them
+    let request = multipart(
+        "module = \"echo.wasm\"\n[[mount]]\nhost = \"/tmp\"\nguest = \"/tmp\"\n",
+        &module,
+    );
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/deployments")
+        .header(header::CONTENT_TYPE, "multipart/form-data; boundary=limes")
+        .body(Body::from("--limes--\r\n"))
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,293 @@
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::tools::reconcile::{self, Change, Reconciler};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+// A fresh directory holding the test components
+fn desired_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("limes_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("echo")).unwrap();
+    for module in ["exec_rust_lambda_function.wasm", "stop_infinite_loop.wasm"] {
+        std::fs::copy(get_crate_path().join(module), dir.join(module)).unwrap();
+    }
+    dir
+}
+
+fn create(name: &str) -> Change {
+    Change::Create {
+        name: name.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn reconcile_plan_and_apply() {
+    let dir = desired_dir("reconcile");
+    std::fs::write(
+        dir.join("echo/limes.toml"),
+        "module = \"../exec_rust_lambda_function.wasm\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("looping.toml"),
+        "name = \"loop\"\nmodule = \"stop_infinite_loop.wasm\"\ntimeout = \"100ms\"\n",
+    )
+    .unwrap();
+
+    let runtime = Arc::new(Runtime::default());
+    let mut reconciler = Reconciler::new(runtime.clone(), Ipv4Addr::LOCALHOST);
+    let plan = reconciler.plan(&dir).unwrap();
+    assert_eq!(plan.changes, vec![create("echo"), create("loop")]);
+    assert!(plan
+        .to_string()
+        .ends_with("2 to create, 0 to update, 0 to remove, 0 unchanged"));
+    let report = reconciler.apply(plan).await;
+    assert_eq!(report.applied.len(), 2);
+    assert!(report.failed.is_empty());
+
+    let echo = reconciler.deployments()["echo"].clone();
+    let result = runtime.exec_function(echo.function_id.clone(), "").await;
+    assert_eq!(result.unwrap(), "### TEST ###");
+    let plan = reconciler.plan(&dir).unwrap();
+    assert!(plan.is_empty());
+    assert_eq!(plan.unchanged, vec!["echo", "loop"]);
+
+    // A manifest change updates the settings of the function on the same module
+    std::fs::write(
+        dir.join("echo/limes.toml"),
+        "module = \"../exec_rust_lambda_function.wasm\"\ntimeout = \"5s\"\n",
+    )
+    .unwrap();
+    let plan = reconciler.plan(&dir).unwrap();
+    assert_eq!(
+        plan.changes,
+        vec![Change::Update {
+            name: "echo".to_string(),
+            module_changed: false
+        }]
+    );
+    reconciler.apply(plan).await;
+    let updated = reconciler.deployments()["echo"].clone();
+    assert_eq!(updated.function_id, echo.function_id);
+    assert_eq!(updated.module_id, echo.module_id);
+    let info = runtime
+        .function_info(echo.function_id.clone())
+        .await
+        .unwrap();
+    assert_eq!(info.timeout_ms, Some(5000));
+    let result = runtime.exec_function(updated.function_id, "").await;
+    assert_eq!(result.unwrap(), "### TEST ###");
+
+    // A new component goes to a new module, a missing manifest removes its function
+    std::fs::copy(
+        dir.join("exec_rust_lambda_function.wasm"),
+        dir.join("stop_infinite_loop.wasm"),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(dir.join("echo")).unwrap();
+    let plan = reconciler.plan(&dir).unwrap();
+    assert_eq!(
+        plan.changes,
+        vec![
+            Change::Update {
+                name: "loop".to_string(),
+                module_changed: true
+            },
+            Change::Remove {
+                name: "echo".to_string()
+            }
+        ]
+    );
+    let looping = reconciler.deployments()["loop"].clone();
+    let report = reconciler.apply(plan).await;
+    assert!(report.failed.is_empty());
+    let deployments = reconciler.deployments();
+    assert_eq!(deployments.len(), 1);
+    assert_eq!(deployments["loop"].function_id, looping.function_id);
+    assert_ne!(deployments["loop"].module_id, looping.module_id);
+    assert_eq!(runtime.list_modules().await.len(), 1);
+    let result = runtime
+        .exec_function(deployments["loop"].function_id.clone(), "")
+        .await;
+    assert_eq!(result.unwrap(), "### TEST ###");
+    let result = runtime.exec_function(echo.function_id, "").await;
+    assert_eq!(result, Err(RuntimeError::FunctionNotRegistered));
+}
+
+#[tokio::test]
+async fn reconcile_updates_keep_the_function_namespace() {
+    let dir = desired_dir("reconcile_kv");
+    let counter = get_crate_path().join("../kv_counter/kv_counter.wat");
+    std::fs::write(dir.join("counter.wasm"), wat::parse_file(counter).unwrap()).unwrap();
+    let write_manifest = |extra: &str| {
+        let text = format!("name = \"counter\"\nmodule = \"counter.wasm\"\n{}", extra);
+        std::fs::write(dir.join("counter.toml"), text).unwrap();
+    };
+    write_manifest("version = \"1\"");
+
+    let runtime = Arc::new(Runtime::default());
+    let mut reconciler = Reconciler::new(runtime.clone(), Ipv4Addr::LOCALHOST);
+    reconciler.reconcile(&dir).await.unwrap();
+    let function_id = reconciler.deployments()["counter"].function_id.clone();
+    let exec = |args: &'static str| runtime.exec_function(function_id.clone(), args);
+    assert_eq!(exec("calls").await.unwrap(), "1");
+
+    // A new version is a new module, the count carries over along with the function id
+    write_manifest("version = \"2\"");
+    let plan = reconciler.plan(&dir).unwrap();
+    assert_eq!(
+        plan.changes,
+        vec![Change::Update {
+            name: "counter".to_string(),
+            module_changed: true
+        }]
+    );
+    let report = reconciler.apply(plan).await;
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert_eq!(exec("calls").await.unwrap(), "2");
+    let module_id = runtime.resolve_module("counter@latest").unwrap();
+    assert_eq!(reconciler.deployments()["counter"].module_id, module_id);
+
+    write_manifest("version = \"2\"\ntimeout = \"5s\"");
+    reconciler.reconcile(&dir).await.unwrap();
+    assert_eq!(exec("calls").await.unwrap(), "3");
+    assert_eq!(
+        runtime.kv_store().namespaces(),
+        vec![format!("function/{}", function_id)]
+    );
+}
+
+#[tokio::test]
+async fn reconcile_recreates_functions_at_the_cap() {
+    let dir = desired_dir("reconcile_cap");
+    let manifest = dir.join("echo.toml");
+    std::fs::write(&manifest, "module = \"exec_rust_lambda_function.wasm\"").unwrap();
+    let runtime = Arc::new(Runtime::new().set_max_functions_number(1).build().unwrap());
+    let mut reconciler = Reconciler::new(runtime.clone(), Ipv4Addr::LOCALHOST);
+
+    // Every removal gives the slot back to the next create
+    for _ in 0..3 {
+        let report = reconciler.reconcile(&dir).await.unwrap();
+        assert_eq!(report.applied, vec![create("echo")], "{:?}", report.failed);
+        std::fs::rename(&manifest, dir.join("echo.off")).unwrap();
+        let report = reconciler.reconcile(&dir).await.unwrap();
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        std::fs::rename(dir.join("echo.off"), &manifest).unwrap();
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn reconcile_retries_failed_module_removals() {
+    let dir = desired_dir("reconcile_cleanup");
+    let counter = get_crate_path().join("../kv_counter/kv_counter.wat");
+    std::fs::write(dir.join("counter.wasm"), wat::parse_file(counter).unwrap()).unwrap();
+    let write_manifest = |version: &str| {
+        let text = format!(
+            "name = \"counter\"\nmodule = \"counter.wasm\"\nversion = \"{}\"",
+            version
+        );
+        std::fs::write(dir.join("counter.toml"), text).unwrap();
+    };
+    write_manifest("1");
+    let runtime = Arc::new(Runtime::default());
+    let mut reconciler = Reconciler::new(runtime.clone(), Ipv4Addr::LOCALHOST);
+    reconciler.reconcile(&dir).await.unwrap();
+
+    // Another function storing in the namespace of the module keeps it from being removed
+    let old = reconciler.deployments()["counter"].clone();
+    let module_scope =
+        FunctionManifest::from_toml("module = \"counter.wasm\"\n[kv]\nscope = \"module\"").unwrap();
+    let holder = runtime
+        .init_function_with_manifest(old.module_id.clone(), &module_scope)
+        .await
+        .unwrap();
+
+    // The update itself went through, the old module is removed once it can be
+    write_manifest("2");
+    let report = reconciler.reconcile(&dir).await.unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    let updated = reconciler.deployments()["counter"].clone();
+    assert_eq!(updated.function_id, old.function_id);
+    assert_ne!(updated.module_id, old.module_id);
+    assert_eq!(runtime.list_modules().await.len(), 2);
+
+    // A failed removal keeps the function planned for removal
+    std::fs::remove_file(dir.join("counter.toml")).unwrap();
+    let holder_of_new = runtime
+        .init_function_with_manifest(updated.module_id.clone(), &module_scope)
+        .await
+        .unwrap();
+    let report = reconciler.reconcile(&dir).await.unwrap();
+    assert_eq!(report.failed.len(), 1);
+    assert!(reconciler.deployments().contains_key("counter"));
+
+    assert!(runtime.remove_function(holder).await);
+    assert!(runtime.remove_function(holder_of_new).await);
+    let report = reconciler.reconcile(&dir).await.unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert!(reconciler.deployments().is_empty());
+    assert!(runtime.list_modules().await.is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reconcile_invalid_dir() {
+    let dir = desired_dir("reconcile_invalid");
+    std::fs::write(
+        dir.join("echo/limes.toml"),
+        "module = \"../exec_rust_lambda_function.wasm\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("other.toml"),
+        "name = \"echo\"\nmodule = \"exec_rust_lambda_function.wasm\"\n",
+    )
+    .unwrap();
+    let error = reconcile::load_dir(&dir).unwrap_err();
+    assert!(error.to_string().contains("declared by both"));
+
+    std::fs::write(dir.join("other.toml"), "module = \"missing.wasm\"\n").unwrap();
+    assert!(reconcile::load_dir(&dir).is_err());
+    std::fs::write(dir.join("other.toml"), "modul = \"typo.wasm\"\n").unwrap();
+    assert!(reconcile::load_dir(&dir).is_err());
+    assert!(reconcile::load_dir(&dir.join("missing")).is_err());
+}
+
+#[tokio::test]
+async fn reconcile_watch() {
+    let dir = desired_dir("reconcile_watch");
+    let runtime = Arc::new(Runtime::default());
+    let mut reconciler = Reconciler::new(runtime.clone(), Ipv4Addr::LOCALHOST);
+    let watched = dir.clone();
+    tokio::spawn(async move {
+        reconciler
+            .watch(&watched, Duration::from_millis(100), false)
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    std::fs::write(
+        dir.join("echo/limes.toml"),
+        "module = \"../exec_rust_lambda_function.wasm\"\n",
+    )
+    .unwrap();
+
+    let start = Instant::now();
+    loop {
+        let metrics = runtime.metrics().encode();
+        if metrics.contains("limes_module_registrations_total{") {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files/wasm_compiled")
+}
+
+// Runs `limes run <module> <args>` with `stdin` piped in
+fn limes_run(module: &str, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_limes"))
+        .arg("run")
+        .arg(get_crate_path().join(module))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn run_prints_the_result() {
+    let output = limes_run("exec_rust_lambda_function.wasm", &["--arg", "Hello"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hello### TEST ###");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("execute"));
+
+    let output = limes_run("exec_rust_lambda_function.wasm", &["--arg", "-"], b"piped");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "piped### TEST ###");
+}
+
+#[test]
+fn run_reports_stdio_and_usage_as_json() {
+    let output = limes_run(
+        "tcp_udp_bind_to_not_allowed_ip.wasm",
+        &["--arg", "TCP,127.0.0.1:0", "--format", "json"],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(5));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["exit_code"], 5);
+    assert!(report["result"].is_null());
+    assert!(report["usage"]["trap"].is_string());
+    assert!(report["stderr"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|line| line.as_str().unwrap().contains("panicked")));
+
+    let output = limes_run(
+        "tcp_udp_bind_to_not_allowed_ip.wasm",
+        &[
+            "--arg",
+            "TCP,127.0.0.1:0",
+            "--allow-bind",
+            "127.0.0.1",
+            "--format",
+            "json",
+        ],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["result"], "### TCP ###");
+}
+
+#[test]
+fn run_exit_codes() {
+    let output = limes_run("stop_infinite_loop.wasm", &["--timeout", "300ms"], b"");
+    assert_eq!(output.status.code(), Some(6));
+
+    let output = limes_run("exec_rust_lambda_function.wasm", &["--mem", "1M"], b"");
+    assert_eq!(output.status.code(), Some(7));
+
+    let output = limes_run("exec_rust_lambda_function.wasm", &["--dir", "/tmp"], b"");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = limes_run("missing.wasm", &[], b"");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn run_keeps_the_kv_namespace_in_a_directory() {
+    let dir = std::env::temp_dir().join(format!("limes_run_kv_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let kv_dir = dir.to_str().unwrap();
+    for expected in ["1", "2"] {
+        let output = limes_run(
+            "../kv_counter/kv_counter.wat",
+            &["--arg", "calls", "--kv-dir", kv_dir],
+            b"",
+        );
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+    let output = limes_run("../kv_counter/kv_counter.wat", &["--arg", "calls"], b"");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1");
+    let _ = std::fs::remove_dir_all(&dir);
+}
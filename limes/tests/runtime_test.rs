@@ -1,8 +1,11 @@
+use limes::runtime::invocation::{InvocationReport, InvocationStatus};
+use limes::runtime::lambda::{InstanceMode, ResetPolicy, WarmPoolConfig};
 use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
 use std::net::Ipv4Addr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio;
+use std::time::{Duration, Instant};
 
 fn get_crate_path() -> PathBuf {
     let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -19,7 +22,6 @@ async fn create_runtime() {
         .set_total_memory_size(1024 * 1024 * 500)
         .build()
         .unwrap();
-    assert!(true);
 }
 
 #[tokio::test]
@@ -33,6 +35,26 @@ async fn register_and_remove_modules() {
     assert_eq!((), runtime.remove_module(module_id).await.unwrap());
 }
 
+#[tokio::test]
+async fn runtime_removed_functions_free_their_slot() {
+    let runtime = Runtime::new().set_max_functions_number(1).build().unwrap();
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let init = || runtime.init_function(module_id.clone(), Ipv4Addr::LOCALHOST);
+
+    let function_id = init().await.unwrap();
+    assert_eq!(
+        init().await,
+        Err(RuntimeError::MaxFunctionDeplaymentReached)
+    );
+    assert!(runtime.remove_function(function_id).await);
+    let function_id = init().await.unwrap();
+    assert_eq!(
+        runtime.exec_function(function_id, "").await.unwrap(),
+        "### TEST ###"
+    );
+}
+
 #[tokio::test]
 async fn runtime_run_functions() {
     let runtime = Runtime::default();
@@ -54,11 +76,275 @@ async fn runtime_run_functions() {
     assert_eq!(result, "### TEST ###");
 }
 
+#[tokio::test]
+async fn runtime_run_warm_function() {
+    let runtime = Runtime::default();
+    let bytes = load_file("exec_rust_lambda_function.wasm");
+    let module_id = runtime.register_module(bytes).await.unwrap();
+
+    let func_id = runtime
+        .init_function_with_mode(
+            module_id,
+            Ipv4Addr::new(127, 0, 0, 1),
+            InstanceMode::Warm(WarmPoolConfig::default()),
+        )
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let result = runtime.exec_function(func_id.clone(), "").await.unwrap();
+        assert_eq!(result, "### TEST ###");
+    }
+}
+
+#[tokio::test]
+async fn runtime_run_snapshot_function() {
+    let runtime = Runtime::default();
+    let file = get_crate_path().join("../stateful_counter/stateful_counter.wat");
+    let bytes = wat::parse_file(file).unwrap();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+
+    let policy = ResetPolicy::Snapshot { init_export: None };
+    let config = WarmPoolConfig::new(1, Duration::from_secs(60), 100, policy);
+    let func_id = runtime
+        .init_function_with_mode(
+            module_id,
+            Ipv4Addr::new(127, 0, 0, 1),
+            InstanceMode::Warm(config),
+        )
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let result = runtime.exec_function(func_id.clone(), "").await.unwrap();
+        assert_eq!(result, "11");
+    }
+}
+
+#[tokio::test]
+async fn runtime_run_preinitialized_function() {
+    let runtime = Runtime::default();
+    let file = get_crate_path().join("../stateful_counter/stateful_counter.wat");
+    let bytes = wat::parse_file(file).unwrap();
+
+    // The second registration reuses the cached pre-initialized bytes, the third one runs
+    // the init again once the modules using them were removed
+    for removed in [false, false, true] {
+        if removed {
+            for module in runtime.list_modules().await {
+                runtime.remove_module(module.module_id).await.unwrap();
+            }
+        }
+        let module_id = runtime
+            .register_module_with_preinit(bytes.clone(), "init")
+            .await
+            .unwrap();
+        let func_id = runtime
+            .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let result = runtime.exec_function(func_id.clone(), "").await.unwrap();
+            assert_eq!(result, "63");
+        }
+    }
+}
+
+#[tokio::test]
+async fn runtime_preinit_missing_export() {
+    let runtime = Runtime::default();
+    let file = get_crate_path().join("../stateful_counter/stateful_counter.wat");
+    let bytes = wat::parse_file(file).unwrap();
+    let result = runtime.register_module_with_preinit(bytes, "missing").await;
+    assert!(matches!(result, Err(RuntimeError::ModulePreinitError(_))));
+}
+
+#[tokio::test]
+async fn runtime_preinit_timeout() {
+    let runtime = Runtime::new()
+        .set_preinit_timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    let file = get_crate_path().join("../stateful_counter/stateful_counter.wat");
+    let text = std::fs::read_to_string(file).unwrap().replace(
+        "(func (export \"init\")",
+        "(func (export \"init\") (loop (br 0))",
+    );
+    let bytes = wat::parse_str(text).unwrap();
+
+    let start = Instant::now();
+    let result = runtime.register_module_with_preinit(bytes, "init").await;
+    match result {
+        Err(RuntimeError::ModulePreinitError(e)) => assert!(e.contains("did not finish")),
+        _ => panic!("Expected a pre-initialization timeout"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn runtime_preinit_real_component() {
+    let runtime = Runtime::default();
+    let bytes = load_file("multiple_function_exec.wasm");
+    // Instrumented and instantiated, then refused since the export is not a func()
+    let result = runtime
+        .register_module_with_preinit(bytes, "component:run/run")
+        .await;
+    match result {
+        Err(RuntimeError::ModulePreinitError(e)) => assert!(e.contains("has no")),
+        _ => panic!("Expected a pre-initialization error"),
+    }
+}
+
+async fn wait_invocation(runtime: &Runtime, invocation_id: &str) -> InvocationReport {
+    loop {
+        let report = runtime
+            .get_invocation(invocation_id.to_string())
+            .await
+            .unwrap();
+        if report.status.is_finished() {
+            return report;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn runtime_submit_function() {
+    let runtime = Runtime::default();
+    let bytes = load_file("exec_rust_lambda_function.wasm");
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    let invocation_id = runtime
+        .submit_function(func_id.clone(), String::new(), None)
+        .await
+        .unwrap();
+    let report = wait_invocation(&runtime, &invocation_id).await;
+    assert_eq!(report.status, InvocationStatus::Completed);
+    assert_eq!(report.function_id, func_id);
+    assert!(report.duration_ms.is_some());
+    assert!(report
+        .usage
+        .is_some_and(|usage| usage.peak_memory_bytes > 0));
+    assert_eq!(
+        runtime.invocation_result(invocation_id).await.unwrap(),
+        "### TEST ###"
+    );
+
+    let missing = runtime
+        .submit_function("missing".to_string(), String::new(), None)
+        .await;
+    assert_eq!(missing, Err(RuntimeError::FunctionNotRegistered));
+}
+
+#[tokio::test]
+async fn runtime_cancel_invocation() {
+    let runtime = Runtime::default();
+    let bytes = load_file("stop_infinite_loop.wasm");
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    let invocation_id = runtime
+        .submit_function(func_id.clone(), String::new(), None)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        runtime.invocation_result(invocation_id.clone()).await,
+        Err(RuntimeError::InvocationNotFinished)
+    );
+
+    runtime
+        .cancel_invocation(invocation_id.clone())
+        .await
+        .unwrap();
+    let report = runtime.get_invocation(invocation_id.clone()).await.unwrap();
+    assert_eq!(report.status, InvocationStatus::Cancelled);
+    assert_eq!(
+        runtime.invocation_result(invocation_id.clone()).await,
+        Err(RuntimeError::InvocationCancelled)
+    );
+    assert_eq!(
+        runtime.cancel_invocation(invocation_id).await,
+        Err(RuntimeError::InvocationAlreadyFinished)
+    );
+
+    // The function was not stopped, the guest was only dropped
+    let second = runtime
+        .submit_function(func_id, String::new(), None)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let report = runtime.get_invocation(second.clone()).await.unwrap();
+    assert_eq!(report.status, InvocationStatus::Running);
+    runtime.cancel_invocation(second).await.unwrap();
+}
+
+#[tokio::test]
+async fn runtime_invocation_retention() {
+    let runtime = Runtime::default();
+    let bytes = load_file("exec_rust_lambda_function.wasm");
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    let invocation_id = runtime
+        .submit_function(func_id, String::new(), Some(Duration::from_millis(200)))
+        .await
+        .unwrap();
+    wait_invocation(&runtime, &invocation_id).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(
+        runtime.get_invocation(invocation_id).await.unwrap_err(),
+        RuntimeError::InvocationNotFound
+    );
+}
+
+#[tokio::test]
+async fn runtime_trap_metrics() {
+    let runtime = Runtime::default();
+    let bytes = wat::parse_str(
+        r#"(component
+            (core module $m
+                (memory (export "memory") 1)
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 0)
+                (func (export "run") (param i32 i32) (result i32) unreachable))
+            (core instance $i (instantiate $m))
+            (func $run (param "args" string) (result string)
+                (canon lift (core func $i "run") (memory (core memory $i "memory"))
+                    (realloc (core func $i "realloc"))))
+            (instance $run-instance (export "run" (func $run)))
+            (export "component:run/run" (instance $run-instance)))"#,
+    )
+    .unwrap();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+
+    assert!(runtime.exec_function(func_id.clone(), "").await.is_err());
+    let metrics = runtime.metrics().encode();
+    let trap = format!(
+        r#"limes_traps_total{{function="{}",kind="UnreachableCodeReached""#,
+        func_id
+    );
+    assert!(metrics.contains(&trap));
+}
+
 fn load_file(name: &str) -> Vec<u8> {
     let wasm_path = get_crate_path();
     let file_path = wasm_path.join(name);
-    let file_bytes = std::fs::read(file_path).unwrap();
-    file_bytes
+
+    std::fs::read(file_path).unwrap()
 }
 
 #[tokio::test]
@@ -0,0 +1,381 @@
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use http_body_util::BodyExt;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::webhook::{self, WebhookConfig};
+use limes::server::api::{self, ApiOptions};
+use serde_json::Value;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let wasm_path = Path::new(&crate_dir).join(Path::new(
+        "resources/wasm_wasi_module_test_files/wasm_compiled",
+    ));
+    wasm_path
+}
+
+async fn send(router: &Router, method: &str, uri: &str, body: Body) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(body)
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, value)
+}
+
+async fn deploy(router: &Router, name: &str) -> String {
+    let bytes = std::fs::read(get_crate_path().join(name)).unwrap();
+    let (status, body) = send(router, "POST", "/modules", Body::from(bytes)).await;
+    assert_eq!(status, StatusCode::OK);
+    let module_id = body["module_id"].as_str().unwrap();
+
+    let uri = format!("/modules/{}/functions", module_id);
+    let (status, body) = send(router, "POST", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    body["function_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn server_exec_function() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let func_id = deploy(&router, "exec_rust_lambda_function.wasm").await;
+
+    let uri = format!("/functions/{}/exec", func_id);
+    let (status, body) = send(&router, "POST", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "### TEST ###");
+    assert!(body["usage"]["execute_us"].is_u64());
+    assert!(body["usage"]["peak_memory_bytes"].as_u64().unwrap() > 0);
+
+    let (status, _) = send(&router, "POST", "/functions/missing/exec", Body::empty()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn server_async_invocation() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let func_id = deploy(&router, "exec_rust_lambda_function.wasm").await;
+
+    let uri = format!("/functions/{}/invocations", func_id);
+    let request = Body::from(r#"{"args": "", "retention_secs": 60}"#);
+    let (status, body) = send(&router, "POST", &uri, request).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let invocation_id = body["invocation_id"].as_str().unwrap().to_string();
+
+    let uri = format!("/invocations/{}", invocation_id);
+    loop {
+        let (status, body) = send(&router, "GET", &uri, Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        if body["status"] == "completed" {
+            assert_eq!(body["result"], "### TEST ###");
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let uri = format!("/invocations/{}/result", invocation_id);
+    let (status, body) = send(&router, "GET", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "### TEST ###");
+
+    let uri = format!("/invocations/{}", invocation_id);
+    let (status, _) = send(&router, "DELETE", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn server_invocation_callback() {
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let receiver = Router::new().route(
+        "/callback",
+        post(move |headers: HeaderMap, body: Bytes| async move {
+            sender.send((headers, body)).unwrap();
+            StatusCode::OK
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let callback_url = format!("http://{}/callback", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+    let config = WebhookConfig::new(
+        0,
+        Duration::ZERO,
+        Duration::ZERO,
+        Some("secret".to_string()),
+    );
+    let runtime = Runtime::new().set_webhook_config(config).build().unwrap();
+    let router = api::router(Arc::new(runtime));
+    let func_id = deploy(&router, "exec_rust_lambda_function.wasm").await;
+
+    let uri = format!("/functions/{}/invocations", func_id);
+    for url in ["not a url", "ftp://127.0.0.1/callback", "unix:/tmp/socket"] {
+        let request = serde_json::json!({ "callback_url": url }).to_string();
+        let (status, _) = send(&router, "POST", &uri, Body::from(request)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", url);
+    }
+
+    let request = serde_json::json!({ "callback_url": callback_url }).to_string();
+    let (status, body) = send(&router, "POST", &uri, Body::from(request)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let invocation_id = body["invocation_id"].as_str().unwrap();
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(10), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(headers[webhook::INVOCATION_HEADER], invocation_id);
+    assert_eq!(
+        headers[webhook::SIGNATURE_HEADER],
+        format!("sha256={}", webhook::sign("secret", &body)).as_str()
+    );
+    let report: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["status"], "completed");
+    assert_eq!(report["result"], "### TEST ###");
+}
+
+#[tokio::test]
+async fn server_cancel_invocation() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let func_id = deploy(&router, "stop_infinite_loop.wasm").await;
+
+    let uri = format!("/functions/{}/invocations", func_id);
+    let (status, body) = send(&router, "POST", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let invocation_id = body["invocation_id"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let uri = format!("/invocations/{}/result", invocation_id);
+    let (status, _) = send(&router, "GET", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let uri = format!("/invocations/{}", invocation_id);
+    let (status, _) = send(&router, "DELETE", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send(&router, "GET", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "cancelled");
+}
+
+#[tokio::test]
+async fn server_metrics() {
+    let runtime = Runtime::new().set_metrics_label_cap(1).build().unwrap();
+    let router = api::router(Arc::new(runtime));
+    let first = deploy(&router, "exec_rust_lambda_function.wasm").await;
+    let _second = deploy(&router, "exec_rust_lambda_function.wasm").await;
+
+    let uri = format!("/functions/{}/exec", first);
+    let (status, _) = send(&router, "POST", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(bytes.to_vec()).unwrap();
+
+    // The second module is past the cardinality cap
+    assert!(text.contains(r#"limes_module_registrations_total{module="other"} 1"#));
+    assert!(text.contains("limes_module_compile_seconds_count"));
+    assert!(text.contains(&format!(r#"function="{}""#, first)));
+    assert!(text.contains("limes_execute_seconds_bucket"));
+    assert!(text.contains("limes_instantiate_seconds_sum"));
+    assert!(text.contains(r#"limes_active_invocations{engine="0"} 0"#));
+    assert!(text.contains("limes_queue_depth 0"));
+    let reserved = format!("limes_memory_reserved_bytes {}", 2 * 1024 * 1024 * 2);
+    assert!(text.contains(&reserved));
+}
+
+#[tokio::test]
+async fn server_metrics_release_removed_ids() {
+    let runtime = Runtime::new().set_metrics_label_cap(1).build().unwrap();
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let mut removed: Option<(String, String)> = None;
+    for _ in 0..3 {
+        let module_id = runtime.register_module(bytes.clone()).await.unwrap();
+        let function_id = runtime
+            .init_function(module_id.clone(), Ipv4Addr::LOCALHOST)
+            .await
+            .unwrap();
+        runtime
+            .exec_function(function_id.clone(), "")
+            .await
+            .unwrap();
+
+        // Removed ids give their label and their series back to the next ones
+        let text = runtime.metrics().encode();
+        assert!(text.contains(&format!(r#"module="{}""#, module_id)));
+        assert!(text.contains(&format!(r#"function="{}""#, function_id)));
+        assert!(!text.contains(r#"function="other""#));
+        if let Some((module_id, function_id)) = removed.take() {
+            assert!(!text.contains(&module_id) && !text.contains(&function_id));
+        }
+        assert!(runtime.remove_function(function_id.clone()).await);
+        runtime.remove_module(module_id.clone()).await.unwrap();
+        removed = Some((module_id, function_id));
+    }
+}
+
+#[tokio::test]
+async fn server_validate_and_inspect_module() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let (status, body) = send(&router, "POST", "/modules/validate", Body::from("garbage")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let (status, body) = send(
+        &router,
+        "POST",
+        "/modules/validate",
+        Body::from(bytes.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["errors"], serde_json::json!([]));
+    assert_eq!(body["runnable"], true);
+
+    let (_, body) = send(&router, "POST", "/modules", Body::from(bytes)).await;
+    let uri = format!("/modules/{}", body["module_id"].as_str().unwrap());
+    let (status, body) = send(&router, "GET", &uri, Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["exports"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|export| export["name"] == "component:run/run" && export["kind"] == "instance"));
+
+    let (status, _) = send(&router, "GET", "/modules/missing", Body::empty()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn server_accepts_components_over_two_mib() {
+    let path = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("resources/benchmarks/mandelbrotset/mandelbrotset.wasm");
+    let bytes = std::fs::read(path).unwrap();
+    assert!(bytes.len() > 2 * 1024 * 1024);
+    let router = api::router(Arc::new(Runtime::default()));
+    let (status, _) = send(&router, "POST", "/modules", Body::from(bytes.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The limit is configurable
+    let options = ApiOptions {
+        max_upload_size: 1024 * 1024,
+        ..ApiOptions::default()
+    };
+    let router = api::router_with_options(Arc::new(Runtime::default()), options);
+    let (status, _) = send(&router, "POST", "/modules", Body::from(bytes)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+// A failed limes-guest function reports its error code and message
+#[tokio::test]
+async fn server_reports_guest_errors() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let func_id = deploy(&router, "guest_sdk.wasm").await;
+
+    let uri = format!("/functions/{}/exec", func_id);
+    let (status, body) = send(&router, "POST", &uri, Body::from("jump")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["guest_error"]["code"], "invalid_args");
+    assert_eq!(body["guest_error"]["message"], "unknown command `jump`");
+}
+
+#[tokio::test]
+async fn server_adapts_wasip1_modules() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let bytes = std::fs::read(get_crate_path().join("wasip1_function.wasm")).unwrap();
+    let (status, _) = send(&router, "POST", "/modules", Body::from(bytes.clone())).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let uri = "/modules?adapt_wasip1=true";
+    let (status, body) = send(&router, "POST", uri, Body::from(bytes)).await;
+    assert_eq!(status, StatusCode::OK);
+    let uri = format!("/modules/{}/functions", body["module_id"].as_str().unwrap());
+    let (_, body) = send(&router, "POST", &uri, Body::empty()).await;
+    let uri = format!("/functions/{}/exec", body["function_id"].as_str().unwrap());
+    let (status, body) = send(&router, "POST", &uri, Body::from("api")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"], "wasip1 api");
+}
+
+#[tokio::test]
+async fn server_publishes_versions_and_updates_functions() {
+    let router = api::router(Arc::new(Runtime::default()));
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let (_, body) = send(&router, "POST", "/modules", Body::from(bytes)).await;
+    let module_id = body["module_id"].as_str().unwrap().to_string();
+
+    let publish = serde_json::json!({ "module_id": module_id }).to_string();
+    let uri = "/names/test/versions/1.0.0";
+    let (status, body) = send(&router, "PUT", uri, Body::from(publish.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["aliases"]["latest"], "1.0.0");
+    let (status, _) = send(&router, "PUT", "/names/test/versions/1.0.0", Body::empty()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(
+        &router,
+        "PUT",
+        "/names/te!st/versions/1",
+        Body::from(publish),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let alias = serde_json::json!({ "version": "1.0.0" }).to_string();
+    let (status, body) = send(
+        &router,
+        "PUT",
+        "/names/test/aliases/stable",
+        Body::from(alias),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["aliases"]["stable"], "1.0.0");
+
+    // Functions are initialized from, and moved to, references
+    let (status, body) = send(
+        &router,
+        "POST",
+        "/modules/test@stable/functions",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let function_id = body["function_id"].as_str().unwrap().to_string();
+    let uri = format!("/functions/{}/module", function_id);
+    let update = serde_json::json!({ "module": "test@latest" }).to_string();
+    let (status, body) = send(&router, "PUT", &uri, Body::from(update)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["module_id"], module_id.as_str());
+    let update = serde_json::json!({ "module": "test@2.0.0" }).to_string();
+    let (status, _) = send(&router, "PUT", &uri, Body::from(update)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(
+        &router,
+        "DELETE",
+        "/names/test/aliases/stable",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send(&router, "GET", "/names/test", Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["versions"]["1.0.0"], module_id.as_str());
+    assert!(body["aliases"].get("stable").is_none());
+}
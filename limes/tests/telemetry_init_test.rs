@@ -0,0 +1,12 @@
+use limes::server::telemetry;
+
+// Installs global state, so it runs in its own test binary
+#[tokio::test]
+async fn tracing_starts_after_the_logger() {
+    // As the server does: the logger first, then the subscriber exporting the spans
+    env_logger::try_init().unwrap();
+    let provider = telemetry::init_tracing("http://127.0.0.1:4318/v1/traces").unwrap();
+    log::info!("still logged through env_logger");
+    assert!(telemetry::init_tracing("http://127.0.0.1:4318/v1/traces").is_err());
+    let _ = provider.shutdown();
+}
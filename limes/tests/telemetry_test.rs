@@ -0,0 +1,155 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use limes::runtime::runtime::Runtime;
+use limes::server::api;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry::Value;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let wasm_path = Path::new(&crate_dir).join(Path::new(
+        "resources/wasm_wasi_module_test_files/wasm_compiled",
+    ));
+    wasm_path
+}
+
+// Spans go to an in memory exporter, for the current thread only
+fn setup() -> (
+    InMemorySpanExporter,
+    SdkTracerProvider,
+    tracing::subscriber::DefaultGuard,
+) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let guard = tracing::subscriber::set_default(subscriber);
+    (exporter, provider, guard)
+}
+
+fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|span| span.name == name)
+        .unwrap_or_else(|| panic!("Missing span {}", name))
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.clone())
+}
+
+#[tokio::test]
+async fn runtime_spans() {
+    let (exporter, provider, _guard) = setup();
+    let runtime = Runtime::default();
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id.clone(), Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+    runtime.exec_function(func_id.clone(), "").await.unwrap();
+    let invocation_id = runtime
+        .submit_function(func_id.clone(), String::new(), None)
+        .await
+        .unwrap();
+    while !runtime
+        .get_invocation(invocation_id.clone())
+        .await
+        .unwrap()
+        .status
+        .is_finished()
+    {
+        tokio::task::yield_now().await;
+    }
+    runtime.stop_function(func_id.clone()).await.unwrap();
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let register = find(&spans, "limes.register_module");
+    assert_eq!(
+        attribute(register, "module_id"),
+        Some(Value::from(module_id.clone()))
+    );
+    let compile = find(&spans, "limes.compile");
+    assert_eq!(compile.parent_span_id, register.span_context.span_id());
+
+    let init = find(&spans, "limes.init_function");
+    assert_eq!(
+        attribute(init, "function_id"),
+        Some(Value::from(func_id.clone()))
+    );
+
+    let exec = find(&spans, "limes.exec");
+    assert_eq!(exec.parent_span_id, opentelemetry::trace::SpanId::INVALID);
+    assert_eq!(attribute(exec, "module_id"), Some(Value::from(module_id)));
+    let run = find(&spans, "limes.lambda.run");
+    assert_eq!(run.parent_span_id, exec.span_context.span_id());
+    for phase in [
+        "limes.lambda.linker",
+        "limes.lambda.instantiate",
+        "limes.lambda.call",
+    ] {
+        assert_eq!(
+            find(&spans, phase).parent_span_id,
+            run.span_context.span_id()
+        );
+    }
+    find(&spans, "limes.stop_function");
+
+    let invocation = find(&spans, "limes.invocation");
+    assert_eq!(
+        attribute(invocation, "invocation_id"),
+        Some(Value::from(invocation_id.clone()))
+    );
+    let submitted = spans
+        .iter()
+        .find(|span| {
+            span.name == "limes.exec" && span.parent_span_id == invocation.span_context.span_id()
+        })
+        .unwrap();
+    assert_eq!(
+        attribute(submitted, "invocation_id"),
+        Some(Value::from(invocation_id))
+    );
+}
+
+#[tokio::test]
+async fn http_trace_context() {
+    let (exporter, provider, _guard) = setup();
+    let router = api::router(Arc::new(Runtime::default()));
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let request = Request::builder()
+        .method("POST")
+        .uri("/modules")
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", trace_id),
+        )
+        .body(Body::from(bytes))
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let http = find(&spans, "limes.http");
+    assert_eq!(http.span_context.trace_id().to_string(), trace_id);
+    assert_eq!(http.parent_span_id.to_string(), "00f067aa0ba902b7");
+    let register = find(&spans, "limes.register_module");
+    assert_eq!(register.span_context.trace_id().to_string(), trace_id);
+    assert_eq!(register.parent_span_id, http.span_context.span_id());
+}
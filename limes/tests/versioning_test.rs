@@ -0,0 +1,251 @@
+use limes::runtime::lambda_error::LambdaError;
+use limes::runtime::manifest::FunctionManifest;
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use limes::runtime::versions::LATEST_ALIAS;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files")
+}
+
+// Prints "hello " and its args, "spin" loops forever
+fn echo_bytes() -> Vec<u8> {
+    wat::parse_file(get_crate_path().join("command/echo.wat")).unwrap()
+}
+
+// Returns "### TEST ###"
+fn test_bytes() -> Vec<u8> {
+    std::fs::read(get_crate_path().join("wasm_compiled/exec_rust_lambda_function.wasm")).unwrap()
+}
+
+#[tokio::test]
+async fn versioning_publishes_and_resolves_names() {
+    let runtime = Runtime::default();
+    let echo = runtime.register_module(echo_bytes()).await.unwrap();
+    let test = runtime.register_module(test_bytes()).await.unwrap();
+
+    runtime.publish_module(&echo, "greet", "1.0.0").unwrap();
+    let name = runtime.publish_module(&test, "greet", "2.0.0").unwrap();
+    assert_eq!(name.versions["1.0.0"], echo);
+    assert_eq!(name.aliases[LATEST_ALIAS], "2.0.0");
+    assert_eq!(runtime.resolve_module("greet@latest").unwrap(), test);
+    assert_eq!(runtime.resolve_module("greet@1.0.0").unwrap(), echo);
+    assert_eq!(runtime.resolve_module(&echo).unwrap(), echo);
+    assert_eq!(
+        runtime.resolve_module("greet@3.0.0"),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+
+    // Publishing again is a no-op, a version never changes module
+    assert!(runtime.publish_module(&echo, "greet", "1.0.0").is_ok());
+    assert_eq!(
+        runtime.publish_module(&test, "greet", "1.0.0"),
+        Err(RuntimeError::ModuleVersionExists("greet@1.0.0".to_string()))
+    );
+    assert!(matches!(
+        runtime.publish_module(&echo, "gr eet", "1"),
+        Err(RuntimeError::ModuleNameError(_))
+    ));
+    assert!(matches!(
+        runtime.publish_module(&echo, "greet", LATEST_ALIAS),
+        Err(RuntimeError::ModuleNameError(_))
+    ));
+    assert_eq!(
+        runtime.publish_module(&"missing".to_string(), "greet", "3.0.0"),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+
+    // Aliases point to versions and can be moved
+    runtime
+        .set_module_alias("greet", "stable", "1.0.0")
+        .unwrap();
+    assert_eq!(runtime.resolve_module("greet@stable").unwrap(), echo);
+    runtime
+        .set_module_alias("greet", "stable", "2.0.0")
+        .unwrap();
+    assert_eq!(runtime.resolve_module("greet@stable").unwrap(), test);
+    assert!(matches!(
+        runtime.set_module_alias("greet", "1.0.0", "2.0.0"),
+        Err(RuntimeError::ModuleNameError(_))
+    ));
+    assert_eq!(
+        runtime.set_module_alias("greet", "beta", "9.9.9"),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+    runtime.remove_module_alias("greet", "stable").unwrap();
+    assert_eq!(
+        runtime.resolve_module("greet@stable"),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+
+    // Functions are initialized from references
+    let function_id = runtime
+        .init_function("greet@1.0.0".to_string(), Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    assert_eq!(
+        runtime.exec_function(function_id, "you").await.unwrap(),
+        "hello you"
+    );
+    assert_eq!(
+        runtime
+            .init_function("greet@beta".to_string(), Ipv4Addr::LOCALHOST)
+            .await,
+        Err(RuntimeError::ComponentNotFound)
+    );
+
+    // Removing a module drops its versions and the aliases pointing to them
+    let modules = runtime.list_modules().await;
+    let listed = modules.iter().find(|m| m.module_id == test).unwrap();
+    assert_eq!(listed.versions, vec!["greet@2.0.0"]);
+    runtime.remove_module(test).await.unwrap();
+    let name = runtime.module_name("greet").unwrap();
+    assert_eq!(name.versions.len(), 1);
+    assert!(name.aliases.is_empty());
+    runtime.remove_module(echo).await.unwrap();
+    assert_eq!(
+        runtime.module_name("greet"),
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+}
+
+#[tokio::test]
+async fn versioning_deploys_published_manifests() {
+    let runtime = Runtime::default();
+    let manifest = FunctionManifest::from_toml(
+        "name = \"greet\"\nversion = \"1.0.0\"\nmodule = \"echo.wasm\"",
+    )
+    .unwrap();
+    let deployment = runtime.deploy(&manifest, echo_bytes()).await.unwrap();
+    assert_eq!(
+        runtime.resolve_module("greet@latest").unwrap(),
+        deployment.module_id
+    );
+
+    // A version needs a name, an invalid one removes the module again
+    assert!(matches!(
+        FunctionManifest::from_toml("version = \"1.0.0\"\nmodule = \"echo.wasm\""),
+        Err(RuntimeError::ManifestError(_))
+    ));
+    let manifest =
+        FunctionManifest::from_toml("name = \"my greet\"\nversion = \"1\"\nmodule = \"test.wasm\"")
+            .unwrap();
+    assert!(matches!(
+        runtime.deploy(&manifest, test_bytes()).await,
+        Err(RuntimeError::ModuleNameError(_))
+    ));
+    assert_eq!(runtime.list_modules().await.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn versioning_updates_functions_without_downtime() {
+    let runtime = Arc::new(Runtime::default());
+    let echo = runtime.register_module(echo_bytes()).await.unwrap();
+    let test = runtime.register_module(test_bytes()).await.unwrap();
+    runtime.publish_module(&echo, "greet", "1.0.0").unwrap();
+    runtime.publish_module(&test, "greet", "2.0.0").unwrap();
+    runtime
+        .set_module_alias("greet", "stable", "1.0.0")
+        .unwrap();
+
+    let manifest = FunctionManifest::from_toml("module = \"echo.wasm\"\ntimeout = \"1s\"").unwrap();
+    let function_id = runtime
+        .init_function_with_manifest("greet@stable".to_string(), &manifest)
+        .await
+        .unwrap();
+    let running = runtime.clone();
+    let spinning = function_id.clone();
+    let call = tokio::spawn(async move { running.exec_function(spinning, "spin").await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The update does not wait for the running call, which ends on the previous module
+    let updated = tokio::time::timeout(
+        Duration::from_millis(500),
+        runtime.update_function(function_id.clone(), "greet@2.0.0"),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(updated, test);
+    assert_eq!(
+        runtime
+            .exec_function(function_id.clone(), "")
+            .await
+            .unwrap(),
+        "### TEST ###"
+    );
+    assert_eq!(
+        call.await.unwrap(),
+        Err(RuntimeError::FunctionExecError(
+            LambdaError::Timeout.to_string()
+        ))
+    );
+    let info = runtime.function_info(function_id.clone()).await.unwrap();
+    assert_eq!(info.module_id, test);
+    assert_eq!(info.timeout_ms, Some(1000));
+
+    // Moving the function back
+    runtime
+        .update_function(function_id.clone(), "greet@stable")
+        .await
+        .unwrap();
+    assert_eq!(
+        runtime
+            .exec_function(function_id.clone(), "again")
+            .await
+            .unwrap(),
+        "hello again"
+    );
+    assert_eq!(
+        runtime
+            .update_function(function_id.clone(), "greet@3.0.0")
+            .await,
+        Err(RuntimeError::ModuleNotRegistered)
+    );
+    runtime.stop_function(function_id.clone()).await.unwrap();
+    assert!(matches!(
+        runtime.update_function(function_id, "greet@2.0.0").await,
+        Err(RuntimeError::FunctionInitError(_))
+    ));
+}
+
+#[tokio::test]
+async fn versioning_moves_module_namespaces_with_updates() {
+    let runtime = Runtime::default();
+    let counter = || wat::parse_file(get_crate_path().join("kv_counter/kv_counter.wat")).unwrap();
+    let v1 = runtime.register_module(counter()).await.unwrap();
+    let v2 = runtime.register_module(counter()).await.unwrap();
+    runtime.publish_module(&v1, "counter", "1").unwrap();
+    runtime.publish_module(&v2, "counter", "2").unwrap();
+
+    let manifest =
+        FunctionManifest::from_toml("module = \"counter.wasm\"\n[kv]\nscope = \"module\"").unwrap();
+    let function_id = runtime
+        .init_function_with_manifest("counter@1".to_string(), &manifest)
+        .await
+        .unwrap();
+    let exec = |args: &'static str| runtime.exec_function(function_id.clone(), args);
+    assert_eq!(exec("calls").await.unwrap(), "1");
+
+    // The namespace of v1 can not be dropped while the function stores in it
+    assert_eq!(
+        runtime.remove_module(v1.clone()).await,
+        Err(RuntimeError::ModuleNamespaceInUse(function_id.clone()))
+    );
+
+    // After the update the function stores in the namespace of v2, v1 goes with its namespace
+    runtime
+        .update_function(function_id.clone(), "counter@2")
+        .await
+        .unwrap();
+    assert_eq!(exec("calls").await.unwrap(), "1");
+    runtime.remove_module(v1).await.unwrap();
+    let namespaces = runtime.kv_store().namespaces();
+    assert_eq!(namespaces, vec![format!("module/{}", v2)]);
+    assert_eq!(exec("calls").await.unwrap(), "2");
+}
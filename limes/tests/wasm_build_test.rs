@@ -0,0 +1,138 @@
+use limes::runtime::runtime::Runtime;
+use limes::runtime::runtime_error::RuntimeError;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+fn get_guests_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/limes_macros_test")
+}
+
+// The guests written with #[limes::function], as built in `compiled`
+#[tokio::test]
+async fn limes_function_components() {
+    run_limes_function_components(&get_guests_path().join("compiled")).await;
+}
+
+// Builds the guests for wasm32-wasip2 and runs them. Needs the target, `rustup target add
+// wasm32-wasip2`, and wit-bindgen: `cargo test -- --ignored`
+#[tokio::test]
+#[ignore]
+async fn limes_function_components_rebuilt() {
+    let guests = get_guests_path();
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = std::process::Command::new(cargo)
+        .args(["build", "--release", "--target", "wasm32-wasip2"])
+        .current_dir(&guests)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    run_limes_function_components(&guests.join("target/wasm32-wasip2/release")).await;
+}
+
+async fn run_limes_function_components(dir: &Path) {
+    let runtime = Runtime::default();
+    let mut functions = Vec::new();
+    for name in ["text_function", "bytes_function", "result_function"] {
+        let path = dir.join(format!("{}.wasm", name));
+        let module_id = runtime
+            .register_module(std::fs::read(path).unwrap())
+            .await
+            .unwrap();
+        let function_id = runtime
+            .init_function(module_id, Ipv4Addr::LOCALHOST)
+            .await
+            .unwrap();
+        functions.push(function_id);
+    }
+
+    let result = runtime.exec_function(functions[0].clone(), "limes").await;
+    assert_eq!(result.unwrap(), "Hello, limes");
+    let logs = runtime
+        .function_logs(functions[0].clone(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(logs[0].line, "greeting limes");
+
+    let result = runtime.exec_function(functions[1].clone(), "abc").await;
+    assert_eq!(result.unwrap(), "Y2Jh");
+
+    let result = runtime.exec_function(functions[2].clone(), "21").await;
+    assert_eq!(result.unwrap(), "42");
+    let result = runtime.exec_function(functions[2].clone(), "NaN").await;
+    assert!(result.is_err());
+}
+
+fn get_fixtures_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    Path::new(&crate_dir).join("resources/wasm_wasi_module_test_files")
+}
+
+// The fixtures written with limes-guest, as built in wasm_compiled
+#[tokio::test]
+async fn limes_guest_fixtures() {
+    let compiled = get_fixtures_path().join("wasm_compiled");
+    let read = |name: &str| std::fs::read(compiled.join(format!("{}.wasm", name))).unwrap();
+    run_limes_guest_fixtures(
+        read("exec_rust_lambda_function"),
+        read("tcp_udp_bind_to_not_allowed_ip"),
+    )
+    .await;
+}
+
+// Builds the fixtures for wasm32-wasip2 and runs them, `cargo test -- --ignored`
+#[tokio::test]
+#[ignore]
+async fn limes_guest_fixtures_rebuilt() {
+    run_limes_guest_fixtures(
+        build_fixture("exec_rust_lambda_function"),
+        build_fixture("tcp_udp_bind_to_not_allowed_ip"),
+    )
+    .await;
+}
+
+// Builds the fixture `name` for wasm32-wasip2, returns its component
+fn build_fixture(name: &str) -> Vec<u8> {
+    let fixture = get_fixtures_path().join(name);
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = std::process::Command::new(cargo)
+        .args(["build", "--release", "--target", "wasm32-wasip2"])
+        .current_dir(&fixture)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::read(
+        fixture
+            .join("target/wasm32-wasip2/release")
+            .join(format!("{}.wasm", name)),
+    )
+    .unwrap()
+}
+
+async fn run_limes_guest_fixtures(exec_bytes: Vec<u8>, tcp_udp_bytes: Vec<u8>) {
+    let runtime = Runtime::default();
+    let module_id = runtime.register_module(exec_bytes).await.unwrap();
+    let function_id = runtime
+        .init_function(module_id, Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    let result = runtime.exec_function(function_id, "Hello").await;
+    assert_eq!(result.unwrap(), "Hello### TEST ###");
+
+    let module_id = runtime.register_module(tcp_udp_bytes).await.unwrap();
+    let function_id = runtime
+        .init_function(module_id, Ipv4Addr::LOCALHOST)
+        .await
+        .unwrap();
+    let result = runtime
+        .exec_function(function_id.clone(), "TCP,127.0.0.1:0")
+        .await;
+    assert_eq!(result.unwrap(), "### TCP ###");
+    let result = runtime
+        .exec_function(function_id.clone(), "TCP,10.0.0.1:8080")
+        .await;
+    match result {
+        Err(RuntimeError::GuestError(error)) => assert_eq!(error.code, "io"),
+        result => panic!("expected the guest error, got {:?}", result),
+    }
+}
@@ -0,0 +1,163 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use limes::runtime::invocation::{InvocationReport, InvocationStatus};
+use limes::runtime::runtime::Runtime;
+use limes::runtime::webhook::{self, WebhookConfig, WebhookError};
+use serde_json::Value;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn get_crate_path() -> PathBuf {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let wasm_path = Path::new(&crate_dir).join(Path::new(
+        "resources/wasm_wasi_module_test_files/wasm_compiled",
+    ));
+    wasm_path
+}
+
+// Local stand-in for the orchestrator, answering `failures` times with `status` before a 200
+#[derive(Clone, Default)]
+struct Receiver {
+    requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    failures: Arc<Mutex<usize>>,
+    status: Option<StatusCode>,
+}
+
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    receiver.requests.lock().unwrap().push((headers, body));
+    let mut failures = receiver.failures.lock().unwrap();
+    if *failures > 0 {
+        *failures -= 1;
+        return receiver.status.unwrap();
+    }
+    StatusCode::OK
+}
+
+async fn spawn_receiver(receiver: Receiver) -> String {
+    let router = Router::new()
+        .route("/callback", post(receive))
+        .with_state(receiver);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}/callback", addr)
+}
+
+fn fast_config(secret: Option<&str>) -> WebhookConfig {
+    WebhookConfig::new(
+        3,
+        Duration::from_millis(10),
+        Duration::from_millis(40),
+        secret.map(String::from),
+    )
+}
+
+fn report() -> InvocationReport {
+    InvocationReport {
+        id: "invocation".to_string(),
+        function_id: "function".to_string(),
+        status: InvocationStatus::Completed,
+        result: Some("done".to_string()),
+        error: None,
+        duration_ms: Some(3),
+        usage: None,
+    }
+}
+
+#[tokio::test]
+async fn webhook_retries_and_signs() {
+    let receiver = Receiver {
+        failures: Arc::new(Mutex::new(2)),
+        status: Some(StatusCode::SERVICE_UNAVAILABLE),
+        ..Default::default()
+    };
+    let url = spawn_receiver(receiver.clone()).await;
+
+    let client = reqwest::Client::new();
+    webhook::deliver(&client, &fast_config(Some("secret")), &url, &report())
+        .await
+        .unwrap();
+
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    let (headers, body) = &requests[2];
+    let signature = headers[webhook::SIGNATURE_HEADER].to_str().unwrap();
+    assert_eq!(
+        signature,
+        format!("sha256={}", webhook::sign("secret", body))
+    );
+    assert_eq!(headers[webhook::INVOCATION_HEADER], "invocation");
+    let body: Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(body["result"], "done");
+}
+
+#[tokio::test]
+async fn webhook_client_error_not_retried() {
+    let receiver = Receiver {
+        failures: Arc::new(Mutex::new(5)),
+        status: Some(StatusCode::BAD_REQUEST),
+        ..Default::default()
+    };
+    let url = spawn_receiver(receiver.clone()).await;
+
+    let client = reqwest::Client::new();
+    let result = webhook::deliver(&client, &fast_config(None), &url, &report()).await;
+    assert_eq!(result, Err(WebhookError::Refused(400)));
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].0.contains_key(webhook::SIGNATURE_HEADER));
+}
+
+#[tokio::test]
+async fn webhook_retries_exhausted() {
+    // Nothing listens on the port once the listener is dropped
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/callback", listener.local_addr().unwrap());
+    drop(listener);
+
+    let client = reqwest::Client::new();
+    let result = webhook::deliver(&client, &fast_config(None), &url, &report()).await;
+    assert!(matches!(result, Err(WebhookError::RetriesExhausted(4, _))));
+}
+
+#[tokio::test]
+async fn runtime_invocation_callback() {
+    let receiver = Receiver::default();
+    let url = spawn_receiver(receiver.clone()).await;
+
+    let runtime = Runtime::new()
+        .set_webhook_config(fast_config(Some("secret")))
+        .build()
+        .unwrap();
+    let bytes = std::fs::read(get_crate_path().join("exec_rust_lambda_function.wasm")).unwrap();
+    let module_id = runtime.register_module(bytes).await.unwrap();
+    let func_id = runtime
+        .init_function(module_id, Ipv4Addr::new(127, 0, 0, 1))
+        .await
+        .unwrap();
+    let invocation_id = runtime
+        .submit_function_with_callback(func_id, String::new(), None, Some(url))
+        .await
+        .unwrap();
+
+    for _ in 0..500 {
+        if !receiver.requests.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (headers, body) = &requests[0];
+    assert_eq!(headers[webhook::INVOCATION_HEADER], invocation_id.as_str());
+    assert!(headers.contains_key(webhook::SIGNATURE_HEADER));
+    let body: Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["result"], "### TEST ###");
+    assert!(body["duration_ms"].is_u64());
+}